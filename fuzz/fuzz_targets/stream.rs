@@ -0,0 +1,10 @@
+//! Feeds hostile bytes to the streaming signature check, which parses them by hand.
+//! Run with `cargo fuzz run stream`.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use sn_handover::stream;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = stream::verify_signed_vote_bytes::<u64>(data);
+});
@@ -0,0 +1,42 @@
+//! Feeds hostile bytes to a voter, as a peer on the network could.
+//! Run with `cargo fuzz run vote_msg`, any panic aborts and is reported as a crash.
+#![no_main]
+
+use std::collections::BTreeSet;
+
+use libfuzzer_sys::fuzz_target;
+use rand::{rngs::StdRng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use sn_handover::{CompactVoteMsg, HandoverState, Proposal, Result, VoteMsg};
+
+#[derive(Clone, Copy, Debug, Eq, PartialOrd, Ord, PartialEq, Serialize, Deserialize)]
+struct FuzzProposal(u64);
+
+impl Proposal for FuzzProposal {
+    fn validate(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+// the same voters every run, so that signatures found by the fuzzer stay valid
+fn voter() -> HandoverState<FuzzProposal> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut procs = Vec::from_iter(
+        (0..4).map(|_| HandoverState::<FuzzProposal>::random(&mut rng, Default::default())),
+    );
+    let voters = BTreeSet::from_iter(procs.iter().map(HandoverState::public_key));
+    let mut proc = procs.remove(0);
+    proc.voters = voters;
+    proc
+}
+
+fuzz_target!(|data: &[u8]| {
+    let mut proc = voter();
+    if let Ok(msg) = VoteMsg::<FuzzProposal>::from_bytes(data) {
+        let _ = proc.handle_vote_msg(msg);
+    }
+    if let Ok(msg) = bincode::deserialize::<CompactVoteMsg<FuzzProposal>>(data) {
+        let _ = msg.expand();
+        let _ = proc.handle_compact_vote_msg(msg);
+    }
+});
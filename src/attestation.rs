@@ -0,0 +1,109 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    proposal_hash, Generation, Hash, KeyVerifier, Proposal, ProtocolError, PublicKey, QuorumPolicy,
+    Result, Signature, Snapshot, VoteVerifier,
+};
+
+// Attestations can't be passed off as votes, or votes as attestations
+const ATTESTATION_PREFIX: &[u8] = b"sn_handover/section-state/";
+
+/// What a section agrees on once generation `gen` is decided: who voted and what they decided.
+/// Every honest elder that decided the generation holds the same, whichever votes it saw.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct SectionState {
+    pub gen: Generation,
+    pub voters: BTreeSet<PublicKey>,
+    pub decided: Hash,
+}
+
+impl SectionState {
+    pub(crate) fn decided<T: Serialize + Proposal>(
+        gen: Generation,
+        voters: BTreeSet<PublicKey>,
+        proposal: &T,
+    ) -> Result<Self> {
+        Ok(Self {
+            gen,
+            voters,
+            decided: proposal_hash(proposal)?,
+        })
+    }
+
+    pub fn hash(&self) -> Result<Hash> {
+        Ok(Hash::of(&bincode::serialize(self)?))
+    }
+
+    pub fn signing_bytes(&self) -> Result<Vec<u8>> {
+        Ok([ATTESTATION_PREFIX, &bincode::serialize(self)?].concat())
+    }
+}
+
+/// An elder vouching for the state of the section at a generation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateAttestation {
+    pub state: SectionState,
+    pub voter: PublicKey,
+    pub sig: Signature,
+}
+
+/// The state of the section co-signed by a quorum of its voters,
+/// operators keep it along with their snapshots as a consistent point to recover from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SectionStateProof {
+    pub state: SectionState,
+    pub signatures: BTreeMap<PublicKey, Signature>,
+}
+
+impl SectionStateProof {
+    pub fn verify(&self, policy: &QuorumPolicy, voters: &BTreeSet<PublicKey>) -> Result<()> {
+        self.verify_with(&KeyVerifier, policy, voters)
+    }
+
+    /// Check the proof against the voters we know the section had at its generation
+    pub fn verify_with(
+        &self,
+        verifier: &dyn VoteVerifier,
+        policy: &QuorumPolicy,
+        voters: &BTreeSet<PublicKey>,
+    ) -> Result<()> {
+        if &self.state.voters != voters {
+            return Err(invalid(format!(
+                "attested voters {:?} are not {:?}",
+                self.state.voters, voters
+            )));
+        }
+        let msg = self.state.signing_bytes()?;
+        for (signer, sig) in self.signatures.iter() {
+            if !voters.contains(signer) {
+                return Err(ProtocolError::NonMember {
+                    public_key: *signer,
+                    members: voters.clone(),
+                }
+                .into());
+            }
+            verifier.verify(signer, &msg, sig)?;
+        }
+        if !policy.is_quorum(policy.weight_of(self.signatures.keys()), voters) {
+            return Err(invalid(format!(
+                "only {} of the voters signed",
+                self.signatures.len()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Whether the snapshot was taken at the attested state, it is then a consistent backup
+    pub fn covers<T: Ord + Serialize + Proposal>(&self, snapshot: &Snapshot<T>) -> Result<bool> {
+        let decided = snapshot.consensus.as_ref().map(proposal_hash).transpose()?;
+        Ok(snapshot.gen == self.state.gen
+            && snapshot.voters == self.state.voters
+            && decided == Some(self.state.decided))
+    }
+}
+
+fn invalid(reason: String) -> crate::Error {
+    ProtocolError::InvalidStateProof(reason).into()
+}
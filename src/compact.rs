@@ -0,0 +1,185 @@
+//! A wire form of signed votes where each distinct nested vote is sent once.
+//!
+//! Merge and SuperMajority ballots embed the votes they're built on, so every late vote
+//! repeats the same proposals once per voter that merged them. Signatures cover the full
+//! nested encoding, we can't drop nested votes from a signed vote, but we can send each
+//! distinct one in a table and refer to it by its index everywhere else.
+//! The current wire version sends every message in this form, see `wire::WIRE_V3`.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::stream::MAX_BALLOT_DEPTH;
+use crate::{
+    Ballot, CorrelationId, Generation, Priority, ProtocolError, PublicKey, Result, Signature,
+    SignedVote, Vote, VoteMsg,
+};
+
+/// Compact votes expanding to more votes than this, over all their entries, are refused
+pub const MAX_EXPANDED_VOTES: u64 = 1 << 16;
+
+/// A ballot whose nested votes are indices into the table of a `CompactVote`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CompactBallot<T> {
+    Propose(T),
+    Merge(Vec<u32>),
+    SuperMajority(Vec<u32>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompactEntry<T> {
+    pub gen: Generation,
+    pub ballot: CompactBallot<T>,
+    pub extensions: BTreeMap<u16, Vec<u8>>,
+    pub voter: PublicKey,
+    pub sig: Signature,
+}
+
+/// The distinct votes of a signed vote, each after the votes it nests, the outer vote last
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompactVote<T> {
+    pub entries: Vec<CompactEntry<T>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompactVoteMsg<T> {
+    pub vote: CompactVote<T>,
+    pub dest: PublicKey,
+    pub correlation_id: Option<CorrelationId>,
+    pub priority: Priority,
+}
+
+impl<T: Clone + Ord + Serialize> SignedVote<T> {
+    /// This vote with every distinct nested vote listed once
+    pub fn compact(&self) -> CompactVote<T> {
+        let mut compact = CompactVote {
+            entries: Vec::new(),
+        };
+        let mut indices = BTreeMap::new();
+        compact.push(self, &mut indices);
+        compact
+    }
+}
+
+impl<T: Clone + Ord + Serialize> CompactVote<T> {
+    // Post-order, so an entry only ever refers to entries before it
+    #[allow(clippy::clone_on_copy)] // signatures are only Copy with bad_crypto
+    fn push<'a>(
+        &mut self,
+        signed_vote: &'a SignedVote<T>,
+        indices: &mut BTreeMap<&'a SignedVote<T>, u32>,
+    ) -> u32 {
+        if let Some(index) = indices.get(signed_vote) {
+            return *index;
+        }
+        let mut nested = |votes: &'a BTreeSet<SignedVote<T>>| {
+            Vec::from_iter(votes.iter().map(|v| self.push(v, indices)))
+        };
+        let ballot = match &signed_vote.vote.ballot {
+            Ballot::Propose(proposal) => CompactBallot::Propose(proposal.clone()),
+            Ballot::Merge(votes) => CompactBallot::Merge(nested(votes)),
+            Ballot::SuperMajority(votes) => CompactBallot::SuperMajority(nested(votes)),
+        };
+        let index = self.entries.len() as u32;
+        self.entries.push(CompactEntry {
+            gen: signed_vote.vote.gen,
+            ballot,
+            extensions: signed_vote.vote.extensions.clone(),
+            voter: signed_vote.voter,
+            sig: signed_vote.sig.clone(),
+        });
+        indices.insert(signed_vote, index);
+        index
+    }
+
+    /// The signed vote back in its full form, its signatures are left for validation to check.
+    /// Entries are refused before we clone them once the votes expanded so far, nested votes
+    /// counted each time they're referred to, exceed `MAX_EXPANDED_VOTES`, or nest deeper
+    /// than `MAX_BALLOT_DEPTH`: a few bytes of references could otherwise expand to millions.
+    #[allow(clippy::clone_on_copy)] // signatures are only Copy with bad_crypto
+    pub fn expand(&self) -> Result<SignedVote<T>> {
+        let mut expanded: Vec<SignedVote<T>> = Vec::with_capacity(self.entries.len());
+        let mut costs: Vec<(u64, usize)> = Vec::with_capacity(self.entries.len()); // (votes, depth)
+        let mut total: u64 = 0;
+        for (index, entry) in self.entries.iter().enumerate() {
+            let refers_to = |i: &u32| {
+                ProtocolError::malformed(format!("entry {} refers to entry {}", index, i))
+            };
+            let (mut votes, mut depth) = (1u64, 0);
+            if let CompactBallot::Merge(indices) | CompactBallot::SuperMajority(indices) =
+                &entry.ballot
+            {
+                for i in indices {
+                    // only the entries before this one have a cost yet
+                    let (nested_votes, nested_depth) =
+                        costs.get(*i as usize).ok_or_else(|| refers_to(i))?;
+                    votes = votes.saturating_add(*nested_votes);
+                    depth = depth.max(nested_depth + 1);
+                }
+            }
+            total = total.saturating_add(votes);
+            if total > MAX_EXPANDED_VOTES {
+                return Err(ProtocolError::malformed(format!(
+                    "expands to more than {} votes",
+                    MAX_EXPANDED_VOTES
+                )));
+            }
+            if depth > MAX_BALLOT_DEPTH {
+                return Err(ProtocolError::malformed(format!(
+                    "ballot nested deeper than {}",
+                    MAX_BALLOT_DEPTH
+                )));
+            }
+            costs.push((votes, depth));
+
+            let nested = |indices: &[u32]| -> Result<BTreeSet<SignedVote<T>>> {
+                let mut votes = BTreeSet::new();
+                for i in indices {
+                    let vote = expanded.get(*i as usize).ok_or_else(|| refers_to(i))?;
+                    votes.insert(vote.clone());
+                }
+                Ok(votes)
+            };
+            let ballot = match &entry.ballot {
+                CompactBallot::Propose(proposal) => Ballot::Propose(proposal.clone()),
+                CompactBallot::Merge(indices) => Ballot::Merge(nested(indices)?),
+                CompactBallot::SuperMajority(indices) => Ballot::SuperMajority(nested(indices)?),
+            };
+            expanded.push(SignedVote {
+                vote: Vote {
+                    gen: entry.gen,
+                    ballot,
+                    extensions: entry.extensions.clone(),
+                },
+                voter: entry.voter,
+                sig: entry.sig.clone(),
+            });
+        }
+        expanded
+            .pop()
+            .ok_or_else(|| ProtocolError::malformed("no vote"))
+    }
+}
+
+impl<T: Clone + Ord + Serialize> VoteMsg<T> {
+    pub fn compact(&self) -> CompactVoteMsg<T> {
+        CompactVoteMsg {
+            vote: self.vote.compact(),
+            dest: self.dest,
+            correlation_id: self.correlation_id,
+            priority: self.priority,
+        }
+    }
+}
+
+impl<T: Clone + Ord + Serialize> CompactVoteMsg<T> {
+    pub fn expand(&self) -> Result<VoteMsg<T>> {
+        Ok(VoteMsg {
+            vote: self.vote.expand()?,
+            dest: self.dest,
+            correlation_id: self.correlation_id,
+            priority: self.priority,
+        })
+    }
+}
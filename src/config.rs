@@ -0,0 +1,103 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ConfigHandshake, Hash, Limits, ProtocolDescriptor, QuorumPolicy, Result, SigningDomain,
+};
+
+// Fingerprints can't be passed off as any other hash of ours
+const FINGERPRINT_PREFIX: &[u8] = b"sn_handover/config/";
+
+/// Below this many elders we can't tolerate a faulty one, BFT needs n >= 3f + 1
+pub const BFT_MINIMUM_ELDERS: usize = 4;
+
+/// Tunables of a HandoverState, the defaults match the protocol with no extra behaviour
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Config {
+    /// After a (re)start, how long we only collect votes and serve anti-entropy
+    /// before we start voting ourselves. Our view of the generation may be stale until
+    /// our peers brought us up to date, voting on it could make us contradict a vote
+    /// we cast before the restart.
+    pub startup_grace_period: Duration,
+    /// How long after the start of a generation (by our local clock) we accept fresh proposals,
+    /// past it we only help converge on the proposals already in play. `None` never closes a round.
+    pub generation_deadline: Option<Duration>,
+    /// Added to the deadline to make up for our clock running ahead of our peers'
+    pub clock_skew_tolerance: Duration,
+    /// With fewer than `BFT_MINIMUM_ELDERS` elders (test networks, bootstrapping sections)
+    /// there is no fault to tolerate: decide as soon as every elder voted for the same proposals
+    /// instead of going through the super majority rounds.
+    pub unanimity_below_bft_minimum: bool,
+    /// Run the rounds as a drill: votes are signed in the rehearsal domain and the result is
+    /// a non-binding `RehearsalProof`, never a `Decision`, so the elder set can't change
+    pub rehearsal: bool,
+    /// How much agreement decides, see `HandoverState::set_quorum_policy`
+    pub quorum_policy: QuorumPolicy,
+    /// How many terminated rounds we keep, in `HandoverState::history` and `history_stats`,
+    /// `None` keeps them all. Peers lagging further behind can't catch up from our history.
+    pub stats_retention: Option<usize>,
+    /// How many votes, decision proofs and nested votes included, a catch up or anti-entropy
+    /// carries at most before the rest is left to a continuation. A page always carries at
+    /// least one decision or vote, whatever its size. `None` sends everything at once.
+    pub catch_up_page_size: Option<usize>,
+    /// How many bytes of a message `HandoverState::handle_vote_msg_bytes` reads at most,
+    /// `None` reads it whatever its size
+    pub max_message_size: Option<u64>,
+    /// The genesis of an instance spawned by a split, see `HandoverState::split`.
+    /// Our votes are signed in the domain of the split, `None` for an instance that wasn't.
+    pub genesis: Option<Hash>,
+}
+
+impl Config {
+    /// The protocol these settings speak, for peers to check they can vote with us
+    pub fn protocol_descriptor(&self) -> ProtocolDescriptor {
+        ProtocolDescriptor {
+            quorum_rule: self.quorum_policy.rule(),
+            limits: Limits {
+                max_message_size: self.max_message_size,
+                catch_up_page_size: self.catch_up_page_size.map(|size| size as u64),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    /// The domain our votes are signed in
+    pub fn signing_domain(&self) -> SigningDomain {
+        match (self.rehearsal, self.genesis) {
+            (true, _) => SigningDomain::Rehearsal,
+            (false, Some(genesis)) => SigningDomain::Split(genesis),
+            (false, None) => SigningDomain::Live,
+        }
+    }
+
+    /// Identifies the rules we vote by, the settings our peers must share, paging and the
+    /// limits of the descriptor among them. Local tunables (grace period, deadlines,
+    /// retention) are left out.
+    pub fn fingerprint(&self) -> Result<Hash> {
+        let shared = (
+            self.protocol_descriptor(),
+            &self.quorum_policy,
+            self.unanimity_below_bft_minimum,
+            self.rehearsal,
+            self.genesis,
+        );
+        Ok(Hash::of_parts([
+            FINGERPRINT_PREFIX,
+            &bincode::serialize(&shared)?,
+        ]))
+    }
+
+    /// What to send peers when connecting, see `ConfigHandshake::check`
+    pub fn handshake(&self) -> Result<ConfigHandshake> {
+        Ok(ConfigHandshake {
+            fingerprint: self.fingerprint()?,
+            descriptor: self.protocol_descriptor(),
+            quorum_policy: self.quorum_policy.clone(),
+            unanimity_below_bft_minimum: self.unanimity_below_bft_minimum,
+            rehearsal: self.rehearsal,
+            genesis: self.genesis,
+        })
+    }
+}
@@ -0,0 +1,372 @@
+//! Checks an implementation of the handover protocol, or a backend plugged into this one
+//! (a `Proposal`, a signer), can run against itself to show it agrees with this crate.
+//!
+//! Each check returns `ConfigError::NonConformant` naming what diverged.
+
+use std::collections::{BTreeSet, VecDeque};
+
+use rand::{CryptoRng, Rng};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::hash::{rank, round_seed};
+use crate::{
+    proposal_hash, Ballot, ConfigError, HandoverState, Hash, KeyVerifier, Proposal, PublicKey,
+    Result, SecretKey, Signature, SignedVote, SigningDomain, Vote, VoteMsg, VoteSigner,
+    VoteVerifier,
+};
+use core::fmt::Debug;
+
+/// (input, expected SHA3-256 in hex) pairs `Hash::of` must reproduce
+pub const HASH_VECTORS: &[(&[u8], &str)] = &[
+    (
+        b"",
+        "a7ffc6f8bf1ed76651c14756a061d662f580ff4de43b49fa82d80a4b80f8434a",
+    ),
+    (
+        b"abc",
+        "3a985da74fe225b2045c172d6bd390bd855f086e3e9d525b46bfe24511431532",
+    ),
+];
+
+/// The vectors of the encodings voters sign and break ties with, in the signature scheme we're
+/// built with, see `check_vote_vectors`
+pub struct SchemeVectors {
+    /// The encoded voter and signature of the votes nested in the ballots, only their
+    /// encoding matters: the signature doesn't verify
+    pub voter: &'static str,
+    pub signature: &'static str,
+    /// (ballot, expected SHA3-256 in hex of `Vote::to_bytes`) pairs
+    pub votes: [(&'static str, &'static str); 3],
+    /// Expected seed of generation 7 with the voter alone, in hex
+    pub round_seed: &'static str,
+}
+
+#[cfg(feature = "bad_crypto")]
+pub const SCHEME_VECTORS: SchemeVectors = SchemeVectors {
+    voter: "f400927857aaf641",
+    signature: "d863c32ae42a502b",
+    votes: [
+        (
+            "propose",
+            "a774bd0463eeefd05b11ef46ec76e3ea01cd25b578f3327647e78bb06db2a3e9",
+        ),
+        (
+            "merge",
+            "e481bf78f5d2751baff6a818cf28b534a47ddd1f58fbc1450bf1bf68a2377f70",
+        ),
+        (
+            "super_majority",
+            "2005fda03fcbf8d9080cb43f5f1c41fb24b98716f3fa361c143050512e971c57",
+        ),
+    ],
+    round_seed: "1792d889fe061a4a2ae88f1d2475b557fc95d541acb3cdb264e6b5d098af04ab",
+};
+#[cfg(feature = "blsttc")]
+pub const SCHEME_VECTORS: SchemeVectors = SchemeVectors {
+    voter: "936a01a55369d7c9a0e8ea7b37f7465c605498b249505f78234371429cec0833d656e7fde54439a7ba8625ecb67f774c",
+    signature: "874dd1476a3c49ed9374282d34de31bce752d39a4e3693ca08fb06e549600f5924e280d8e32d82b7ffd04b3df5c3d2d80c5edf5153ee98c3f00ee430860e4304f8cc571d7ef3a2735c276e1211f86e5d9e1da0749c3b532a908f7e116710d2fc",
+    votes: [
+        ("propose", "a774bd0463eeefd05b11ef46ec76e3ea01cd25b578f3327647e78bb06db2a3e9"),
+        ("merge", "ca1b8ba249c63487c84ad4705452652a9796c78fd5efe50fc4d329dffe580b4b"),
+        ("super_majority", "9269a299e746dfd54dfd14909eaa749f62cb4f5bcd8d69ff3da5c067bba9f93c"),
+    ],
+    round_seed: "344d5a3d32002b2c69258c429689c4681c9767dff35de51b701335270cdfe0fc",
+};
+#[cfg(feature = "ed25519")]
+pub const SCHEME_VECTORS: SchemeVectors = SchemeVectors {
+    voter: "2000000000000000e79a4e621583674785585866dc854fb85e2b5d208693483a4cdecd901f43d85d",
+    signature: "39b5ff75d4bce44dc711b0b962734eaf1332fce4c2f16e152d7af687c9f7b69c10287e34a357e8d5a2d91a683c225dd1f433639e55905b51ad1db771ab26af05",
+    votes: [
+        ("propose", "a774bd0463eeefd05b11ef46ec76e3ea01cd25b578f3327647e78bb06db2a3e9"),
+        ("merge", "4025b1fc45ff6cc690a8b59b1f79a7d363c2e16848bebc60f9dd77bf10ccc18d"),
+        ("super_majority", "090fb2bb734e12e5c7deb4ea8459395bb4d79df5a54d7eca5c4444d1648697f2"),
+    ],
+    round_seed: "d7c418b5a7f7b253f9bc685923c1205fee49e38eb5e7c3fc22ec47684f78b59a",
+};
+
+/// Expected `rank` in hex of the proposal hashed H(b"abc") under the seed H(b""), in any scheme
+pub const RANK_VECTOR: &str = "0311212feb67fc564595c623b7be4a4fd9fbb21f801d0f7bfb86962c29d8092b";
+
+/// Scenarios give up after this many messages, a conformant implementation decides well before
+pub const MAX_SCENARIO_MESSAGES: usize = 10_000;
+
+fn non_conformant(check: &'static str, reason: impl Into<String>) -> crate::Error {
+    ConfigError::NonConformant {
+        check,
+        reason: reason.into(),
+    }
+    .into()
+}
+
+pub fn check_hash_vectors() -> Result<()> {
+    for (input, expected) in HASH_VECTORS {
+        let hash = hex::encode(Hash::of(input).as_bytes());
+        if &hash != expected {
+            return Err(non_conformant(
+                "hash_vectors",
+                format!("H({:?}) = {} != {}", input, hash, expected),
+            ));
+        }
+    }
+    Ok(())
+}
+
+// The proposals of the vote vectors, encoded as the u64 they wrap
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+struct VectorProposal(u64);
+
+impl Proposal for VectorProposal {
+    fn validate(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+fn decode_vector<V: DeserializeOwned>(encoded: &str) -> Result<V> {
+    let bytes =
+        hex::decode(encoded).map_err(|err| non_conformant("vote_vectors", err.to_string()))?;
+    bincode::deserialize(&bytes).map_err(|err| non_conformant("vote_vectors", err.to_string()))
+}
+
+/// The bytes voters sign, and the seed and rank they break ties with, must be those of
+/// `SCHEME_VECTORS` and `RANK_VECTOR`. The votes are of generation 7: a proposal of 1,
+/// a merge of the proposals of 1 and 2, and a super majority of that merge.
+#[allow(clippy::clone_on_copy)]
+pub fn check_vote_vectors() -> Result<()> {
+    let voter: PublicKey = decode_vector(SCHEME_VECTORS.voter)?;
+    let sig: Signature = decode_vector(SCHEME_VECTORS.signature)?;
+    let propose = |proposal| Vote {
+        gen: 7,
+        ballot: Ballot::Propose(VectorProposal(proposal)),
+        extensions: Default::default(),
+    };
+    let signed = |vote| SignedVote {
+        vote,
+        voter,
+        sig: sig.clone(),
+    };
+    let merge = Vote {
+        gen: 7,
+        ballot: Ballot::Merge(BTreeSet::from_iter([
+            signed(propose(1)),
+            signed(propose(2)),
+        ])),
+        extensions: Default::default(),
+    };
+    let super_majority = Vote {
+        gen: 7,
+        ballot: Ballot::SuperMajority(BTreeSet::from_iter([signed(merge.clone())])),
+        extensions: Default::default(),
+    };
+
+    let votes = [propose(1), merge, super_majority];
+    for ((ballot, expected), vote) in SCHEME_VECTORS.votes.iter().zip(votes) {
+        let hash = hex::encode(Hash::of(&vote.to_bytes()?).as_bytes());
+        if &hash != expected {
+            return Err(non_conformant(
+                "vote_vectors",
+                format!("H(to_bytes({})) = {} != {}", ballot, hash, expected),
+            ));
+        }
+    }
+
+    let seed = round_seed(7, &BTreeSet::from_iter([voter]))?;
+    if hex::encode(seed.as_bytes()) != SCHEME_VECTORS.round_seed {
+        return Err(non_conformant(
+            "vote_vectors",
+            format!(
+                "round_seed(7) = {} != {}",
+                hex::encode(seed.as_bytes()),
+                SCHEME_VECTORS.round_seed
+            ),
+        ));
+    }
+    let rank = rank(&Hash::of(b""), &Hash::of(b"abc"));
+    if hex::encode(rank.as_bytes()) != RANK_VECTOR {
+        return Err(non_conformant(
+            "vote_vectors",
+            format!("rank = {} != {}", hex::encode(rank.as_bytes()), RANK_VECTOR),
+        ));
+    }
+    Ok(())
+}
+
+/// Proposals must validate, encode deterministically and survive a round trip through their encoding,
+/// otherwise voters may disagree on which proposal a vote is for
+pub fn check_proposal_encoding<T>(samples: &[T]) -> Result<()>
+where
+    T: Debug + PartialEq + Serialize + DeserializeOwned + Proposal,
+{
+    for sample in samples {
+        sample.validate()?;
+
+        let hash = proposal_hash(sample)?;
+        if proposal_hash(sample)? != hash {
+            return Err(non_conformant(
+                "proposal_encoding",
+                format!("encoding of {:?} is not deterministic", sample),
+            ));
+        }
+
+        let decoded: T = bincode::deserialize(&bincode::serialize(sample)?)?;
+        if &decoded != sample || proposal_hash(&decoded)? != hash {
+            return Err(non_conformant(
+                "proposal_encoding",
+                format!("{:?} changed through an encoding round trip", sample),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Votes signed by `signer` must verify with `verifier`, and stop verifying once tampered with
+pub fn check_signer<T>(
+    signer: impl VoteSigner + 'static,
+    verifier: &dyn VoteVerifier,
+    proposal: T,
+) -> Result<()>
+where
+    T: Clone + Copy + Debug + Ord + Serialize + DeserializeOwned + Proposal,
+{
+    let state = HandoverState::<T>::from(signer, 0, Default::default());
+    let signed_vote = state.sign_vote(Vote {
+        gen: 0,
+        ballot: Ballot::Propose(proposal),
+        extensions: Default::default(),
+    })?;
+    signed_vote.validate_signature_with(verifier, SigningDomain::Live)?;
+
+    let tampered = SignedVote {
+        vote: Vote {
+            gen: signed_vote.vote.gen + 1,
+            ..signed_vote.vote.clone()
+        },
+        ..signed_vote
+    };
+    if tampered
+        .validate_signature_with(verifier, SigningDomain::Live)
+        .is_ok()
+    {
+        return Err(non_conformant(
+            "signer",
+            "signature still verifies on a tampered vote",
+        ));
+    }
+    Ok(())
+}
+
+/// Delivers messages in the order they were sent until no more are produced
+fn run_scenario<T>(
+    check: &'static str,
+    procs: &mut [HandoverState<T>],
+    msgs: Vec<VoteMsg<T>>,
+) -> Result<()>
+where
+    T: Clone + Copy + Debug + Ord + Serialize + DeserializeOwned + Proposal,
+{
+    let mut queue = VecDeque::from(msgs);
+    let mut delivered = 0;
+    while let Some(msg) = queue.pop_front() {
+        delivered += 1;
+        if delivered > MAX_SCENARIO_MESSAGES {
+            return Err(non_conformant(check, "scenario did not terminate"));
+        }
+        if let Some(proc) = procs.iter_mut().find(|p| p.public_key() == msg.dest) {
+            queue.extend(proc.handle_vote_msg(msg)?.msgs);
+        }
+    }
+    Ok(())
+}
+
+fn network<T>(n: usize, mut rng: impl Rng + CryptoRng) -> Vec<HandoverState<T>>
+where
+    T: Clone + Copy + Debug + Ord + Serialize + DeserializeOwned + Proposal,
+{
+    let mut procs =
+        Vec::from_iter((0..n).map(|_| HandoverState::random(&mut rng, Default::default())));
+    let voters = BTreeSet::from_iter(procs.iter().map(HandoverState::public_key));
+    for proc in procs.iter_mut() {
+        proc.voters = voters.clone();
+    }
+    procs
+}
+
+/// Small networks must all decide the same proposal, one that was proposed
+pub fn check_scenarios<T>(samples: &[T], mut rng: impl Rng + CryptoRng) -> Result<()>
+where
+    T: Clone + Copy + Debug + Ord + Serialize + DeserializeOwned + Proposal,
+{
+    let first = *samples
+        .first()
+        .ok_or_else(|| non_conformant("scenarios", "no sample proposals given"))?;
+
+    // a lone voter decides its own proposal
+    let mut procs = network(1, &mut rng);
+    let msgs = match procs.first_mut() {
+        Some(lone) => lone.propose(first)?,
+        None => vec![],
+    };
+    run_scenario("scenario_single_voter", &mut procs, msgs)?;
+    let decided = procs.first().and_then(|lone| lone.consensus);
+    if decided.map(|c| proposal_hash(&c)).transpose()? != Some(proposal_hash(&first)?) {
+        return Err(non_conformant(
+            "scenario_single_voter",
+            "a lone voter did not decide its own proposal",
+        ));
+    }
+
+    // voters that agree decide what they agree on, voters that don't still decide together
+    for (check, proposals) in [
+        ("scenario_unanimous", vec![first; 4]),
+        (
+            "scenario_split",
+            Vec::from_iter(samples.iter().copied().cycle().take(4)),
+        ),
+    ] {
+        let mut procs = network(proposals.len(), &mut rng);
+        let mut msgs = Vec::new();
+        for (proc, proposal) in procs.iter_mut().zip(proposals.iter()) {
+            msgs.extend(proc.propose(*proposal)?);
+        }
+        run_scenario(check, &mut procs, msgs)?;
+
+        let proposed = proposals
+            .iter()
+            .map(proposal_hash)
+            .collect::<Result<BTreeSet<_>>>()?;
+        let decided = procs
+            .iter()
+            .map(|p| p.consensus.map(|c| proposal_hash(&c)).transpose())
+            .collect::<Result<BTreeSet<_>>>()?;
+        match Vec::from_iter(decided).as_slice() {
+            [Some(hash)] if proposed.contains(hash) => (),
+            [other] => {
+                return Err(non_conformant(
+                    check,
+                    format!("decided {:?} which is not one of {:?}", other, proposed),
+                ))
+            }
+            decided => {
+                return Err(non_conformant(
+                    check,
+                    format!("voters decided differently: {:?}", decided),
+                ))
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Runs every check of this module
+pub fn run_all<T>(samples: &[T], mut rng: impl Rng + CryptoRng) -> Result<()>
+where
+    T: Clone + Copy + Debug + Ord + Serialize + DeserializeOwned + Proposal,
+{
+    check_hash_vectors()?;
+    check_vote_vectors()?;
+    check_proposal_encoding(samples)?;
+    if let Some(proposal) = samples.first() {
+        check_signer(SecretKey::random(&mut rng), &KeyVerifier, *proposal)?;
+    }
+    check_scenarios(samples, rng)
+}
@@ -0,0 +1,222 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::hash::{self, Hash};
+use crate::signer::KeyVerifier;
+use crate::{
+    Ballot, Config, Generation, Proposal, ProtocolError, PublicKey, QuorumPolicy, Result,
+    SignedVote, SigningDomain, VoteVerifier, BFT_MINIMUM_ELDERS,
+};
+use core::fmt::Debug;
+
+/// A decided proposal along with its proof,
+/// the super majority of super majority votes that decided it
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Decision<T>
+where
+    T: Ord + Serialize,
+{
+    pub gen: Generation,
+    pub proposal: T,
+    pub votes: BTreeSet<SignedVote<T>>,
+}
+
+/// Gossiped once we decided, observers and lagging elders accept the decision
+/// after checking its proof instead of going through the vote exchange
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct DecisionAnnounce<T>
+where
+    T: Ord + Serialize,
+{
+    pub decision: Decision<T>,
+    pub dest: PublicKey,
+}
+
+/// The result of a rehearsal, carries the would-be decision signed in the rehearsal domain.
+/// None of its votes verify as live votes, it can't be used to hand over.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct RehearsalProof<T>
+where
+    T: Ord + Serialize,
+{
+    pub rehearsed: Decision<T>,
+}
+
+impl<'de, T> RehearsalProof<T>
+where
+    T: Clone + Copy + Debug + Ord + Serialize + Deserialize<'de> + Proposal,
+{
+    /// Checks the drill went through like a live round would have
+    pub fn verify(&self, voters: &BTreeSet<PublicKey>) -> Result<()> {
+        self.rehearsed.verify_in(
+            &KeyVerifier,
+            &QuorumPolicy::default(),
+            SigningDomain::Rehearsal,
+            voters,
+        )
+    }
+}
+
+fn invalid(reason: impl Into<String>) -> crate::Error {
+    ProtocolError::InvalidDecision(reason.into()).into()
+}
+
+// The proposal sets of `votes` with the distinct voters behind each
+fn count_voters<'a, 'de, T, I>(votes: I) -> Result<BTreeMap<BTreeSet<Hash>, BTreeSet<PublicKey>>>
+where
+    T: Clone + Copy + Debug + Ord + Serialize + Deserialize<'de> + Proposal + 'a,
+    I: IntoIterator<Item = &'a SignedVote<T>>,
+{
+    let mut count: BTreeMap<BTreeSet<Hash>, BTreeSet<PublicKey>> = Default::default();
+    for vote in votes {
+        count
+            .entry(vote.proposal_set()?)
+            .or_default()
+            .insert(vote.voter);
+    }
+    Ok(count)
+}
+
+impl<'de, T> Decision<T>
+where
+    T: Clone + Copy + Debug + Ord + Serialize + Deserialize<'de> + Proposal,
+{
+    /// Checks the votes decide this proposal among `voters`, the elders of the generation,
+    /// without any other state. This is what nodes outside the elder set rely on.
+    pub fn verify(&self, voters: &BTreeSet<PublicKey>) -> Result<()> {
+        self.verify_with(&KeyVerifier, &QuorumPolicy::default(), voters)
+    }
+
+    /// Same as `verify`, checking the signatures with `verifier` and the quorums with `policy`
+    pub fn verify_with(
+        &self,
+        verifier: &dyn VoteVerifier,
+        policy: &QuorumPolicy,
+        voters: &BTreeSet<PublicKey>,
+    ) -> Result<()> {
+        self.verify_in(verifier, policy, SigningDomain::Live, voters)
+    }
+
+    /// Same as `verify_with`, for votes signed in `domain`, e.g. those of a split instance
+    pub fn verify_in(
+        &self,
+        verifier: &dyn VoteVerifier,
+        policy: &QuorumPolicy,
+        domain: SigningDomain,
+        voters: &BTreeSet<PublicKey>,
+    ) -> Result<()> {
+        self.check(verifier, policy, domain, voters, false)
+    }
+
+    /// Checks the decision by the rules of elders running with `config`: its quorum policy,
+    /// its signing domain and, if they allow it, unanimity below `BFT_MINIMUM_ELDERS`
+    pub fn verify_for(
+        &self,
+        verifier: &dyn VoteVerifier,
+        config: &Config,
+        voters: &BTreeSet<PublicKey>,
+    ) -> Result<()> {
+        self.check(
+            verifier,
+            &config.quorum_policy,
+            config.signing_domain(),
+            voters,
+            config.unanimity_below_bft_minimum,
+        )
+    }
+
+    fn check(
+        &self,
+        verifier: &dyn VoteVerifier,
+        policy: &QuorumPolicy,
+        domain: SigningDomain,
+        voters: &BTreeSet<PublicKey>,
+        unanimity_below_bft_minimum: bool,
+    ) -> Result<()> {
+        let is_super_majority =
+            |backers: &BTreeSet<PublicKey>| policy.is_quorum(policy.weight_of(backers), voters);
+
+        for vote in self.votes.iter().flat_map(SignedVote::unpack_votes) {
+            if vote.vote.gen != self.gen {
+                return Err(ProtocolError::VoteWithInvalidGeneration {
+                    vote_gen: vote.vote.gen,
+                    gen: self.gen,
+                }
+                .into());
+            }
+            if !voters.contains(&vote.voter) {
+                return Err(ProtocolError::NonMember {
+                    public_key: vote.voter,
+                    members: voters.clone(),
+                }
+                .into());
+            }
+            vote.validate_signature_with(verifier, domain)?;
+        }
+
+        let deciders = BTreeSet::from_iter(self.votes.iter().map(|v| v.voter));
+        if deciders.len() != self.votes.len() {
+            return Err(invalid("a voter appears more than once"));
+        }
+
+        // too few elders to tolerate a fault, every one of them agreeing decides
+        let unanimous = unanimity_below_bft_minimum
+            && voters.len() < BFT_MINIMUM_ELDERS
+            && &deciders == voters
+            && count_voters(&self.votes)?.len() == 1;
+        if unanimous {
+            return self.verify_winner(policy, voters);
+        }
+
+        for vote in self.votes.iter() {
+            let seen = match &vote.vote.ballot {
+                Ballot::SuperMajority(seen) => seen,
+                _ => return Err(invalid(format!("{:?} is not a super majority", vote))),
+            };
+            // like the elders accepting the ballot, any super majority among the votes it saw
+            // backs it: the ballot carries every proposal seen, the minority ones included
+            let backers = count_voters(seen.iter().flat_map(SignedVote::unpack_votes))?
+                .into_values()
+                .max_by_key(|voters| policy.weight_of(voters))
+                .unwrap_or_default();
+            if !is_super_majority(&backers) {
+                return Err(invalid(format!(
+                    "{:?} is not backed by a super majority",
+                    vote
+                )));
+            }
+        }
+
+        let (_, deciders) = count_voters(&self.votes)?
+            .into_iter()
+            .max_by_key(|(_, voters)| policy.weight_of(voters))
+            .unwrap_or_default();
+        if !is_super_majority(&deciders) {
+            return Err(invalid(
+                "the votes are not a super majority over super majorities",
+            ));
+        }
+
+        self.verify_winner(policy, voters)
+    }
+
+    // The proposals the votes are for resolve to our proposal
+    fn verify_winner(&self, policy: &QuorumPolicy, voters: &BTreeSet<PublicKey>) -> Result<()> {
+        let (winning_proposals, _) = count_voters(&self.votes)?
+            .into_iter()
+            .max_by_key(|(_, voters)| policy.weight_of(voters))
+            .unwrap_or_default();
+        let seed = hash::round_seed(self.gen, voters)?;
+        let winner = winning_proposals
+            .into_iter()
+            .max_by_key(|hash| hash::rank(&seed, hash));
+        if winner != Some(hash::proposal_hash(&self.proposal)?) {
+            return Err(invalid(format!(
+                "the votes do not decide {:?}",
+                self.proposal
+            )));
+        }
+        Ok(())
+    }
+}
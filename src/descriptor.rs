@@ -0,0 +1,244 @@
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::stream::MAX_BALLOT_DEPTH;
+use crate::{ConfigError, Hash, QuorumPolicy, Result, MAX_EXPANDED_VOTES, MAX_RELAY_HOPS};
+
+/// Bumped whenever a change makes us unable to take part in consensus with older nodes
+pub const PROTOCOL_VERSION: u16 = 5;
+
+#[cfg(feature = "bad_crypto")]
+const SIGNATURE_SCHEME: &str = "bad_crypto";
+#[cfg(feature = "blsttc")]
+const SIGNATURE_SCHEME: &str = "blsttc";
+#[cfg(feature = "ed25519")]
+const SIGNATURE_SCHEME: &str = "ed25519";
+
+/// A vote decides once strictly more than `numerator / denominator` of the voters agree
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuorumRule {
+    pub numerator: u64,
+    pub denominator: u64,
+}
+
+/// Bounds a node enforces on what it takes in and sends, `None` means no limit is enforced.
+/// Peers with other limits may refuse messages we accept, or send us pages we wait on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Limits {
+    pub max_message_size: Option<u64>,
+    pub catch_up_page_size: Option<u64>,
+    pub max_expanded_votes: u64,
+    pub max_ballot_depth: u64,
+    pub max_relay_hops: u64,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_message_size: None,
+            catch_up_page_size: None,
+            max_expanded_votes: MAX_EXPANDED_VOTES,
+            max_ballot_depth: MAX_BALLOT_DEPTH as u64,
+            max_relay_hops: MAX_RELAY_HOPS as u64,
+        }
+    }
+}
+
+/// What a node needs to agree on with its peers before voting with them,
+/// nodes exchange these when connecting to fail fast instead of mid-consensus
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProtocolDescriptor {
+    pub version: u16,
+    pub encoding: String,
+    pub hash_algorithm: String,
+    pub signature_scheme: String,
+    pub quorum_rule: QuorumRule,
+    pub limits: Limits,
+}
+
+impl Default for ProtocolDescriptor {
+    fn default() -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            encoding: "bincode".to_string(),
+            hash_algorithm: "sha3-256".to_string(),
+            signature_scheme: SIGNATURE_SCHEME.to_string(),
+            quorum_rule: QuorumRule {
+                numerator: 2,
+                denominator: 3,
+            },
+            limits: Limits::default(),
+        }
+    }
+}
+
+impl ProtocolDescriptor {
+    /// Checks we can take part in consensus with a peer that described itself as `theirs`
+    pub fn check_compatible(&self, theirs: &ProtocolDescriptor) -> Result<()> {
+        match self.diff(theirs).into_iter().next() {
+            Some(ConfigDifference {
+                field,
+                ours,
+                theirs,
+            }) => Err(ConfigError::IncompatibleProtocol {
+                field,
+                ours,
+                theirs,
+            }
+            .into()),
+            None => Ok(()),
+        }
+    }
+
+    /// Every field `theirs` differs on, in the order `check_compatible` checks them
+    pub fn diff(&self, theirs: &ProtocolDescriptor) -> Vec<ConfigDifference> {
+        let mut differences = Vec::new();
+        let mut compare = |field, ours: String, theirs: String| {
+            if ours != theirs {
+                differences.push(ConfigDifference {
+                    field,
+                    ours,
+                    theirs,
+                });
+            }
+        };
+        compare(
+            "version",
+            self.version.to_string(),
+            theirs.version.to_string(),
+        );
+        compare("encoding", self.encoding.clone(), theirs.encoding.clone());
+        compare(
+            "hash_algorithm",
+            self.hash_algorithm.clone(),
+            theirs.hash_algorithm.clone(),
+        );
+        compare(
+            "signature_scheme",
+            self.signature_scheme.clone(),
+            theirs.signature_scheme.clone(),
+        );
+        compare(
+            "quorum_rule",
+            format!("{:?}", self.quorum_rule),
+            format!("{:?}", theirs.quorum_rule),
+        );
+        compare(
+            "limits.max_message_size",
+            format!("{:?}", self.limits.max_message_size),
+            format!("{:?}", theirs.limits.max_message_size),
+        );
+        compare(
+            "limits.catch_up_page_size",
+            format!("{:?}", self.limits.catch_up_page_size),
+            format!("{:?}", theirs.limits.catch_up_page_size),
+        );
+        compare(
+            "limits.max_expanded_votes",
+            self.limits.max_expanded_votes.to_string(),
+            theirs.limits.max_expanded_votes.to_string(),
+        );
+        compare(
+            "limits.max_ballot_depth",
+            self.limits.max_ballot_depth.to_string(),
+            theirs.limits.max_ballot_depth.to_string(),
+        );
+        compare(
+            "limits.max_relay_hops",
+            self.limits.max_relay_hops.to_string(),
+            theirs.limits.max_relay_hops.to_string(),
+        );
+        differences
+    }
+}
+
+/// A parameter a peer runs with another value than ours
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigDifference {
+    pub field: &'static str,
+    pub ours: String,
+    pub theirs: String,
+}
+
+impl fmt::Display for ConfigDifference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: ours {} != theirs {}",
+            self.field, self.ours, self.theirs
+        )
+    }
+}
+
+/// What elders exchange when connecting: the fingerprint of the configuration they vote with
+/// and the parameters it covers, to tell which of them differ when fingerprints don't match
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConfigHandshake {
+    pub fingerprint: Hash,
+    pub descriptor: ProtocolDescriptor,
+    pub quorum_policy: QuorumPolicy,
+    pub unanimity_below_bft_minimum: bool,
+    pub rehearsal: bool,
+    pub genesis: Option<Hash>,
+}
+
+impl ConfigHandshake {
+    /// The parameters `theirs` differs on, none when the fingerprints match.
+    /// Quorum policies are only compared when their rules agree, e.g. on stakes.
+    pub fn diff(&self, theirs: &ConfigHandshake) -> Vec<ConfigDifference> {
+        if self.fingerprint == theirs.fingerprint {
+            return Vec::new();
+        }
+        let mut differences = self.descriptor.diff(&theirs.descriptor);
+        let mut compare = |field, ours: String, theirs: String| {
+            if ours != theirs {
+                differences.push(ConfigDifference {
+                    field,
+                    ours,
+                    theirs,
+                });
+            }
+        };
+        if self.descriptor.quorum_rule == theirs.descriptor.quorum_rule {
+            compare(
+                "quorum_policy",
+                format!("{:?}", self.quorum_policy),
+                format!("{:?}", theirs.quorum_policy),
+            );
+        }
+        compare(
+            "unanimity_below_bft_minimum",
+            self.unanimity_below_bft_minimum.to_string(),
+            theirs.unanimity_below_bft_minimum.to_string(),
+        );
+        compare(
+            "rehearsal",
+            self.rehearsal.to_string(),
+            theirs.rehearsal.to_string(),
+        );
+        compare(
+            "genesis",
+            format!("{:?}", self.genesis),
+            format!("{:?}", theirs.genesis),
+        );
+        // they fingerprint something we don't know of, a later release may
+        if differences.is_empty() {
+            differences.push(ConfigDifference {
+                field: "fingerprint",
+                ours: self.fingerprint.to_string(),
+                theirs: theirs.fingerprint.to_string(),
+            });
+        }
+        differences
+    }
+
+    /// Checks we vote by the same rules as the peer that sent us `theirs`, reporting every
+    /// parameter we differ on
+    pub fn check(&self, theirs: &ConfigHandshake) -> Result<()> {
+        match self.diff(theirs) {
+            differences if differences.is_empty() => Ok(()),
+            differences => Err(ConfigError::IncompatibleConfig(differences).into()),
+        }
+    }
+}
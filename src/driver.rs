@@ -0,0 +1,363 @@
+//! Runs a HandoverState over a transport so nodes don't have to hand-roll the packet plumbing.
+//! The driver is runtime agnostic, the transport brings the IO and the timers of its runtime.
+
+use core::fmt::Debug;
+use std::collections::BTreeMap;
+use std::future::{poll_fn, Future};
+use std::pin::pin;
+use std::task::Poll;
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    ContinuationToken, CorrelationId, Decision, Error, HandoverState, Priority, Proposal,
+    PublicKey, RehearsalProof, Result, StateError, TransportError, VoteMsg,
+};
+
+/// Moves vote messages between elders
+pub trait Transport<T: Ord + Serialize> {
+    /// Best effort delivery of `msg` to `msg.dest`, the driver resends what got lost
+    fn send(&self, msg: VoteMsg<T>) -> impl Future<Output = Result<()>> + Send;
+
+    /// The next message for us, `None` once the transport is closed.
+    /// It must be cancel safe, the driver drops it when a timer fires first.
+    fn recv(&self) -> impl Future<Output = Option<VoteMsg<T>>> + Send;
+
+    /// Resolves once `duration` elapsed, e.g. `tokio::time::sleep`
+    fn sleep(&self, duration: Duration) -> impl Future<Output = ()> + Send;
+}
+
+/// Timers of the driver
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DriverConfig {
+    /// How long we wait for a peer to show it saw our vote before sending it again,
+    /// until it answered one of our messages: from then on we go by its response times
+    pub resend_interval: Duration,
+    /// Bounds of the resend interval adapted to a peer, equal bounds keep it fixed
+    pub min_resend_interval: Duration,
+    pub max_resend_interval: Duration,
+    /// How often we send our view of the votes to every voter
+    pub anti_entropy_interval: Duration,
+}
+
+impl Default for DriverConfig {
+    fn default() -> Self {
+        Self {
+            resend_interval: Duration::from_millis(500),
+            min_resend_interval: Duration::from_millis(50),
+            max_resend_interval: Duration::from_secs(10),
+            anti_entropy_interval: Duration::from_secs(5),
+        }
+    }
+}
+
+#[allow(clippy::large_enum_variant)]
+enum Event<T: Ord + Serialize> {
+    Msg(Option<VoteMsg<T>>),
+    Tick,
+}
+
+/// Owns a HandoverState and delivers its messages through `transport` until the round is decided
+#[derive(Debug)]
+pub struct Handover<P: Ord + Serialize, T> {
+    state: HandoverState<P>,
+    transport: T,
+    config: DriverConfig,
+    unacked: BTreeMap<PublicKey, (VoteMsg<P>, Instant)>, // our last vote to each peer, until it shows it saw it
+    last_anti_entropy: Instant,
+    anti_entropy_from: BTreeMap<PublicKey, ContinuationToken>, // where the next page for each peer starts
+    sent_at: BTreeMap<(PublicKey, CorrelationId), Instant>, // our messages awaiting an answer to time, by peer
+    latencies: BTreeMap<PublicKey, Latency>,
+}
+
+// Smoothed response time of a peer and its variation, as TCP estimates round trips (RFC 6298)
+#[derive(Debug, Clone, Copy)]
+struct Latency {
+    smoothed: Duration,
+    variation: Duration,
+}
+
+impl Latency {
+    fn new(sample: Duration) -> Self {
+        Self {
+            smoothed: sample,
+            variation: sample / 2,
+        }
+    }
+
+    fn update(&mut self, sample: Duration) {
+        let deviation = sample.abs_diff(self.smoothed);
+        self.variation = (self.variation * 3 + deviation) / 4;
+        self.smoothed = (self.smoothed * 7 + sample) / 8;
+    }
+
+    fn resend_interval(&self) -> Duration {
+        self.smoothed + self.variation * 4
+    }
+}
+
+impl<P, T> Handover<P, T>
+where
+    P: Clone + Copy + Debug + Ord + Serialize + DeserializeOwned + Proposal,
+    T: Transport<P>,
+{
+    pub fn new(mut state: HandoverState<P>, transport: T, config: DriverConfig) -> Self {
+        // peers number their messages too, starting at random keeps us from mixing them up
+        state.next_correlation_id = rand::random();
+        Self {
+            state,
+            transport,
+            config,
+            unacked: Default::default(),
+            last_anti_entropy: Instant::now(),
+            anti_entropy_from: Default::default(),
+            sent_at: Default::default(),
+            latencies: Default::default(),
+        }
+    }
+
+    pub fn state(&self) -> &HandoverState<P> {
+        &self.state
+    }
+
+    pub fn state_mut(&mut self) -> &mut HandoverState<P> {
+        &mut self.state
+    }
+
+    pub fn into_inner(self) -> HandoverState<P> {
+        self.state
+    }
+
+    /// How long we currently give `peer` to answer before sending it our vote again
+    pub fn resend_interval(&self, peer: &PublicKey) -> Duration {
+        match self.latencies.get(peer) {
+            Some(latency) => latency
+                .resend_interval()
+                .max(self.config.min_resend_interval)
+                .min(self.config.max_resend_interval),
+            None => self.config.resend_interval,
+        }
+    }
+
+    pub async fn propose(&mut self, proposal: P) -> Result<()> {
+        let msgs = self.state.propose(proposal)?;
+        self.send_all(msgs).await
+    }
+
+    /// Handle messages and timers until the round is decided.
+    /// Protocol errors are the peers' doing, we log them and carry on.
+    /// Dropping the future cancels the run, calling it again picks up where it left off.
+    /// Messages it was sending may be lost, our resends and anti-entropy make up for them.
+    /// A rehearsal never decides, it's run with `run_rehearsal`.
+    pub async fn run(&mut self) -> Result<Decision<P>> {
+        if self.state.config.rehearsal {
+            return Err(StateError::RehearsalIsNonBinding.into());
+        }
+        self.run_until(HandoverState::decision).await
+    }
+
+    /// Like `run`, until the rehearsal reached consensus
+    pub async fn run_rehearsal(&mut self) -> Result<RehearsalProof<P>> {
+        if !self.state.config.rehearsal {
+            return Err(StateError::NotARehearsal.into());
+        }
+        self.run_until(HandoverState::rehearsal_proof).await
+    }
+
+    async fn run_until<R>(
+        &mut self,
+        outcome: impl Fn(&HandoverState<P>) -> Result<Option<R>>,
+    ) -> Result<R> {
+        loop {
+            if self.state.shut_down {
+                return Err(StateError::ShutDown.into());
+            }
+            if let Some(outcome) = outcome(&self.state)? {
+                self.flush().await?;
+                return Ok(outcome);
+            }
+
+            match self.next_event().await {
+                Event::Msg(None) => return Err(TransportError::Closed.into()),
+                Event::Msg(Some(msg)) => self.handle(msg).await?,
+                Event::Tick => self.on_tick().await?,
+            }
+        }
+    }
+
+    async fn next_event(&self) -> Event<P> {
+        let mut recv = pin!(self.transport.recv());
+        let mut tick = pin!(self.transport.sleep(self.tick_interval()));
+        poll_fn(|cx| {
+            if let Poll::Ready(msg) = recv.as_mut().poll(cx) {
+                return Poll::Ready(Event::Msg(msg));
+            }
+            if tick.as_mut().poll(cx).is_ready() {
+                return Poll::Ready(Event::Tick);
+            }
+            Poll::Pending
+        })
+        .await
+    }
+
+    // Often enough to resend to the quickest of the peers on time
+    fn tick_interval(&self) -> Duration {
+        let peers = self.peers();
+        let intervals = peers.iter().map(|peer| self.resend_interval(peer));
+        intervals.min().unwrap_or(self.config.resend_interval)
+    }
+
+    async fn handle(&mut self, msg: VoteMsg<P>) -> Result<()> {
+        let saw_ours = self.saw_ours(&msg);
+        let answered = msg.correlation_id.map(|id| (msg.vote.voter, id));
+        match self.state.handle_vote_msg(msg) {
+            Ok(outcome) => {
+                if let Some((peer, sent)) =
+                    saw_ours.and_then(|peer| self.unacked.remove_entry(&peer))
+                {
+                    if let Some(id) = sent.0.correlation_id {
+                        self.time_answer(peer, id);
+                    }
+                }
+                if let Some((sender, id)) = answered {
+                    self.time_answer(sender, id);
+                }
+                // apart from our reply to the sender, the state numbered the messages as its own
+                let now = Instant::now();
+                for msg in outcome.msgs.iter() {
+                    if let Some(id) = msg.correlation_id {
+                        if Some((msg.dest, id)) != answered {
+                            self.sent_at.insert((msg.dest, id), now);
+                        }
+                    }
+                }
+                self.send_all(outcome.msgs).await
+            }
+            Err(Error::Protocol(err)) => {
+                warn!("[MBR] Dropping vote: {}", err);
+                Ok(())
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    // The time a message of ours took to be answered, by its correlation id, is a latency sample
+    fn time_answer(&mut self, peer: PublicKey, id: CorrelationId) {
+        let sent_at = match self.sent_at.remove(&(peer, id)) {
+            Some(sent_at) => sent_at,
+            None => return,
+        };
+        let sample = sent_at.elapsed();
+        self.latencies
+            .entry(peer)
+            .and_modify(|latency| latency.update(sample))
+            .or_insert_with(|| Latency::new(sample));
+    }
+
+    // A peer saw our vote once it sends us a vote built on it, or echoes our message.
+    // Either way it answered the message we sent it with that vote, once the vote checks out.
+    fn saw_ours(&self, msg: &VoteMsg<P>) -> Option<PublicKey> {
+        let peer = msg.vote.voter;
+        let (sent, _) = self.unacked.get(&peer)?;
+        msg.vote.unpack_votes().contains(&sent.vote).then_some(peer)
+    }
+
+    async fn on_tick(&mut self) -> Result<()> {
+        let now = Instant::now();
+        let mut resends = Vec::new();
+        for (peer, (msg, sent_at)) in self.unacked.iter() {
+            if now.duration_since(*sent_at) >= self.resend_interval(peer) {
+                resends.push(msg.clone());
+            }
+        }
+        // an answer to a resent message can't tell which copy it answers, we don't time it
+        for msg in resends.iter() {
+            if let Some(id) = msg.correlation_id {
+                self.sent_at.remove(&(msg.dest, id));
+            }
+        }
+        let max_wait = self.config.max_resend_interval;
+        self.sent_at
+            .retain(|_, sent_at| now.duration_since(*sent_at) <= max_wait);
+        self.send_all(resends).await?;
+
+        if now.duration_since(self.last_anti_entropy) >= self.config.anti_entropy_interval {
+            self.last_anti_entropy = now;
+            // a page per peer and tick, the next tick goes on where this one stopped
+            for peer in self.peers() {
+                let continuation = self.anti_entropy_from.remove(&peer);
+                let page = self.state.anti_entropy_page(peer, continuation);
+                if let Some(continuation) = page.continuation {
+                    self.anti_entropy_from.insert(peer, continuation);
+                }
+                self.send_untracked(page.msgs).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Before a graceful restart, send our votes to every peer once more and stop voting,
+    /// see `HandoverState::shutdown`
+    pub async fn shutdown(&mut self) -> Result<()> {
+        for msg in self.state.shutdown() {
+            self.transport.send(msg).await?;
+        }
+        self.unacked.clear();
+        Ok(())
+    }
+
+    // Once we decided, our view of the votes lets the peers decide too
+    async fn flush(&mut self) -> Result<()> {
+        info!("[MBR] Decided, sending our votes to every voter");
+        for peer in self.peers() {
+            let msgs = self.state.full_anti_entropy(peer);
+            self.send_untracked(Vec::from_iter(msgs.into_iter().map(|msg| VoteMsg {
+                priority: Priority::Decision,
+                ..msg
+            })))
+            .await?;
+        }
+        self.unacked.clear();
+        Ok(())
+    }
+
+    // Our own messages are numbered to time their answers, answers keep the id they echo
+    async fn send_all(&mut self, msgs: Vec<VoteMsg<P>>) -> Result<()> {
+        let us = self.state.public_key();
+        for msg in msgs {
+            let msg = self.numbered(msg);
+            if msg.dest != us {
+                self.unacked.insert(msg.dest, (msg.clone(), Instant::now()));
+            }
+            self.transport.send(msg).await?;
+        }
+        Ok(())
+    }
+
+    // Anti-entropy is numbered and timed the same, but there's no vote of ours in it to resend
+    async fn send_untracked(&mut self, msgs: Vec<VoteMsg<P>>) -> Result<()> {
+        for msg in msgs {
+            let msg = self.numbered(msg);
+            self.transport.send(msg).await?;
+        }
+        Ok(())
+    }
+
+    fn numbered(&mut self, msg: VoteMsg<P>) -> VoteMsg<P> {
+        if msg.dest == self.state.public_key() || msg.correlation_id.is_some() {
+            return msg;
+        }
+        let msg = self.state.tag(msg);
+        if let Some(id) = msg.correlation_id {
+            self.sent_at.insert((msg.dest, id), Instant::now());
+        }
+        msg
+    }
+
+    fn peers(&self) -> Vec<PublicKey> {
+        let us = self.state.public_key();
+        Vec::from_iter(self.state.voters.iter().copied().filter(|v| *v != us))
+    }
+}
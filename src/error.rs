@@ -2,15 +2,45 @@ use core::fmt::Debug;
 use std::collections::BTreeSet;
 use thiserror::Error;
 
-use crate::{Generation, PublicKey};
+use crate::{Generation, Hash, PublicKey, RelayId};
 
+/// Errors are split by where they come from:
+/// - protocol errors are faults in the votes and messages we were given, they are the peers' doing
+/// - state errors are operations our own state doesn't allow (yet or anymore)
+/// - config errors come from the way this node was set up
+/// - storage and transport errors come from the environment we run in
 #[allow(clippy::large_enum_variant)]
 #[derive(Error, Debug)]
 pub enum Error {
-    #[error("We experienced an IO error")]
-    IO(#[from] std::io::Error),
-    #[error("The operation requested assumes we have at least one member")]
-    NoMembers,
+    #[error("A peer's vote or message is at fault")]
+    Protocol(#[from] ProtocolError),
+    #[error("Our state doesn't allow it")]
+    State(#[from] StateError),
+    #[error("This node is set up wrong")]
+    Config(#[from] ConfigError),
+    #[error("Our storage failed")]
+    Storage(#[from] StorageError),
+    #[error("Our transport failed")]
+    Transport(#[from] TransportError),
+}
+
+impl Error {
+    /// The message of this error followed by those of the errors behind it, for logs
+    pub fn report(&self) -> String {
+        let mut report = self.to_string();
+        let mut source = std::error::Error::source(self);
+        while let Some(err) = source {
+            report.push_str(": ");
+            report.push_str(&err.to_string());
+            source = err.source();
+        }
+        report
+    }
+}
+
+#[allow(clippy::large_enum_variant)]
+#[derive(Error, Debug)]
+pub enum ProtocolError {
     #[error("Packet was not destined for this actor: {dest:?} != {actor:?}")]
     WrongDestination { dest: PublicKey, actor: PublicKey },
     #[error(
@@ -61,12 +91,43 @@ pub enum Error {
         ballot: String,
         members: BTreeSet<PublicKey>,
     },
+    #[error("Generation {0} no longer accepts fresh proposals, its deadline passed")]
+    GenerationDeadlinePassed(Generation),
     #[error("Invalid generation {0}")]
     InvalidGeneration(Generation),
     #[error("History contains an invalid vote {0:?}")]
     InvalidVoteInHistory(String),
-    #[error("Failed to encode with bincode")]
-    Encoding(#[from] bincode::Error),
+    #[error("Decision is not backed by its votes: {0}")]
+    InvalidDecision(String),
+    #[error("Malformed message: {0}")]
+    Malformed(String),
+    #[error("Wire version {version} is not supported, we decode versions {oldest} to {newest}")]
+    UnsupportedWireVersion { version: u8, oldest: u8, newest: u8 },
+    #[error("The message has no wire version byte")]
+    MissingWireVersion,
+    #[error("The message can't be encoded in wire version {version}: {reason}")]
+    NotInWireVersion { version: u8, reason: String },
+    #[error("{voter:?} attested another state of generation {gen} than ours")]
+    AttestedStateMismatch { voter: PublicKey, gen: Generation },
+    #[error("The section state is not backed by its signatures: {0}")]
+    InvalidStateProof(String),
+    #[error("The vote of {voter} at {path:?} in the ballot failed verification")]
+    InvalidNestedSignature {
+        path: Vec<usize>,
+        voter: PublicKey,
+        #[source]
+        reason: Box<crate::Error>,
+    },
+    #[error("The opening does not reveal the sealed proposal {0:?}")]
+    InvalidOpening(Hash),
+    #[error("Invalid split: {0}")]
+    InvalidSplit(String),
+    #[error("The relayed vote ran out of hops")]
+    RelayTtlExpired,
+    #[error("The relayed vote already went through relay {0}")]
+    RelayLoop(RelayId),
+    #[error("The relayed vote went through relay {0}, which we don't know")]
+    UnknownRelay(RelayId),
 
     #[cfg(feature = "ed25519")]
     #[error("Ed25519 Error {0}")]
@@ -80,3 +141,111 @@ pub enum Error {
     #[error("Failed Signature Verification")]
     BadCrypto(#[from] crate::bad_crypto::Error),
 }
+
+impl ProtocolError {
+    /// Peer input we can't decode
+    pub(crate) fn malformed(reason: impl ToString) -> crate::Error {
+        Self::Malformed(reason.to_string()).into()
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum StateError {
+    #[error("We only collect votes during the startup grace period")]
+    InGracePeriod,
+    #[error("We refuse to sign until we rebuilt our votes from our peers")]
+    Rebuilding,
+    #[error("We shut down, the state is read-only")]
+    ShutDown,
+    #[error("A rehearsal does not decide anything")]
+    RehearsalIsNonBinding,
+    #[error("This is not a rehearsal, it decides for real")]
+    NotARehearsal,
+    #[error("No decision was reached yet in generation {0}")]
+    NoDecision(Generation),
+    #[error("The decision of generation {0} is not a split")]
+    NotASplit(Generation),
+    #[cfg(feature = "testing")]
+    #[error("A test hook dropped it")]
+    DroppedByHook,
+}
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("The operation requested assumes we have at least one member")]
+    NoMembers,
+    #[error("Peer runs an incompatible protocol, {field} differs: ours {ours} != theirs {theirs}")]
+    IncompatibleProtocol {
+        field: &'static str,
+        ours: String,
+        theirs: String,
+    },
+    #[error("Conformance check {check} failed: {reason}")]
+    NonConformant { check: &'static str, reason: String },
+    #[error("Invalid quorum policy: {0}")]
+    InvalidQuorumPolicy(String),
+    #[error("Peer runs an incompatible configuration, {}", list(.0))]
+    IncompatibleConfig(Vec<crate::ConfigDifference>),
+}
+
+fn list(differences: &[crate::ConfigDifference]) -> String {
+    Vec::from_iter(differences.iter().map(ToString::to_string)).join(", ")
+}
+
+#[derive(Error, Debug)]
+pub enum StorageError {
+    #[error("We experienced an IO error")]
+    IO(#[from] std::io::Error),
+    #[error("Failed to encode with bincode")]
+    Encoding(#[from] bincode::Error),
+    #[error(
+        "Delta taken against gen {base_gen} at watermark {base:?} does not apply to a snapshot of gen {gen} at {watermark:?}"
+    )]
+    SnapshotMismatch {
+        base_gen: Generation,
+        base: Option<Hash>,
+        gen: Generation,
+        watermark: Option<Hash>,
+    },
+    #[error("Our stored state is corrupted: {0}")]
+    Corrupted(String),
+}
+
+#[derive(Error, Debug)]
+pub enum TransportError {
+    #[error("The transport was closed")]
+    Closed,
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Self::Storage(err.into())
+    }
+}
+
+impl From<bincode::Error> for Error {
+    fn from(err: bincode::Error) -> Self {
+        Self::Storage(err.into())
+    }
+}
+
+#[cfg(feature = "ed25519")]
+impl From<crate::ed25519::Error> for Error {
+    fn from(err: crate::ed25519::Error) -> Self {
+        Self::Protocol(err.into())
+    }
+}
+
+#[cfg(feature = "blsttc")]
+impl From<crate::blsttc::Error> for Error {
+    fn from(err: crate::blsttc::Error) -> Self {
+        Self::Protocol(err.into())
+    }
+}
+
+#[cfg(feature = "bad_crypto")]
+impl From<crate::bad_crypto::Error> for Error {
+    fn from(err: crate::bad_crypto::Error) -> Self {
+        Self::Protocol(err.into())
+    }
+}
@@ -0,0 +1,31 @@
+use serde::{Deserialize, Serialize};
+
+use crate::SignedVote;
+
+/// Evidence of misbehaviour by a current elder, signed by the elder itself
+/// so it can be reported to others. Garbage we can't attribute to an elder,
+/// e.g. from a relay forging votes, is not a fault, it's only an error.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Fault<T>
+where
+    T: Ord + Serialize,
+{
+    /// The elder signed a proposal that does not validate
+    InvalidProposal { vote: SignedVote<T> },
+    /// The elder signed two votes in the same generation where neither supersedes the other
+    ConflictingVotes {
+        first: SignedVote<T>,
+        second: SignedVote<T>,
+    },
+}
+
+impl<T: Ord + Serialize> Fault<T> {
+    /// The offending vote, the first one of a conflicting pair: its voter and generation
+    pub fn vote(&self) -> &SignedVote<T> {
+        match self {
+            Fault::InvalidProposal { vote } => vote,
+            Fault::ConflictingVotes { first, .. } => first,
+        }
+    }
+}
@@ -0,0 +1,28 @@
+use core::fmt::Debug;
+use std::sync::Arc;
+
+use crate::Generation;
+
+/// Decides the generation that follows a decision.
+/// The default is to move to the next generation, but the application may want
+/// `gen` to follow something else, e.g. a membership-layer epoch carried in the decision.
+/// The returned generation must be greater than the current one.
+pub trait GenerationPolicy<T>: Debug + Send + Sync {
+    fn next_gen(&self, gen: Generation, decision: &T) -> Generation;
+}
+
+/// Every decision moves us to the next generation
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Increment;
+
+impl<T> GenerationPolicy<T> for Increment {
+    fn next_gen(&self, gen: Generation, _decision: &T) -> Generation {
+        gen + 1
+    }
+}
+
+impl<T, P: GenerationPolicy<T> + ?Sized> GenerationPolicy<T> for Arc<P> {
+    fn next_gen(&self, gen: Generation, decision: &T) -> Generation {
+        (**self).next_gen(gen, decision)
+    }
+}
@@ -1,12 +1,33 @@
+use crate::hash;
+use crate::history::{
+    AntiEntropy, CatchUp, ContinuationToken, History, HistoryStats, Participation, RoundStats,
+    SyncRequest, VoterHistory,
+};
+use crate::signer::KeyVerifier;
+use crate::split::{split_genesis, SplitPolicy};
 use crate::vote::*;
-use std::collections::{BTreeMap, BTreeSet};
+#[cfg(feature = "testing")]
+use crate::{HookAction, Hooks};
+use std::borrow::Cow;
+use std::collections::{btree_map::Entry, BTreeMap, BTreeSet, VecDeque};
+use std::io::Read;
+use std::ops::Bound;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use rand::{CryptoRng, Rng};
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
-use crate::{Error, Proposal, PublicKey, Result, SecretKey};
+use crate::{
+    proposal_hash, CompactVoteMsg, Config, ConsensusReceipt, Decision, DecisionAnnounce, Fault,
+    GenerationPolicy, Hash, Increment, Outcome, Proposal, ProposalEvent, ProposalSource,
+    ProposalStatus, ProtocolDescriptor, ProtocolError, PublicKey, QuorumPolicy, QuorumReport,
+    RehearsalProof, Result, SecretKey, SectionState, SectionStateProof, Signature, Simulation,
+    Snapshot, SnapshotDelta, StateAttestation, StateError, StorageError, TransitionReceipt,
+    VoteLog, VoteSigner, VoteVerifier, BFT_MINIMUM_ELDERS,
+};
 use core::fmt::Debug;
-use log::info;
+use log::{debug, info};
 
 /// A local state each elder keeps
 /// Contains their view of the current votes
@@ -15,13 +36,33 @@ use log::info;
 #[derive(Debug)]
 pub struct HandoverState<T>
 where
-    T: Ord,
+    T: Ord + Serialize,
 {
-    pub secret_key: SecretKey,
-    pub gen: Generation, // section state unique id based on sn_membership
+    pub signer: Box<dyn VoteSigner>, // signs our votes, wherever our key is kept
+    pub verifier: Box<dyn VoteVerifier>, // checks the signatures on the votes we're given
+    pub gen: Generation,             // section state unique id based on sn_membership
     pub votes: BTreeMap<PublicKey, SignedVote<T>>, // the votes we collected
-    pub voters: BTreeSet<PublicKey>, // current elders
+    pub vote_hashes: Vec<(Hash, PublicKey)>, // hashes of the votes in the order we saved them
+    pub(crate) hashed: BTreeMap<PublicKey, HashedProposals>, // the proposals of each vote we saved, hashed once
+    pub voters: BTreeSet<PublicKey>,                         // current elders
     pub consensus: Option<T>, // proposition elders agreed on in the end
+    pub faults: BTreeSet<Fault<T>>, // evidence of misbehaving elders we came across
+    pub generation_policy: Box<dyn GenerationPolicy<T>>, // how gen moves on after a decision
+    pub proposal_source: Option<Box<dyn ProposalSource<T>>>, // where our proposals come from
+    pub history: History<T>,  // the rounds we terminated, to help lagging peers catch up
+    pub config: Config,
+    pub started_at: Instant, // when this state was created, i.e. when we (re)started
+    pub round_started_at: Instant, // when we started the current generation
+    pub decided_after: Option<Duration>, // how long into the generation we decided
+    pub stats: VecDeque<RoundStats>, // how our latest terminated rounds went, oldest first
+    pub rebuilding: Option<BTreeSet<PublicKey>>, // the peers we resynchronized from since we discarded our state
+    pub lifecycle: BTreeMap<Hash, (T, ProposalStatus)>, // where the candidate proposals of the round stand
+    pub events: Vec<ProposalEvent<T>>, // lifecycle changes not yet reported in an outcome
+    pub shut_down: bool, // we flushed our votes before going away, the state is read-only since
+    pub in_flight: BTreeMap<PublicKey, VecDeque<CorrelationId>>, // messages we numbered for each peer that it didn't answer yet, oldest first
+    pub next_correlation_id: CorrelationId, // numbers the messages we send of our own accord
+    #[cfg(feature = "testing")]
+    pub hooks: Option<Box<dyn Hooks<T>>>, // failure injection of chaos tests
 }
 
 impl<'de, T> HandoverState<T>
@@ -29,97 +70,1385 @@ where
     T: Clone + Copy + Debug + Ord + PartialEq + Serialize + Deserialize<'de> + Proposal,
 {
     pub fn from(
-        secret_key: SecretKey,
+        signer: impl VoteSigner + 'static,
         gen: Generation,
         voters: BTreeSet<PublicKey>,
     ) -> HandoverState<T> {
         HandoverState {
-            secret_key,
+            signer: Box::new(signer),
+            verifier: Box::new(KeyVerifier),
             gen,
             votes: Default::default(),
+            vote_hashes: Default::default(),
+            hashed: Default::default(),
             voters,
             consensus: None,
+            faults: Default::default(),
+            generation_policy: Box::new(Increment),
+            proposal_source: None,
+            history: Default::default(),
+            config: Default::default(),
+            started_at: Instant::now(),
+            round_started_at: Instant::now(),
+            decided_after: None,
+            stats: Default::default(),
+            rebuilding: None,
+            lifecycle: Default::default(),
+            events: Default::default(),
+            shut_down: false,
+            in_flight: Default::default(),
+            next_correlation_id: 0,
+            #[cfg(feature = "testing")]
+            hooks: None,
         }
     }
 
-    pub fn random(mut rng: impl Rng + CryptoRng, voters: BTreeSet<PublicKey>) -> HandoverState<T> {
-        HandoverState {
-            secret_key: SecretKey::random(&mut rng),
-            gen: Default::default(),
-            votes: Default::default(),
-            voters,
-            consensus: None,
+    pub fn random(rng: impl Rng + CryptoRng, voters: BTreeSet<PublicKey>) -> HandoverState<T> {
+        HandoverState::from(SecretKey::random(rng), Default::default(), voters)
+    }
+
+    pub fn public_key(&self) -> PublicKey {
+        self.signer.public_key()
+    }
+
+    /// What to persist to survive a restart mid-round
+    pub fn snapshot(&self) -> Snapshot<T> {
+        Snapshot {
+            gen: self.gen,
+            votes: self.votes.clone(),
+            watermark: self.watermark(),
+            voters: self.voters.clone(),
+            consensus: self.consensus,
+            faults: self.faults.clone(),
+            config: self.config.clone(),
+            history: self.history.clone(),
+            stats: self.stats.clone(),
+        }
+    }
+
+    /// Hash of the last vote we saved, snapshots taken now are up to it
+    pub fn watermark(&self) -> Option<Hash> {
+        self.vote_hashes.last().map(|(hash, _)| *hash)
+    }
+
+    /// What changed since `base`, a snapshot we took earlier, see `SnapshotDelta`
+    pub fn snapshot_since(&self, base: &Snapshot<T>) -> SnapshotDelta<T> {
+        let since = self
+            .vote_hashes
+            .iter()
+            .position(|(hash, _)| Some(*hash) == base.watermark)
+            .filter(|_| base.gen == self.gen);
+
+        let changed_voters = match since {
+            Some(i) => {
+                BTreeSet::from_iter(self.vote_hashes.iter().skip(i + 1).map(|(_, voter)| *voter))
+            }
+            None => BTreeSet::from_iter(self.votes.keys().copied()),
+        };
+        let last_stats = base.stats.back().map(|stats| stats.gen);
+
+        SnapshotDelta {
+            gen: self.gen,
+            base_gen: base.gen,
+            base: base.watermark,
+            watermark: self.watermark(),
+            all_votes: since.is_none(),
+            votes: self
+                .votes
+                .iter()
+                .filter(|(voter, _)| changed_voters.contains(voter))
+                .map(|(voter, vote)| (*voter, vote.clone()))
+                .collect(),
+            voters: Some(self.voters.clone()).filter(|voters| *voters != base.voters),
+            consensus: self.consensus,
+            faults: self.faults.difference(&base.faults).cloned().collect(),
+            config: Some(self.config.clone()).filter(|config| *config != base.config),
+            rounds: self
+                .history
+                .rounds
+                .iter()
+                .filter(|(gen, _)| !base.history.rounds.contains_key(gen))
+                .map(|(gen, round)| (*gen, round.clone()))
+                .collect(),
+            stats: self
+                .stats
+                .iter()
+                .filter(|stats| last_stats.is_none_or(|last| stats.gen > last))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Back to where we were when the snapshot was taken, counting as a restart
+    /// for the startup grace period.
+    /// A snapshot failing `check_integrity` is discarded, see `rebuild_from_peers`.
+    pub fn restore(
+        snapshot: Snapshot<T>,
+        signer: impl VoteSigner + 'static,
+    ) -> Result<HandoverState<T>> {
+        let mut state = HandoverState::from(signer, snapshot.gen, snapshot.voters);
+
+        // the order we saved votes in before the snapshot is lost, but keeping its watermark last
+        // lets deltas be taken against the snapshot
+        for vote in snapshot.votes.values() {
+            state.vote_hashes.push((vote.hash()?, vote.voter));
+        }
+        state
+            .vote_hashes
+            .sort_by_key(|(hash, _)| Some(*hash) == snapshot.watermark);
+
+        for vote in snapshot.votes.values() {
+            state.track_lifecycle(vote)?;
+        }
+        state.votes = snapshot.votes;
+        state.save_reached_consensus(snapshot.consensus);
+        state.events = Default::default();
+        state.faults = snapshot.faults;
+        state.config = snapshot.config;
+        state.history = snapshot.history;
+        state.stats = snapshot.stats;
+
+        // a snapshot only of sound votes may still have lost the vote its watermark is of
+        let integrity = match snapshot
+            .watermark
+            .is_none_or(|w| state.watermark() == Some(w))
+        {
+            true => state.check_integrity(),
+            false => Err(StorageError::Corrupted("the watermark vote is missing".into()).into()),
+        };
+        if let Err(err) = integrity {
+            info!(
+                "[MBR] Restored a corrupted snapshot, rebuilding: {}",
+                err.report()
+            );
+            state.rebuild_from_peers();
+        }
+        Ok(state)
+    }
+
+    /// Replay the votes of our generation from a log, in the order they were logged.
+    /// Votes that fail validation were refused the first time around and are skipped again.
+    /// Once replayed we decide what to vote, like after absorbing a vote summary.
+    pub fn replay(&mut self, log: &impl VoteLog<T>) -> Result<Outcome<T>> {
+        let mut last_replayed_ballot = None;
+        for signed_vote in log.votes()? {
+            if signed_vote.vote.gen != self.gen || self.validate_signed_vote(&signed_vote).is_err()
+            {
+                continue;
+            }
+            self.save_signed_vote(&signed_vote)?;
+            last_replayed_ballot = Some(signed_vote.vote.ballot);
+        }
+
+        if self.consensus.is_some() || self.only_collecting() {
+            return Ok(Outcome::default());
+        }
+
+        match last_replayed_ballot {
+            Some(ballot) => {
+                let msgs = self.process_votes(ballot)?;
+                self.outcome(msgs)
+            }
+            None => Ok(Outcome::default()),
+        }
+    }
+
+    pub fn set_config(&mut self, config: Config) {
+        self.config = config;
+    }
+
+    /// Right after a (re)start we only collect votes and serve anti-entropy,
+    /// see `Config::startup_grace_period`
+    pub fn in_grace_period(&self) -> bool {
+        self.started_at.elapsed() < self.config.startup_grace_period
+    }
+
+    // Until we can trust our view of the votes we keep it to ourselves
+    fn only_collecting(&self) -> bool {
+        self.in_grace_period() || self.is_rebuilding()
+    }
+
+    /// Checks our votes are the ones we saved, by recomputing their hashes and signatures.
+    /// Run it after loading our state and periodically, see `rebuild_from_peers` when it fails.
+    pub fn check_integrity(&self) -> Result<()> {
+        let corrupted = |reason: String| Err(StorageError::Corrupted(reason).into());
+
+        let mut saved_hashes = BTreeMap::new();
+        for (hash, voter) in self.vote_hashes.iter() {
+            saved_hashes.insert(*voter, *hash);
+        }
+        if saved_hashes.len() != self.votes.len() {
+            return corrupted(format!(
+                "{} votes saved but {} stored",
+                saved_hashes.len(),
+                self.votes.len()
+            ));
+        }
+
+        for (voter, vote) in self.votes.iter() {
+            if voter != &vote.voter {
+                return corrupted(format!(
+                    "{:?} stored as the vote of {:?}",
+                    vote.redacted(),
+                    voter
+                ));
+            }
+            if saved_hashes.get(voter) != Some(&vote.hash()?) {
+                return corrupted(format!("{:?} is not the vote we saved", vote.redacted()));
+            }
+            if vote.vote.gen != self.gen || !self.voters.contains(voter) {
+                return corrupted(format!("{:?} does not belong here", vote.redacted()));
+            }
+            for signed_vote in vote.unpack_votes() {
+                if signed_vote
+                    .validate_signature_with(&*self.verifier, self.signing_domain())
+                    .is_err()
+                {
+                    return corrupted(format!("{:?} has a bad signature", signed_vote.redacted()));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Discard our corrupted votes and resynchronize from our peers.
+    /// Until catch-ups from a super majority (counting us) came back, we only collect votes
+    /// and refuse to sign, we could otherwise contradict a vote of ours we lost.
+    pub fn rebuild_from_peers(&mut self) -> Vec<SyncRequest> {
+        info!(
+            "[MBR] Discarding our votes of gen {} to rebuild them",
+            self.gen
+        );
+        self.votes = Default::default();
+        self.vote_hashes = Default::default();
+        self.consensus = None;
+        self.decided_after = None;
+        self.lifecycle = Default::default();
+        self.rebuilding = Some(Default::default());
+
+        let us = self.public_key();
+        self.voters
+            .iter()
+            .filter(|voter| **voter != us)
+            .map(|voter| self.sync_request(*voter))
+            .collect()
+    }
+
+    pub fn is_rebuilding(&self) -> bool {
+        self.rebuilding.is_some()
+    }
+
+    /// Before a graceful restart: all the votes we hold, ours among them, for every peer,
+    /// so our vote outlives us in the network while we're away. We neither vote nor take votes in afterwards,
+    /// the votes and anti-entropy can still be read.
+    pub fn shutdown(&mut self) -> Vec<VoteMsg<T>> {
+        info!(
+            "[MBR] {:?} shutting down at gen {}",
+            self.public_key(),
+            self.gen
+        );
+        self.shut_down = true;
+        let us = self.public_key();
+        self.voters
+            .iter()
+            .filter(|peer| **peer != us)
+            .flat_map(|peer| self.full_anti_entropy(*peer))
+            .collect()
+    }
+
+    fn ensure_running(&self) -> Result<()> {
+        if self.shut_down {
+            Err(StateError::ShutDown.into())
+        } else {
+            Ok(())
+        }
+    }
+
+    // We're consistent again once a super majority of us brought us up to date
+    fn rebuilt_from(&mut self, peer: PublicKey) -> Result<Vec<VoteMsg<T>>> {
+        let heard_from = match self.rebuilding.as_mut() {
+            Some(heard_from) => heard_from,
+            None => return Ok(vec![]),
+        };
+        heard_from.insert(self.signer.public_key());
+        if self.voters.contains(&peer) {
+            heard_from.insert(peer);
+        }
+        let weight = self.config.quorum_policy.weight_of(heard_from.iter());
+        if !self.is_quorum(weight) {
+            return Ok(vec![]);
+        }
+
+        self.check_integrity()?;
+        info!("[MBR] Rebuilt our votes of gen {} from our peers", self.gen);
+        self.rebuilding = None;
+        match self.votes.values().next().map(|v| v.vote.ballot.clone()) {
+            Some(ballot) if self.consensus.is_none() && !self.only_collecting() => {
+                self.process_votes(ballot)
+            }
+            _ => Ok(vec![]),
+        }
+    }
+
+    /// Past the deadline of the generation, fresh proposals are refused
+    pub fn deadline_passed(&self) -> bool {
+        match self.config.generation_deadline {
+            Some(deadline) => {
+                self.round_started_at.elapsed() > deadline + self.config.clock_skew_tolerance
+            }
+            None => false,
+        }
+    }
+
+    pub fn propose(&mut self, proposition: T) -> Result<Vec<VoteMsg<T>>> {
+        if self.in_grace_period() {
+            return Err(StateError::InGracePeriod.into());
+        }
+        if self.is_rebuilding() {
+            return Err(StateError::Rebuilding.into());
+        }
+        if self.deadline_passed() {
+            return Err(ProtocolError::GenerationDeadlinePassed(self.gen).into());
+        }
+        let vote = Vote {
+            gen: self.gen,
+            ballot: Ballot::Propose(proposition),
+            extensions: Default::default(),
+        };
+        let signed_vote = self.sign_vote(vote)?;
+        self.validate_signed_vote(&signed_vote)?;
+        self.cast_vote(signed_vote)
+    }
+
+    /// Describes the protocol we speak, for peers to check they can vote with us
+    pub fn protocol_descriptor(&self) -> ProtocolDescriptor {
+        self.config.protocol_descriptor()
+    }
+
+    /// Replace the supermajority threshold, e.g. with a stricter one or stake-weighted voting.
+    /// All voters must run with the same policy.
+    pub fn set_quorum_policy(&mut self, policy: QuorumPolicy) -> Result<()> {
+        policy.validate()?;
+        self.config.quorum_policy = policy;
+        Ok(())
+    }
+
+    pub fn set_proposal_source(&mut self, source: impl ProposalSource<T> + 'static) {
+        self.proposal_source = Some(Box::new(source));
+    }
+
+    pub fn set_verifier(&mut self, verifier: impl VoteVerifier + 'static) {
+        self.verifier = Box::new(verifier);
+    }
+
+    /// Proposers take turns, one voter per generation
+    pub fn proposer(&self, gen: Generation) -> Option<PublicKey> {
+        if self.voters.is_empty() {
+            return None;
+        }
+        let turn = gen % self.voters.len() as u64;
+        self.voters.iter().nth(turn as usize).copied()
+    }
+
+    /// If it's our turn to propose and we haven't voted yet, propose what our source supplies
+    pub fn poll_proposal_source(&mut self) -> Result<Vec<VoteMsg<T>>> {
+        let our_turn = self.proposer(self.gen) == Some(self.public_key());
+        let we_have_voted = self.votes.contains_key(&self.public_key());
+        if !our_turn
+            || we_have_voted
+            || self.consensus.is_some()
+            || self.only_collecting()
+            || self.deadline_passed()
+        {
+            return Ok(vec![]);
+        }
+
+        match self
+            .proposal_source
+            .as_ref()
+            .and_then(|source| source.next_proposal(self.gen))
+        {
+            Some(proposal) => {
+                info!(
+                    "[MBR] proposing for gen {} from our proposal source",
+                    self.gen
+                );
+                self.propose(proposal)
+            }
+            None => Ok(vec![]),
+        }
+    }
+
+    pub fn save_reached_consensus(&mut self, consensus: Option<T>) {
+        if consensus.is_some() && self.decided_after.is_none() {
+            self.decided_after = Some(self.round_started_at.elapsed());
+        }
+        if let Some(decided) = consensus {
+            let decided_hash = proposal_hash(&decided).ok();
+            if let Some(hash) = decided_hash {
+                self.lifecycle
+                    .entry(hash)
+                    .or_insert((decided, ProposalStatus::Seen));
+            }
+            let hashes = Vec::from_iter(self.lifecycle.keys().copied());
+            for hash in hashes {
+                let status = match Some(hash) == decided_hash {
+                    true => ProposalStatus::Decided,
+                    false => ProposalStatus::Abandoned,
+                };
+                self.set_status(hash, status);
+            }
+        }
+        self.consensus = consensus;
+    }
+
+    /// Where a candidate proposal of the current round stands, `None` if we never came across it
+    pub fn proposal_status(&self, proposal: &T) -> Result<Option<ProposalStatus>> {
+        let hash = proposal_hash(proposal)?;
+        Ok(self.lifecycle.get(&hash).map(|(_, status)| *status))
+    }
+
+    /// The candidate proposals of the current round with where they stand
+    pub fn proposal_statuses(&self) -> impl Iterator<Item = (&T, ProposalStatus)> {
+        self.lifecycle
+            .values()
+            .map(|(proposal, status)| (proposal, *status))
+    }
+
+    // Move the proposals of a vote we just saved along their lifecycle
+    fn track_lifecycle(&mut self, signed_vote: &SignedVote<T>) -> Result<()> {
+        signed_vote.for_each_proposal(&mut |_, proposal| {
+            if let Entry::Vacant(entry) = self.lifecycle.entry(proposal_hash(proposal)?) {
+                entry.insert((*proposal, ProposalStatus::Seen));
+                self.events.push(ProposalEvent {
+                    proposal: *proposal,
+                    status: ProposalStatus::Seen,
+                });
+            }
+            Ok(())
+        })?;
+
+        let status = match signed_vote.vote.is_super_majority_ballot() {
+            true => ProposalStatus::SuperMajorityForming,
+            false if signed_vote.voter == self.public_key() => ProposalStatus::EndorsedByMe,
+            false => return Ok(()),
+        };
+        let proposals = match self.hashed(signed_vote) {
+            Some(hashed) => hashed.proposals.clone(),
+            None => signed_vote.proposal_set()?,
+        };
+        for hash in proposals {
+            self.set_status(hash, status);
+        }
+        Ok(())
+    }
+
+    fn set_status(&mut self, hash: Hash, status: ProposalStatus) {
+        if let Some((proposal, current)) = self.lifecycle.get_mut(&hash) {
+            if status > *current && !current.is_final() {
+                *current = status;
+                self.events.push(ProposalEvent {
+                    proposal: *proposal,
+                    status,
+                });
+            }
+        }
+    }
+
+    /// How our latest terminated rounds went, see `Config::stats_retention`
+    pub fn history_stats(&self) -> HistoryStats {
+        HistoryStats {
+            rounds: self.stats.iter().cloned().collect(),
+        }
+    }
+
+    /// Persist the stats of the round we last terminated, call it after `advance`
+    pub fn log_stats(&self, log: &mut impl VoteLog<T>) -> Result<()> {
+        match self.stats.back() {
+            Some(stats) => log.append_stats(stats),
+            None => Ok(()),
+        }
+    }
+
+    /// Pick up the stats persisted before a restart
+    pub fn load_stats(&mut self, log: &impl VoteLog<T>) -> Result<()> {
+        self.stats = log.stats()?.into();
+        self.apply_retention();
+        Ok(())
+    }
+
+    fn record_stats(&mut self, rounds: usize) {
+        self.stats.push_back(RoundStats {
+            gen: self.gen,
+            duration: self
+                .decided_after
+                .unwrap_or_else(|| self.round_started_at.elapsed()),
+            rounds,
+            participants: self.votes.keys().copied().collect(),
+        });
+    }
+
+    // Forget the rounds past `Config::stats_retention`, their history along with their stats
+    fn apply_retention(&mut self) {
+        let retention = self.config.stats_retention.unwrap_or(usize::MAX);
+        while self.stats.len() > retention {
+            self.stats.pop_front();
+        }
+        self.history.trim(retention);
+    }
+
+    /// How `voter` took part in the rounds we terminated and still hold, see `History`.
+    /// We heard from it if its vote is in the decision proof or in the stats of the round.
+    pub fn voter_history(&self, voter: PublicKey) -> VoterHistory {
+        let faulted = BTreeSet::from_iter(
+            self.faults
+                .iter()
+                .map(Fault::vote)
+                .filter(|vote| vote.voter == voter)
+                .map(|vote| vote.vote.gen),
+        );
+        let mut generations = BTreeMap::new();
+        let mut response_rounds = Vec::new();
+        for (gen, round) in self.history.rounds.iter() {
+            if !round.voters.contains(&voter) {
+                continue;
+            }
+            let response_round = round
+                .decision
+                .votes
+                .iter()
+                .flat_map(SignedVote::unpack_votes)
+                .filter(|vote| vote.voter == voter)
+                .map(SignedVote::round)
+                .min();
+            response_rounds.extend(response_round);
+            let heard_from = response_round.is_some()
+                || self
+                    .stats
+                    .iter()
+                    .any(|stats| stats.gen == *gen && stats.participants.contains(&voter));
+            let participation = match (faulted.contains(gen), heard_from) {
+                (true, _) => Participation::Faulted,
+                (false, true) => Participation::Voted,
+                (false, false) => Participation::Abstained,
+            };
+            generations.insert(*gen, participation);
+        }
+        VoterHistory {
+            voter,
+            generations,
+            mean_response_round: match response_rounds.len() {
+                0 => None,
+                n => Some(response_rounds.iter().sum::<usize>() as f64 / n as f64),
+            },
+        }
+    }
+
+    /// Once we decided, report which voters formed the deciding quorum and which didn't take part
+    pub fn quorum_report(&self) -> Option<QuorumReport<T>> {
+        let decision = self.consensus?;
+        let tally = self.tally(self.votes.values()).ok()?;
+        let quorum = BTreeSet::from_iter(tally.super_majority_votes().map(|v| v.voter));
+        let participants = BTreeSet::from_iter(self.votes.keys().copied());
+
+        Some(QuorumReport {
+            gen: self.gen,
+            decision,
+            outside_quorum: participants.difference(&quorum).copied().collect(),
+            absent: self.voters.difference(&participants).copied().collect(),
+            quorum,
+        })
+    }
+
+    /// Predicts the round if the voters of `assumed_votes` proposed what they're assumed to,
+    /// letting upper layers tell whether starting a handover now is likely to succeed.
+    /// Works on a copy of our votes, nothing is signed or stored. Voters we already have a vote
+    /// from can't change their mind, their assumed vote is ignored, like those of non-members.
+    pub fn simulate_peer_votes(
+        &self,
+        assumed_votes: impl IntoIterator<Item = (PublicKey, T)>,
+    ) -> Result<Simulation<T>> {
+        let mut proposal_sets = BTreeMap::new();
+        let mut candidates = BTreeMap::new();
+        for (voter, vote) in self.votes.iter() {
+            proposal_sets.insert(*voter, vote.proposal_set()?);
+            vote.for_each_proposal(&mut |_, proposal| {
+                candidates.insert(proposal_hash(proposal)?, *proposal);
+                Ok(())
+            })?;
+        }
+        for (voter, proposal) in assumed_votes {
+            if !self.voters.contains(&voter) || proposal_sets.contains_key(&voter) {
+                continue;
+            }
+            let hash = proposal_hash(&proposal)?;
+            proposal_sets.insert(voter, BTreeSet::from_iter([hash]));
+            candidates.insert(hash, proposal);
+        }
+
+        let policy = &self.config.quorum_policy;
+        let mut counts: BTreeMap<&BTreeSet<Hash>, u64> = BTreeMap::new();
+        for (voter, proposals) in proposal_sets.iter() {
+            let count = counts.entry(proposals).or_default();
+            *count = count.saturating_add(policy.weight(voter));
+        }
+        let participants = BTreeSet::from_iter(proposal_sets.keys().copied());
+        let quorum = self.is_quorum(policy.weight_of(&participants));
+        let leading = counts.iter().max_by_key(|(_, count)| **count);
+        let split = !leading.is_some_and(|(_, count)| self.is_quorum(*count));
+
+        // a split is merged into the union of the proposals before a super majority forms
+        let contenders = match (split, leading) {
+            (false, Some((proposals, _))) => (*proposals).clone(),
+            _ => proposal_sets.into_values().flatten().collect(),
+        };
+        let seed = self.round_seed(self.gen)?;
+        let likely_winner = contenders
+            .into_iter()
+            .max_by_key(|hash| hash::rank(&seed, hash))
+            .filter(|_| quorum)
+            .and_then(|hash| candidates.get(&hash).copied());
+
+        Ok(Simulation {
+            gen: self.gen,
+            participants,
+            quorum,
+            split,
+            likely_winner,
+        })
+    }
+
+    pub fn set_generation_policy(&mut self, policy: impl GenerationPolicy<T> + 'static) {
+        self.generation_policy = Box::new(policy);
+    }
+
+    /// The generation that follows our decision, if we decided
+    pub fn next_gen(&self) -> Option<Generation> {
+        self.consensus
+            .map(|decision| self.generation_policy.next_gen(self.gen, &decision))
+    }
+
+    /// Once we decided, move on to the next generation with its set of voters
+    pub fn advance(&mut self, voters: BTreeSet<PublicKey>) -> Result<Generation> {
+        self.ensure_running()?;
+        if self.config.rehearsal {
+            return Err(StateError::RehearsalIsNonBinding.into());
+        }
+        let next_gen = self.next_gen().ok_or(StateError::NoDecision(self.gen))?;
+        if next_gen <= self.gen {
+            return Err(ProtocolError::InvalidGeneration(next_gen).into());
+        }
+
+        if let Some(decision) = self.decision()? {
+            let rounds = decision.votes.iter().map(SignedVote::round).max();
+            self.record_stats(rounds.map_or(0, |round| round + 1));
+            self.history.record(self.voters.clone(), decision);
+            self.apply_retention();
+        }
+
+        info!("[MBR] moving on from gen {} to gen {}", self.gen, next_gen);
+        self.gen = next_gen;
+        self.votes = Default::default();
+        self.vote_hashes = Default::default();
+        self.hashed = Default::default();
+        self.voters = voters;
+        self.consensus = None;
+        self.lifecycle = Default::default();
+        self.in_flight.retain(|peer, _| self.voters.contains(peer));
+        self.round_started_at = Instant::now();
+        self.decided_after = None;
+        Ok(next_gen)
+    }
+
+    /// Once we decided a split, the instances of the two children in the order of
+    /// `Split::children`, sharing our signer, verifier and generation policy.
+    /// They start from the generation that follows ours, we only vote in the child we're an
+    /// elder of. Proposal sources aren't carried over, the children propose for themselves.
+    pub fn split(mut self, policy: &dyn SplitPolicy<T>) -> Result<[HandoverState<T>; 2]>
+    where
+        T: 'static,
+    {
+        let decision = self.consensus.ok_or(StateError::NoDecision(self.gen))?;
+        let split = policy
+            .split(self.gen, &decision)
+            .ok_or(StateError::NotASplit(self.gen))?;
+        split.validate()?;
+        let parent = self.section_state(self.gen)?;
+        let gen = self.advance(Default::default())?;
+        info!("[MBR] gen {} splits {:?}", parent.gen, split.prefix);
+
+        let signer: Arc<dyn VoteSigner> = Arc::from(self.signer);
+        let verifier: Arc<dyn VoteVerifier> = Arc::from(self.verifier);
+        let generation_policy: Arc<dyn GenerationPolicy<T>> = Arc::from(self.generation_policy);
+        let [zero_prefix, one_prefix] = split.children();
+        let [zero, one] = split.voters;
+        let child = |voters, prefix| -> Result<HandoverState<T>> {
+            let mut state = HandoverState::from(signer.clone(), gen, voters);
+            state.verifier = Box::new(verifier.clone());
+            state.generation_policy = Box::new(generation_policy.clone());
+            state.config = Config {
+                genesis: Some(split_genesis(&parent, &prefix)?),
+                ..self.config.clone()
+            };
+            state.started_at = self.started_at;
+            Ok(state)
+        };
+        Ok([child(zero, zero_prefix)?, child(one, one_prefix)?])
+    }
+
+    pub fn force_join(&mut self, public_key: PublicKey) {
+        self.voters.insert(public_key);
+    }
+
+    // Tell an actor our view of the current votes
+    /// Our votes for `actor`, as many as a page of `Config::catch_up_page_size` takes,
+    /// `anti_entropy_page` sends the rest
+    pub fn anti_entropy(&self, actor: PublicKey) -> Vec<VoteMsg<T>> {
+        self.anti_entropy_page(actor, None).msgs
+    }
+
+    /// The page of our votes for `actor` after where `continuation` stopped,
+    /// a continuation of another generation starts over
+    pub fn anti_entropy_page(
+        &self,
+        actor: PublicKey,
+        continuation: Option<ContinuationToken>,
+    ) -> AntiEntropy<T> {
+        info!(
+            "[MBR] anti-entropy for {:?} from {:?}",
+            actor,
+            self.public_key()
+        );
+
+        let after = continuation
+            .filter(|token| token.gen == self.gen)
+            .and_then(|token| token.after);
+        let (votes, more) = self.page_of_votes(after, 0);
+        let continuation = match votes.last() {
+            Some(last) if more => Some(ContinuationToken {
+                gen: self.gen,
+                after: Some(last.voter),
+            }),
+            _ => None,
+        };
+        let msgs = self.outgoing(
+            votes
+                .into_iter()
+                .cloned()
+                .map(|v| VoteMsg {
+                    priority: Priority::AntiEntropy,
+                    ..self.send(v, actor)
+                })
+                .collect(),
+        );
+        AntiEntropy { msgs, continuation }
+    }
+
+    /// Every page of our votes for `actor`
+    pub fn full_anti_entropy(&self, actor: PublicKey) -> Vec<VoteMsg<T>> {
+        let mut page = self.anti_entropy_page(actor, None);
+        let mut msgs = std::mem::take(&mut page.msgs);
+        while let Some(continuation) = page.continuation {
+            page = self.anti_entropy_page(actor, Some(continuation));
+            msgs.append(&mut page.msgs);
+        }
+        msgs
+    }
+
+    fn page_size(&self) -> usize {
+        self.config.catch_up_page_size.unwrap_or(usize::MAX)
+    }
+
+    // The votes of the round after the one of voter `after`, by voter, as many as fit
+    // in a page already carrying `carried` votes, and whether some were left out
+    fn page_of_votes(
+        &self,
+        after: Option<PublicKey>,
+        carried: usize,
+    ) -> (Vec<&SignedVote<T>>, bool) {
+        let start = after.map_or(Bound::Unbounded, Bound::Excluded);
+        let mut page = Vec::new();
+        let mut vote_count = carried;
+        for vote in self
+            .votes
+            .range((start, Bound::Unbounded))
+            .map(|(_, vote)| vote)
+        {
+            if vote_count > 0 && vote_count + vote.vote_count() > self.page_size() {
+                return (page, true);
+            }
+            vote_count += vote.vote_count();
+            page.push(vote);
+        }
+        (page, false)
+    }
+
+    /// What we know of the votes, for `actor` to send us only the votes we're missing
+    pub fn vote_digest(&self, actor: PublicKey) -> Result<VoteDigest> {
+        let mut hashes: BTreeMap<PublicKey, BTreeSet<Hash>> = BTreeMap::new();
+        for signed_vote in self.votes.values().flat_map(SignedVote::unpack_votes) {
+            hashes
+                .entry(signed_vote.voter)
+                .or_default()
+                .insert(signed_vote.hash()?);
+        }
+        Ok(VoteDigest {
+            gen: self.gen,
+            hashes,
+            requester: self.public_key(),
+            dest: actor,
+        })
+    }
+
+    /// Anti-entropy limited to the votes missing from the requester's digest.
+    /// A digest of another generation can't tell what the requester misses, it gets all our votes.
+    pub fn handle_vote_digest(&self, digest: VoteDigest) -> Result<Vec<VoteMsg<T>>> {
+        if digest.dest != self.public_key() {
+            return Err(ProtocolError::WrongDestination {
+                dest: digest.dest,
+                actor: self.public_key(),
+            }
+            .into());
+        }
+        if digest.gen != self.gen {
+            return Ok(self.anti_entropy(digest.requester));
+        }
+
+        let mut msgs = Vec::new();
+        for signed_vote in self.votes.values() {
+            if !digest.knows(&signed_vote.voter, &signed_vote.hash()?) {
+                msgs.push(VoteMsg {
+                    priority: Priority::AntiEntropy,
+                    ..self.send(signed_vote.clone(), digest.requester)
+                });
+            }
+        }
+        Ok(self.outgoing(msgs))
+    }
+
+    /// Anti-entropy in response to a message, the reply echoes its correlation id
+    pub fn anti_entropy_reply(
+        &self,
+        actor: PublicKey,
+        correlation_id: Option<CorrelationId>,
+    ) -> Vec<VoteMsg<T>> {
+        self.anti_entropy(actor)
+            .into_iter()
+            .map(|msg| VoteMsg {
+                correlation_id,
+                ..msg
+            })
+            .collect()
+    }
+
+    /// Number `msg` with a fresh correlation id, unless it already carries one.
+    /// Until the peer answers it, the message counts in the peer's `backpressure`.
+    pub fn tag(&mut self, msg: VoteMsg<T>) -> VoteMsg<T> {
+        if msg.correlation_id.is_some() || msg.dest == self.public_key() {
+            return msg;
+        }
+        let id = self.next_correlation_id;
+        self.next_correlation_id = id.wrapping_add(1);
+        self.in_flight.entry(msg.dest).or_default().push_back(id);
+        msg.with_correlation_id(id)
+    }
+
+    /// How many of the messages we sent each voter we believe are still outstanding,
+    /// for the transport to slow down on the peers that fall behind
+    pub fn backpressure(&self) -> BTreeMap<PublicKey, usize> {
+        BTreeMap::from_iter(self.voters.iter().map(|peer| {
+            let outstanding = self.in_flight.get(peer).map_or(0, VecDeque::len);
+            (*peer, outstanding)
+        }))
+    }
+
+    // An answer of `peer` to a message also tells us it got the ones we numbered for it before
+    fn acknowledge(&mut self, peer: &PublicKey, id: CorrelationId) {
+        if let Some(in_flight) = self.in_flight.get_mut(peer) {
+            if let Some(answered) = in_flight.iter().position(|sent| *sent == id) {
+                in_flight.drain(..=answered);
+            }
+        }
+    }
+
+    /// Handle a message sent in its compact form, it's validated like any other once expanded
+    pub fn handle_compact_vote_msg(&mut self, msg: CompactVoteMsg<T>) -> Result<Outcome<T>> {
+        self.handle_vote_msg(msg.expand()?)
+    }
+
+    /// Handle a message destined to us, our reply to its voter echoes the message's correlation id,
+    /// when it has one, the other responses are numbered afresh
+    pub fn handle_vote_msg(&mut self, msg: VoteMsg<T>) -> Result<Outcome<T>> {
+        if msg.dest != self.public_key() {
+            return Err(ProtocolError::WrongDestination {
+                dest: msg.dest,
+                actor: self.public_key(),
+            }
+            .into());
+        }
+
+        let (sender, correlation_id) = (msg.vote.voter, msg.correlation_id);
+        let mut outcome = self.handle_signed_vote(msg.vote)?;
+        if let Some(id) = correlation_id {
+            self.acknowledge(&sender, id);
+            let msgs = std::mem::take(&mut outcome.msgs);
+            outcome.msgs = Vec::from_iter(msgs.into_iter().map(|resp| match resp.dest == sender {
+                true => resp.with_correlation_id(id),
+                false => self.tag(resp),
+            }));
+        }
+        Ok(outcome)
+    }
+
+    /// Handle a vote, the outcome carries the decision if this vote terminated the round
+    pub fn handle_signed_vote(&mut self, signed_vote: SignedVote<T>) -> Result<Outcome<T>> {
+        self.ensure_running()?;
+        // if consensus was reached, ignore the vote
+        if self.consensus.is_some() {
+            return Ok(Outcome::default());
+        }
+
+        #[cfg(feature = "testing")]
+        let mut signed_vote = signed_vote;
+        #[cfg(feature = "testing")]
+        if let Some(HookAction::Drop) = self
+            .hooks
+            .as_ref()
+            .map(|h| h.before_apply(&mut signed_vote))
+        {
+            return Ok(Outcome::default());
+        }
+
+        debug!("[MBR] handling vote {:?}", signed_vote.redacted());
+
+        // validate and store
+        self.validate_not_fresh_past_deadline(&signed_vote)?;
+        if let Some(faults) = self.validate_signed_vote_or_collect_faults(&signed_vote)? {
+            return Ok(Outcome {
+                faults,
+                ..Default::default()
+            });
+        }
+        self.save_signed_vote(&signed_vote)?;
+
+        if self.only_collecting() {
+            info!("[MBR] In startup grace period or rebuilding, only collecting votes");
+            return Ok(Outcome::default());
+        }
+
+        let msgs = self.process_votes(signed_vote.vote.ballot)?;
+        self.outcome(msgs)
+    }
+
+    // We only get to process votes before consensus, so a decision now is a fresh one
+    fn outcome(&mut self, msgs: Vec<VoteMsg<T>>) -> Result<Outcome<T>> {
+        // our votes that complete a super majority over super majorities let our peers decide
+        let decision = self.decision()?;
+        let decisive = decision.is_some()
+            || self.is_super_majority_over_super_majorities(&self.tally(self.votes.values())?);
+        let msgs = match decisive {
+            true => msgs
+                .into_iter()
+                .map(|msg| VoteMsg {
+                    priority: Priority::Decision,
+                    ..msg
+                })
+                .collect(),
+            false => msgs,
+        };
+        Ok(Outcome {
+            msgs,
+            decision,
+            faults: vec![],
+            events: std::mem::take(&mut self.events),
+        })
+    }
+
+    /// The decision of the current generation with its proof, once we reached consensus
+    /// Never set in a rehearsal, see `rehearsal_proof`
+    pub fn decision(&self) -> Result<Option<Decision<T>>> {
+        if self.config.rehearsal {
+            return Ok(None);
+        }
+        self.build_decision()
+    }
+
+    /// The outcome of a rehearsal, once the drill reached consensus
+    pub fn rehearsal_proof(&self) -> Result<Option<RehearsalProof<T>>> {
+        if !self.config.rehearsal {
+            return Ok(None);
+        }
+        Ok(self
+            .build_decision()?
+            .map(|rehearsed| RehearsalProof { rehearsed }))
+    }
+
+    /// Votes are signed for a rehearsal or for real, in the instance spawned by a split if we are one
+    pub fn signing_domain(&self) -> SigningDomain {
+        self.config.signing_domain()
+    }
+
+    fn build_decision(&self) -> Result<Option<Decision<T>>> {
+        let proposal = match self.consensus {
+            Some(proposal) => proposal,
+            None => return Ok(None),
+        };
+
+        let tally = self.tally(self.votes.values())?;
+        let deciding = BTreeSet::from_iter(tally.super_majority_votes());
+
+        // small elder sets may have decided by unanimity before any super majority vote
+        let votes = match self.is_super_majority_over_super_majorities(&self.tally(deciding)?) {
+            true => BTreeSet::from_iter(tally.super_majority_votes().cloned()),
+            false => BTreeSet::from_iter(
+                tally
+                    .votes
+                    .iter()
+                    .filter(|(_, proposals)| **proposals == tally.winning_proposals)
+                    .map(|(vote, _)| (*vote).clone()),
+            ),
+        };
+
+        Ok(Some(Decision {
+            gen: self.gen,
+            proposal,
+            votes,
+        }))
+    }
+
+    /// Tell an actor what we decided, nothing to announce until we did
+    pub fn announce_decision(&self, actor: PublicKey) -> Result<Option<DecisionAnnounce<T>>> {
+        Ok(self.decision()?.map(|decision| DecisionAnnounce {
+            decision,
+            dest: actor,
+        }))
+    }
+
+    /// Accept a decision once we checked its votes decide what it claims
+    pub fn handle_decision_announce(&mut self, announce: DecisionAnnounce<T>) -> Result<()> {
+        self.ensure_running()?;
+        if announce.dest != self.public_key() {
+            return Err(ProtocolError::WrongDestination {
+                dest: announce.dest,
+                actor: self.public_key(),
+            }
+            .into());
         }
+
+        // if consensus was reached, we already know
+        if self.consensus.is_some() {
+            return Ok(());
+        }
+
+        self.accept_decision(announce.decision)?;
+        info!("[MBR] Accepted announced decision for gen {}", self.gen);
+        Ok(())
     }
 
-    pub fn public_key(&self) -> PublicKey {
-        self.secret_key.public_key()
+    fn accept_decision(&mut self, decision: Decision<T>) -> Result<()> {
+        self.validate_decision(&decision)?;
+        for signed_vote in decision.votes.iter() {
+            self.save_signed_vote(signed_vote)?;
+        }
+        self.save_reached_consensus(Some(decision.proposal));
+        Ok(())
     }
 
-    pub fn propose(&mut self, proposition: T) -> Result<Vec<VoteMsg<T>>> {
-        let vote = Vote {
+    /// The decision of a terminated round, rounds terminate when we advance past them
+    pub fn decision_for(&self, gen: Generation) -> Option<&Decision<T>> {
+        self.history.round(gen).map(|round| &round.decision)
+    }
+
+    /// Ask an actor for what we missed, to be sent when its votes are from a later generation
+    pub fn sync_request(&self, actor: PublicKey) -> SyncRequest {
+        SyncRequest {
             gen: self.gen,
-            ballot: Ballot::Propose(proposition),
+            requester: self.public_key(),
+            dest: actor,
+            continuation: None,
+        }
+    }
+
+    /// What we decided in generation `gen` and who voted, once we decided it
+    pub fn section_state(&self, gen: Generation) -> Result<SectionState> {
+        match (self.history.round(gen), self.consensus) {
+            (Some(round), _) => {
+                SectionState::decided(gen, round.voters.clone(), &round.decision.proposal)
+            }
+            (None, Some(consensus)) if gen == self.gen => {
+                SectionState::decided(gen, self.voters.clone(), &consensus)
+            }
+            _ => Err(StateError::NoDecision(gen).into()),
+        }
+    }
+
+    /// Vouch for the state of the section at generation `gen`, to be sent to the other voters
+    pub fn attest_state(&self, gen: Generation) -> Result<StateAttestation> {
+        let state = self.section_state(gen)?;
+        Ok(StateAttestation {
+            sig: self.signer.sign(&state.signing_bytes()?)?,
+            voter: self.public_key(),
+            state,
+        })
+    }
+
+    /// Co-sign the state of generation `gen` with the attestations of the other voters,
+    /// `None` until they make a quorum along with ours. An attestation of another state
+    /// means we don't hold the same state as that voter, there is no consistent backup point.
+    pub fn co_sign_state(
+        &self,
+        gen: Generation,
+        attestations: impl IntoIterator<Item = StateAttestation>,
+    ) -> Result<Option<SectionStateProof>> {
+        let ours = self.attest_state(gen)?;
+        let msg = ours.state.signing_bytes()?;
+        let mut signatures = BTreeMap::from_iter([(ours.voter, ours.sig)]);
+        for attestation in attestations {
+            if attestation.state != ours.state {
+                return Err(ProtocolError::AttestedStateMismatch {
+                    voter: attestation.voter,
+                    gen,
+                }
+                .into());
+            }
+            if !ours.state.voters.contains(&attestation.voter) {
+                return Err(ProtocolError::NonMember {
+                    public_key: attestation.voter,
+                    members: ours.state.voters.clone(),
+                }
+                .into());
+            }
+            self.verifier
+                .verify(&attestation.voter, &msg, &attestation.sig)?;
+            signatures.insert(attestation.voter, attestation.sig);
+        }
+
+        let policy = &self.config.quorum_policy;
+        if !policy.is_quorum(policy.weight_of(signatures.keys()), &ours.state.voters) {
+            return Ok(None);
+        }
+        Ok(Some(SectionStateProof {
+            state: ours.state,
+            signatures,
+        }))
+    }
+
+    /// The round of generation `gen` for external auditors, once we terminated it
+    pub fn receipt(&self, gen: Generation) -> Result<ConsensusReceipt> {
+        let round = self.history.round(gen).ok_or(StateError::NoDecision(gen))?;
+        ConsensusReceipt::of(round, &self.config.quorum_policy, self.signing_domain())
+    }
+
+    /// The handover decided at generation `gen`, for the node to update its section state
+    /// once we advanced past it
+    pub fn transition_receipt(&self, gen: Generation) -> Result<TransitionReceipt> {
+        let round = self.history.round(gen).ok_or(StateError::NoDecision(gen))?;
+        let (next_gen, new_elders) = match self.history.round_after(gen) {
+            Some((next_gen, next)) => (next_gen, next.voters.clone()),
+            None => (self.gen, self.voters.clone()),
         };
-        let signed_vote = self.sign_vote(vote)?;
-        self.validate_signed_vote(&signed_vote)?;
-        self.cast_vote(signed_vote)
+        TransitionReceipt::of(round, next_gen, new_elders)
     }
 
-    pub fn save_reached_consensus(&mut self, consensus: Option<T>) {
-        self.consensus = consensus;
+    /// The decisions a lagging peer needs to reach our generation, along with our current votes.
+    /// Past `Config::catch_up_page_size` votes, the peer asks for the rest with `next_request`.
+    pub fn handle_sync_request(&self, request: SyncRequest) -> Result<CatchUp<T>> {
+        if request.dest != self.public_key() {
+            return Err(ProtocolError::WrongDestination {
+                dest: request.dest,
+                actor: self.public_key(),
+            }
+            .into());
+        }
+
+        // a token from an earlier round of ours resumes from its decision
+        let (from, after) = match request.continuation {
+            Some(token) if token.gen == self.gen => (token.gen, token.after),
+            Some(token) => (token.gen, None),
+            None => (request.gen, None),
+        };
+        let mut decisions = Vec::new();
+        let mut vote_count = 0;
+        let mut continuation = None;
+        if after.is_none() {
+            for decision in self.history.decisions_since(from) {
+                let votes: usize = decision.votes.iter().map(SignedVote::vote_count).sum();
+                if vote_count > 0 && vote_count + votes > self.page_size() {
+                    continuation = Some(ContinuationToken {
+                        gen: decision.gen,
+                        after: None,
+                    });
+                    break;
+                }
+                vote_count += votes;
+                decisions.push(decision.clone());
+            }
+        }
+
+        let mut summary = VoteSummary {
+            gen: self.gen,
+            votes: Default::default(),
+        };
+        if continuation.is_none() {
+            let (votes, cut_short) = self.page_of_votes(after, vote_count);
+            if cut_short {
+                continuation = Some(ContinuationToken {
+                    gen: self.gen,
+                    after: votes.last().map(|vote| vote.voter).or(after),
+                });
+            }
+            summary.votes = votes.into_iter().cloned().collect();
+        }
+
+        Ok(CatchUp {
+            decisions,
+            summary,
+            source: self.public_key(),
+            dest: request.requester,
+            continuation,
+        })
     }
 
-    pub fn force_join(&mut self, public_key: PublicKey) {
-        self.voters.insert(public_key);
+    /// Fast-forward through the decisions we missed then join the current round.
+    /// `voters_after` gives the voters following a decision, which the application knows
+    /// (e.g. the elder set that was decided), we don't take the peer's word for it.
+    pub fn handle_catch_up(
+        &mut self,
+        catch_up: CatchUp<T>,
+        voters_after: impl Fn(&Decision<T>) -> BTreeSet<PublicKey>,
+    ) -> Result<Outcome<T>> {
+        self.ensure_running()?;
+        if catch_up.dest != self.public_key() {
+            return Err(ProtocolError::WrongDestination {
+                dest: catch_up.dest,
+                actor: self.public_key(),
+            }
+            .into());
+        }
+
+        self.fast_forward(catch_up.decisions, voters_after)?;
+        let mut outcome = match catch_up.summary.gen == self.gen {
+            true => self.absorb(catch_up.summary)?,
+            false => Outcome::default(),
+        };
+        // a peer brought us up to date once we got its last page
+        if self.is_rebuilding() && catch_up.continuation.is_none() {
+            let msgs = self.rebuilt_from(catch_up.source)?;
+            outcome = Outcome {
+                msgs,
+                ..self.outcome(vec![])?
+            };
+        }
+        Ok(outcome)
     }
 
-    // Tell an actor our view of the current votes
-    pub fn anti_entropy(&self, actor: PublicKey) -> Vec<VoteMsg<T>> {
-        info!(
-            "[MBR] anti-entropy for {:?} from {:?}",
-            actor,
-            self.public_key()
-        );
+    /// Accept each decision in turn and advance past it, decisions we are already past are skipped
+    pub fn fast_forward(
+        &mut self,
+        decisions: impl IntoIterator<Item = Decision<T>>,
+        voters_after: impl Fn(&Decision<T>) -> BTreeSet<PublicKey>,
+    ) -> Result<Generation> {
+        for decision in decisions {
+            if decision.gen < self.gen {
+                continue;
+            }
+            if self.consensus.is_none() {
+                self.accept_decision(decision.clone())?;
+            }
+            info!("[MBR] Fast-forwarding past gen {}", self.gen);
+            self.advance(voters_after(&decision))?;
+        }
+        Ok(self.gen)
+    }
 
-        self.votes
-            .values()
-            .cloned()
-            .map(|v| self.send(v, actor))
-            .collect()
+    /// Our view of the current votes, to be absorbed wholesale by another replica
+    pub fn vote_summary(&self) -> VoteSummary<T> {
+        VoteSummary {
+            gen: self.gen,
+            votes: self.votes.values().cloned().collect(),
+        }
     }
 
-    pub fn handle_signed_vote(&mut self, signed_vote: SignedVote<T>) -> Result<Vec<VoteMsg<T>>> {
-        // if consensus was reached, ignore the vote
+    /// Ingest another replica's view of the votes at once
+    /// Votes we already know of are skipped, the others are validated and stored
+    /// before we decide what to vote next, this catches up faster after a netsplit
+    /// than exchanging the votes one anti-entropy message at a time.
+    /// If a vote fails validation we stop there, the valid votes before it are kept.
+    pub fn absorb(&mut self, summary: VoteSummary<T>) -> Result<Outcome<T>> {
+        self.ensure_running()?;
         if self.consensus.is_some() {
-            return Ok(vec![]);
+            return Ok(Outcome::default());
         }
 
-        // validate and store
-        self.validate_signed_vote(&signed_vote)?;
-        self.save_signed_vote(&signed_vote);
+        if summary.gen != self.gen {
+            return Err(ProtocolError::VoteWithInvalidGeneration {
+                vote_gen: summary.gen,
+                gen: self.gen,
+            }
+            .into());
+        }
+
+        let mut last_absorbed_ballot = None;
+        for signed_vote in summary.votes {
+            let already_known = self
+                .votes
+                .get(&signed_vote.voter)
+                .map(|existing_vote| existing_vote.supersedes(&signed_vote))
+                .unwrap_or(false);
+            if already_known {
+                continue;
+            }
+
+            self.validate_not_fresh_past_deadline(&signed_vote)?;
+            if let Some(faults) = self.validate_signed_vote_or_collect_faults(&signed_vote)? {
+                return Ok(Outcome {
+                    faults,
+                    ..Default::default()
+                });
+            }
+            self.save_signed_vote(&signed_vote)?;
+            last_absorbed_ballot = Some(signed_vote.vote.ballot);
+        }
+
+        if self.only_collecting() {
+            info!("[MBR] In startup grace period or rebuilding, only collecting votes");
+            return Ok(Outcome::default());
+        }
+
+        match last_absorbed_ballot {
+            Some(ballot) => {
+                let msgs = self.process_votes(ballot)?;
+                self.outcome(msgs)
+            }
+            None => Ok(Outcome::default()),
+        }
+    }
+
+    // Decide what to vote now that our view of the votes changed,
+    // `ballot` is the last ballot we learned about.
+    fn process_votes(&mut self, ballot: Ballot<T>) -> Result<Vec<VoteMsg<T>>> {
+        if self.decide_by_unanimity()? {
+            return Ok(vec![]);
+        }
+        let tally = self.tally(self.votes.values())?;
 
         // if we have a split vote
         // report a Merge vote, elders will vote for this Merge as they see it,
         // once we have super majority over that Merge, elders vote for SuperMajority over that Merge
         // as everyone signed that SuperMajority over Merge, we have super majority over super majority
         // everyone can just use resolve_votes to get the determined winner proposal
-        if self.is_split_vote(&self.votes.values().cloned().collect()) {
+        if self.is_split_vote(&tally) {
             info!("[MBR] Detected split vote");
             let merge_vote = Vote {
                 gen: self.gen,
                 ballot: Ballot::Merge(self.votes.values().cloned().collect()).simplify(),
+                extensions: Default::default(),
             };
             let signed_merge_vote = self.sign_vote(merge_vote)?;
 
             if let Some(our_vote) = self.votes.get(&self.public_key()) {
-                let proposals_we_voted_for =
-                    BTreeSet::from_iter(our_vote.proposals().into_iter().map(|(_, p)| p));
-                let proposals_we_would_vote_for: BTreeSet<_> = signed_merge_vote
-                    .proposals()
-                    .into_iter()
-                    .map(|(_, r)| r)
-                    .collect();
+                let proposals_we_voted_for = our_vote.proposal_set()?;
+                let proposals_we_would_vote_for = signed_merge_vote.proposal_set()?;
 
                 if proposals_we_voted_for == proposals_we_would_vote_for {
                     info!("[MBR] This vote didn't add new information, waiting for more votes...");
@@ -132,17 +1461,16 @@ where
         }
 
         // super majority over a SuperMajority vote means elders reached consensus
-        if self.is_super_majority_over_super_majorities(&self.votes.values().cloned().collect()) {
-            self.save_reached_consensus(
-                self.resolve_votes(&self.votes.values().cloned().collect()),
-            );
+        if self.is_super_majority_over_super_majorities(&tally) {
+            let consensus = self.resolve_votes(&tally)?;
+            self.save_reached_consensus(consensus);
             info!("[MBR] Detected super majority over super majorities");
             return Ok(vec![]);
         }
 
         // once we reach super majority, we need to vote for it show others we've seen it
         // by voting for it in a SuperMajority vote
-        if self.is_super_majority(&self.votes.values().cloned().collect()) {
+        if self.is_super_majority(&tally) {
             info!("[MBR] Detected super majority");
 
             if let Some(our_vote) = self.votes.get(&self.public_key()) {
@@ -155,17 +1483,10 @@ where
                 // see different super majorities, this case will be resolved by the split vote detection
                 // as more messages are delivered.
 
-                let super_majority_proposal =
-                    self.resolve_votes(&self.votes.values().cloned().collect());
-
+                // We compare proposal sets rather than the resolved proposals: which proposal a
+                // set resolves to depends on the tie break, not on what we committed to.
                 let we_have_comitted_to_proposals_not_in_super_majority =
-                    if let Some(p) = super_majority_proposal {
-                        self.resolve_votes(&our_vote.unpack_votes().into_iter().cloned().collect())
-                            .into_iter()
-                            .any(|r| p != r)
-                    } else {
-                        false
-                    };
+                    !our_vote.proposal_set()?.is_subset(&tally.winning_proposals);
 
                 if we_have_comitted_to_proposals_not_in_super_majority {
                     info!("[MBR] We have committed to proposals that the super majority has not seen, waiting till we either have a split vote or SM/SM");
@@ -181,6 +1502,7 @@ where
             let vote = Vote {
                 gen: self.gen,
                 ballot,
+                extensions: Default::default(),
             };
             let signed_vote = self.sign_vote(vote)?;
             return self.cast_vote(signed_vote);
@@ -191,7 +1513,8 @@ where
         if !self.votes.contains_key(&self.public_key()) {
             let signed_vote = self.sign_vote(Vote {
                 gen: self.gen,
-                ballot: signed_vote.vote.ballot,
+                ballot,
+                extensions: Default::default(),
             })?;
             return self.cast_vote(signed_vote);
         }
@@ -200,118 +1523,212 @@ where
     }
 
     pub fn sign_vote(&self, vote: Vote<T>) -> Result<SignedVote<T>> {
+        self.ensure_running()?;
+        if self.is_rebuilding() {
+            return Err(StateError::Rebuilding.into());
+        }
+        #[cfg(feature = "testing")]
+        let mut vote = vote;
+        #[cfg(feature = "testing")]
+        if let Some(HookAction::Drop) = self.hooks.as_ref().map(|h| h.before_sign(&mut vote)) {
+            return Err(StateError::DroppedByHook.into());
+        }
         Ok(SignedVote {
             voter: self.public_key(),
-            sig: self.secret_key.sign(&vote.to_bytes()?),
+            sig: self
+                .signer
+                .sign(&vote.signing_bytes(self.signing_domain())?)?,
             vote,
         })
     }
 
+    // Our vote may be the last one unanimity waited for, peers still need it to decide
     fn cast_vote(&mut self, signed_vote: SignedVote<T>) -> Result<Vec<VoteMsg<T>>> {
-        self.save_signed_vote(&signed_vote);
+        self.save_signed_vote(&signed_vote)?;
+        self.decide_by_unanimity()?;
         self.broadcast(signed_vote)
     }
 
-    fn save_signed_vote(&mut self, signed_vote: &SignedVote<T>) {
+    // With too few elders to tolerate a fault, every one of them voting alike decides
+    fn decide_by_unanimity(&mut self) -> Result<bool> {
+        if !self.config.unanimity_below_bft_minimum || self.voters.len() >= BFT_MINIMUM_ELDERS {
+            return Ok(false);
+        }
+        let tally = self.tally(self.votes.values())?;
+        if !self.is_unanimous(&tally) {
+            return Ok(false);
+        }
+        info!("[MBR] Detected unanimity among our few elders");
+        let consensus = self.resolve_votes(&tally)?;
+        self.save_reached_consensus(consensus);
+        Ok(true)
+    }
+
+    #[allow(clippy::clone_on_copy)] // signatures are only Copy with bad_crypto
+    fn save_signed_vote(&mut self, signed_vote: &SignedVote<T>) -> Result<()> {
         for vote in signed_vote.unpack_votes() {
-            let existing_vote = self.votes.entry(vote.voter).or_insert_with(|| vote.clone());
-            if vote.supersedes(existing_vote) {
-                *existing_vote = vote.clone()
+            let changed = match self.votes.get(&vote.voter) {
+                Some(existing_vote) => vote != existing_vote && vote.supersedes(existing_vote),
+                None => true,
+            };
+            if changed {
+                self.votes.insert(vote.voter, vote.clone());
+                self.vote_hashes.push((vote.hash()?, vote.voter));
+                let by_voter = vote.proposal_hashes()?;
+                let hashed = HashedProposals {
+                    sig: vote.sig.clone(),
+                    proposals: BTreeSet::from_iter(by_voter.iter().map(|(_, hash)| *hash)),
+                    by_voter,
+                };
+                self.hashed.insert(vote.voter, hashed);
+                self.track_lifecycle(vote)?;
             }
         }
+        Ok(())
     }
 
-    fn count_votes(&self, votes: &BTreeSet<SignedVote<T>>) -> BTreeMap<BTreeSet<T>, usize> {
-        let mut count: BTreeMap<BTreeSet<T>, usize> = Default::default();
+    // The proposals of `vote` as we hashed them when saving it, if it's the vote we hold
+    fn hashed(&self, vote: &SignedVote<T>) -> Option<&HashedProposals> {
+        let held = self.votes.get(&vote.voter)?;
+        let same = std::ptr::eq(held, vote) || (held.sig == vote.sig && held == vote);
+        self.hashed
+            .get(&vote.voter)
+            .filter(|hashed| same && hashed.sig == held.sig)
+    }
 
-        for vote in votes.iter() {
-            let proposals =
-                BTreeSet::from_iter(vote.proposals().into_iter().map(|(_, proposal)| proposal));
-            let c = count.entry(proposals).or_default();
-            *c += 1;
+    // Count the votes once, the checks below all work off the same tally.
+    // The proposals of the votes we saved are those we hashed when saving them.
+    fn tally<'a>(
+        &'a self,
+        votes: impl IntoIterator<Item = &'a SignedVote<T>>,
+    ) -> Result<Tally<'a, T>> {
+        let mut tally = Tally {
+            votes: Vec::new(),
+            counts: BTreeMap::new(),
+            voters: BTreeSet::new(),
+            winning_proposals: BTreeSet::new(),
+        };
+        for vote in votes {
+            let proposals = match self.hashed(vote) {
+                Some(hashed) => Cow::Borrowed(&hashed.proposals),
+                None => Cow::Owned(vote.proposal_set()?),
+            };
+            let weight = self.config.quorum_policy.weight(&vote.voter);
+            match tally.counts.get_mut(&*proposals) {
+                Some(count) => *count = count.saturating_add(weight),
+                None => {
+                    tally.counts.insert(proposals.clone().into_owned(), weight);
+                }
+            }
+            tally.voters.insert(vote.voter);
+            tally.votes.push((vote, proposals));
         }
+        if let Some((proposals, _)) = tally.counts.iter().max_by_key(|(_, count)| **count) {
+            tally.winning_proposals = proposals.clone();
+        }
+        Ok(tally)
+    }
 
-        count
+    fn is_quorum(&self, weight: u64) -> bool {
+        self.config.quorum_policy.is_quorum(weight, &self.voters)
     }
 
     // When voters voted for different proposals and super majority can't be obtained anymore we have a split vote
     // Assuming we have 7 voters if 3 voters voted for A and 4 voters for B, we have a split vote because neither A or B can ever reach super majority (5)
-    fn is_split_vote(&self, votes: &BTreeSet<SignedVote<T>>) -> bool {
-        let counts = self.count_votes(votes);
-        let most_votes = counts.values().max().cloned().unwrap_or_default();
-        let members_count = self.voters.len();
-        let voters = BTreeSet::from_iter(votes.iter().map(|v| v.voter));
-        let remaining_voters = self.voters.difference(&voters).count();
+    fn is_split_vote(&self, tally: &Tally<T>) -> bool {
+        let policy = &self.config.quorum_policy;
+        let remaining_voters = policy.weight_of(self.voters.difference(&tally.voters));
 
         // give the remaining votes to the proposals with the most votes.
-        let predicted_votes = most_votes + remaining_voters;
+        let predicted_votes = tally.most_votes().saturating_add(remaining_voters);
 
-        3 * voters.len() > 2 * members_count && 3 * predicted_votes <= 2 * members_count
+        self.is_quorum(policy.weight_of(&tally.voters)) && !self.is_quorum(predicted_votes)
     }
 
-    fn is_super_majority(&self, votes: &BTreeSet<SignedVote<T>>) -> bool {
-        let most_votes = self
-            .count_votes(votes)
-            .values()
-            .max()
-            .cloned()
-            .unwrap_or_default();
-        let n = self.voters.len();
+    fn is_super_majority(&self, tally: &Tally<T>) -> bool {
+        self.is_quorum(tally.most_votes())
+    }
 
-        3 * most_votes > 2 * n
+    // Every voter voted for the same proposals
+    fn is_unanimous(&self, tally: &Tally<T>) -> bool {
+        tally.voters == self.voters && tally.counts.len() == 1
     }
 
-    fn is_super_majority_over_super_majorities(&self, votes: &BTreeSet<SignedVote<T>>) -> bool {
-        let (winning_proposals, _) = self
-            .count_votes(votes)
-            .into_iter()
-            .max_by_key(|(_, count)| *count)
-            .unwrap_or_default();
+    fn is_super_majority_over_super_majorities(&self, tally: &Tally<T>) -> bool {
+        let count_of_super_majorities = tally
+            .super_majority_votes()
+            .map(|vote| self.config.quorum_policy.weight(&vote.voter))
+            .sum();
+        self.is_quorum(count_of_super_majorities)
+    }
 
-        let count_of_super_majorities = votes
+    fn resolve_votes(&self, tally: &Tally<T>) -> Result<Option<T>> {
+        // we need to choose one deterministically
+        // we can't trust the proposals' Ord so we pick the one with the greatest rank
+        let seed = self.round_seed(self.gen)?;
+        let winner = match tally
+            .winning_proposals
             .iter()
-            .filter(|v| {
-                BTreeSet::from_iter(v.proposals().into_iter().map(|(_, r)| r)) == winning_proposals
-            })
-            .filter(|v| v.vote.is_super_majority_ballot())
-            .count();
+            .max_by_key(|hash| hash::rank(&seed, hash))
+        {
+            Some(winner) => winner,
+            None => return Ok(None),
+        };
 
-        3 * count_of_super_majorities > 2 * self.voters.len()
+        // proposals sharing a dedup key are the same candidate, settle on the same encoding of it
+        let mut resolved: Option<(Vec<u8>, T)> = None;
+        for (vote, _) in tally.votes.iter() {
+            vote.for_each_proposal(&mut |_, proposal| {
+                if proposal_hash(proposal)? == *winner {
+                    let encoding = bincode::serialize(proposal)?;
+                    if resolved.as_ref().is_none_or(|(e, _)| &encoding < e) {
+                        resolved = Some((encoding, *proposal));
+                    }
+                }
+                Ok(())
+            })?;
+        }
+        Ok(resolved.map(|(_, proposal)| proposal))
     }
 
-    fn resolve_votes(&self, votes: &BTreeSet<SignedVote<T>>) -> Option<T> {
-        let (winning_proposals, _) = self
-            .count_votes(votes)
-            .into_iter()
-            .max_by_key(|(_, count)| *count)
-            .unwrap_or_default();
+    /// Seed for any tie breaking in generation `gen`, derived from the generation and voters
+    /// so all honest nodes break ties the same way without coordinating
+    pub fn round_seed(&self, gen: Generation) -> Result<Hash> {
+        hash::round_seed(gen, &self.voters)
+    }
 
-        // we need to choose one deterministically
-        // proposals are comparable because they impl Ord so we arbitrarily pick the max
-        winning_proposals.into_iter().max()
+    /// Among tied proposals, the one with the greatest rank wins the current generation
+    pub fn tie_break_rank(&self, proposal: &T) -> Result<Hash> {
+        Ok(hash::rank(
+            &self.round_seed(self.gen)?,
+            &proposal_hash(proposal)?,
+        ))
     }
 
     fn validate_is_member(&self, public_key: PublicKey) -> Result<()> {
         if !self.voters.contains(&public_key) {
-            Err(Error::NonMember {
+            Err(ProtocolError::NonMember {
                 public_key,
                 members: self.voters.clone(),
-            })
+            }
+            .into())
         } else {
             Ok(())
         }
     }
 
     fn validate_vote_supersedes_existing_vote(&self, signed_vote: &SignedVote<T>) -> Result<()> {
-        if self.votes.contains_key(&signed_vote.voter)
-            && !signed_vote.supersedes(&self.votes[&signed_vote.voter])
-            && !self.votes[&signed_vote.voter].supersedes(signed_vote)
-        {
-            Err(Error::ExistingVoteIncompatibleWithNewVote {
-                existing_vote: format!("{:?}", self.votes[&signed_vote.voter]),
-            })
-        } else {
-            Ok(())
+        match self.votes.get(&signed_vote.voter) {
+            Some(existing_vote)
+                if !signed_vote.supersedes(existing_vote)
+                    && !existing_vote.supersedes(signed_vote) =>
+            {
+                Err(ProtocolError::ExistingVoteIncompatibleWithNewVote {
+                    existing_vote: format!("{:?}", existing_vote),
+                }
+                .into())
+            }
+            _ => Ok(()),
         }
     }
 
@@ -320,41 +1737,178 @@ where
         signed_vote: &SignedVote<T>,
     ) -> Result<()> {
         // Ensure that nobody is trying to change their proposal proposals.
-        let proposals: BTreeSet<(PublicKey, T)> = self
-            .votes
-            .values()
-            .flat_map(|v| v.proposals())
-            .chain(signed_vote.proposals())
-            .collect();
+        let mut proposal_of: BTreeMap<PublicKey, Hash> = BTreeMap::new();
+        let mut changed_mind = false;
+        for vote in std::iter::once(signed_vote).chain(self.votes.values()) {
+            match self.hashed(vote) {
+                Some(hashed) => {
+                    for (voter, hash) in hashed.by_voter.iter() {
+                        changed_mind |= proposal_of.entry(*voter).or_insert(*hash) != hash;
+                    }
+                }
+                None => vote.for_each_proposal(&mut |voter, proposal| {
+                    let hash = proposal_hash(proposal)?;
+                    changed_mind |= *proposal_of.entry(voter).or_insert(hash) != hash;
+                    Ok(())
+                })?,
+            }
+        }
 
-        let voters = BTreeSet::from_iter(proposals.iter().map(|(actor, _)| actor));
-        if voters.len() != proposals.len() {
-            Err(Error::VoterChangedMind {
+        if changed_mind {
+            let mut proposals: BTreeSet<(PublicKey, Hash)> = signed_vote.proposal_hashes()?;
+            for vote in self.votes.values() {
+                proposals.extend(vote.proposal_hashes()?);
+            }
+            Err(ProtocolError::VoterChangedMind {
                 proposal: proposals
                     .into_iter()
                     .map(|(pk, p)| (pk, format!("{:?}", p)))
                     .collect(),
-            })
+            }
+            .into())
         } else {
             Ok(())
         }
     }
 
+    // Past the deadline we still take part in convergence (merges, super majorities),
+    // but refuse any ballot bringing in a proposal we never heard of
+    fn validate_not_fresh_past_deadline(&self, signed_vote: &SignedVote<T>) -> Result<()> {
+        if !self.deadline_passed() {
+            return Ok(());
+        }
+
+        let mut known_proposals = BTreeSet::new();
+        for vote in self.votes.values() {
+            known_proposals.extend(vote.proposal_set()?);
+        }
+        if signed_vote.proposal_set()?.is_subset(&known_proposals) {
+            Ok(())
+        } else {
+            Err(ProtocolError::GenerationDeadlinePassed(self.gen).into())
+        }
+    }
+
+    // When a vote is invalid because elders misbehaved, we keep the evidence and hand back
+    // the faults we had not seen before (if any). An invalid vote no elder can be blamed for is an error.
+    fn validate_signed_vote_or_collect_faults(
+        &mut self,
+        signed_vote: &SignedVote<T>,
+    ) -> Result<Option<Vec<Fault<T>>>> {
+        let err = match self.validate_signed_vote(signed_vote) {
+            Ok(()) => return Ok(None),
+            Err(err) => err,
+        };
+
+        let mut faults = Vec::new();
+        for vote in signed_vote.unpack_votes() {
+            let signed_by_elder = self.voters.contains(&vote.voter)
+                && vote
+                    .validate_signature_with(&*self.verifier, self.signing_domain())
+                    .is_ok();
+            if !signed_by_elder || vote.vote.gen != self.gen {
+                continue;
+            }
+
+            if let Ballot::Propose(proposal) = &vote.vote.ballot {
+                if proposal.validate().is_err() {
+                    info!("[MBR] {:?} signed an invalid proposal", vote.voter);
+                    faults.push(Fault::InvalidProposal { vote: vote.clone() });
+                }
+            }
+
+            if let Some(existing_vote) = self.votes.get(&vote.voter) {
+                if !vote.supersedes(existing_vote) && !existing_vote.supersedes(vote) {
+                    info!("[MBR] {:?} signed conflicting votes", vote.voter);
+                    faults.push(Fault::ConflictingVotes {
+                        first: existing_vote.clone(),
+                        second: vote.clone(),
+                    });
+                }
+            }
+        }
+
+        // evidence we already hold makes the vote no more acceptable than it was
+        let new_faults = Vec::from_iter(
+            faults
+                .into_iter()
+                .filter(|fault| self.faults.insert(fault.clone())),
+        );
+        if new_faults.is_empty() {
+            return Err(err);
+        }
+        Ok(Some(new_faults))
+    }
+
+    /// Evidence against the elders that misbehaved
+    pub fn faults(&self) -> &BTreeSet<Fault<T>> {
+        &self.faults
+    }
+
     pub fn validate_signed_vote(&self, signed_vote: &SignedVote<T>) -> Result<()> {
-        signed_vote.validate_signature()?;
-        self.validate_vote(&signed_vote.vote)?;
-        self.validate_is_member(signed_vote.voter)?;
-        self.validate_vote_supersedes_existing_vote(signed_vote)?;
+        // a vote nested under several of the votes above it is still only validated once
+        self.validate_nested_vote(signed_vote, signed_vote)?;
+        for vote in signed_vote.unpack_votes() {
+            if !std::ptr::eq(vote, signed_vote) {
+                self.validate_nested_vote(signed_vote, vote)?;
+            }
+        }
+        // the proposals of the nested votes are all part of the outer vote's
         self.validate_voters_have_not_changed_proposals(signed_vote)?;
         Ok(())
     }
 
+    // Checks a vote of `root` on its own, its nested votes are checked by the caller.
+    // A bad signature deep in the ballot is pinned down to the vote that carries it.
+    // A vote we already hold was checked when we saved it, only its voter may have left since.
+    fn validate_nested_vote(
+        &self,
+        root: &SignedVote<T>,
+        signed_vote: &SignedVote<T>,
+    ) -> Result<()> {
+        if self.hashed(signed_vote).is_some() {
+            return self.validate_is_member(signed_vote.voter);
+        }
+        if let Err(reason) =
+            signed_vote.validate_signature_with(&*self.verifier, self.signing_domain())
+        {
+            return match root.path_to(signed_vote).filter(|path| !path.is_empty()) {
+                Some(path) => Err(ProtocolError::InvalidNestedSignature {
+                    path,
+                    voter: signed_vote.voter,
+                    reason: Box::new(reason),
+                }
+                .into()),
+                None => Err(reason),
+            };
+        }
+        self.validate_vote(&signed_vote.vote)?;
+        self.validate_is_member(signed_vote.voter)?;
+        self.validate_vote_supersedes_existing_vote(signed_vote)
+    }
+
+    fn validate_decision(&self, decision: &Decision<T>) -> Result<()> {
+        if decision.gen != self.gen {
+            return Err(ProtocolError::VoteWithInvalidGeneration {
+                vote_gen: decision.gen,
+                gen: self.gen,
+            }
+            .into());
+        }
+
+        for signed_vote in decision.votes.iter() {
+            self.validate_signed_vote(signed_vote)?;
+        }
+        decision.verify_for(&*self.verifier, &self.config, &self.voters)
+    }
+
     fn validate_vote(&self, vote: &Vote<T>) -> Result<()> {
         if vote.gen != self.gen {
-            return Err(Error::VoteWithInvalidGeneration {
+            return Err(ProtocolError::VoteWithInvalidGeneration {
                 vote_gen: vote.gen,
                 gen: self.gen,
-            });
+            }
+            .into());
         }
 
         match &vote.ballot {
@@ -362,36 +1916,32 @@ where
             Ballot::Merge(votes) => {
                 for child_vote in votes.iter() {
                     if child_vote.vote.gen != vote.gen {
-                        return Err(Error::MergedVotesMustBeFromSameGen {
+                        return Err(ProtocolError::MergedVotesMustBeFromSameGen {
                             child_gen: child_vote.vote.gen,
                             merge_gen: vote.gen,
-                        });
+                        }
+                        .into());
                     }
-                    self.validate_signed_vote(child_vote)?;
                 }
                 Ok(())
             }
             Ballot::SuperMajority(votes) => {
-                if !self.is_super_majority(
-                    &votes
-                        .iter()
-                        .flat_map(SignedVote::unpack_votes)
-                        .cloned()
-                        .collect(),
-                ) {
-                    Err(Error::SuperMajorityBallotIsNotSuperMajority {
+                let unpacked = BTreeSet::from_iter(votes.iter().flat_map(SignedVote::unpack_votes));
+                if !self.is_super_majority(&self.tally(unpacked)?) {
+                    Err(ProtocolError::SuperMajorityBallotIsNotSuperMajority {
                         ballot: format!("{:?}", vote.ballot),
                         members: self.voters.clone(),
-                    })
+                    }
+                    .into())
                 } else {
                     for child_vote in votes.iter() {
                         if child_vote.vote.gen != vote.gen {
-                            return Err(Error::MergedVotesMustBeFromSameGen {
+                            return Err(ProtocolError::MergedVotesMustBeFromSameGen {
                                 child_gen: child_vote.vote.gen,
                                 merge_gen: vote.gen,
-                            });
+                            }
+                            .into());
                         }
-                        self.validate_signed_vote(child_vote)?;
                     }
                     Ok(())
                 }
@@ -400,15 +1950,92 @@ where
     }
 
     fn broadcast(&self, signed_vote: SignedVote<T>) -> Result<Vec<VoteMsg<T>>> {
-        Ok(self
-            .voters
-            .iter()
-            .cloned()
-            .map(|member| self.send(signed_vote.clone(), member))
-            .collect())
+        Ok(self.outgoing(
+            self.voters
+                .iter()
+                .cloned()
+                .map(|member| self.send(signed_vote.clone(), member))
+                .collect(),
+        ))
+    }
+
+    // The messages that leave us, chaos tests may lose or tamper with some of them
+    fn outgoing(&self, msgs: Vec<VoteMsg<T>>) -> Vec<VoteMsg<T>> {
+        #[cfg(feature = "testing")]
+        if let Some(hooks) = self.hooks.as_ref() {
+            return msgs
+                .into_iter()
+                .filter_map(|mut msg| match hooks.before_send(&mut msg) {
+                    HookAction::Continue => Some(msg),
+                    HookAction::Drop => None,
+                })
+                .collect();
+        }
+        msgs
+    }
+
+    /// Inject failures at the protocol points of `hooks`, for chaos tests
+    #[cfg(feature = "testing")]
+    pub fn set_hooks(&mut self, hooks: impl Hooks<T> + 'static) {
+        self.hooks = Some(Box::new(hooks));
     }
 
     fn send(&self, vote: SignedVote<T>, dest: PublicKey) -> VoteMsg<T> {
-        VoteMsg { vote, dest }
+        VoteMsg {
+            priority: Priority::of(&vote.vote.ballot),
+            vote,
+            dest,
+            correlation_id: None,
+        }
+    }
+}
+
+// Reading messages off the wire decodes proposals we don't borrow from
+impl<T> HandoverState<T>
+where
+    T: Clone + Copy + Debug + Ord + Serialize + DeserializeOwned + Proposal,
+{
+    /// Handle a message as it came off the wire, in any version we support, reading at most
+    /// `Config::max_message_size` bytes of it. The signatures of the vote are checked as they
+    /// are read, before it is decoded, undecodable messages are `ProtocolError::Malformed`.
+    pub fn handle_vote_msg_bytes(&mut self, reader: impl Read) -> Result<Outcome<T>> {
+        let limit = self.config.max_message_size.unwrap_or(u64::MAX);
+        let msg = VoteMsg::read_verified(reader, limit, &*self.verifier, self.signing_domain())?;
+        self.handle_vote_msg(msg)
+    }
+}
+
+// The votes of a round with the proposals each backs, so they're only walked and hashed once
+// The proposals of a vote we saved, hashed once when saving it
+#[derive(Debug)]
+pub(crate) struct HashedProposals {
+    sig: Signature, // of the vote we hashed
+    proposals: BTreeSet<Hash>,
+    by_voter: BTreeSet<(PublicKey, Hash)>,
+}
+
+struct Tally<'a, T: Ord + Serialize> {
+    votes: Vec<(&'a SignedVote<T>, Cow<'a, BTreeSet<Hash>>)>,
+    counts: BTreeMap<BTreeSet<Hash>, u64>, // the weight of the voters behind each set of proposals
+    voters: BTreeSet<PublicKey>,
+    winning_proposals: BTreeSet<Hash>,
+}
+
+impl<'a, 'de, T> Tally<'a, T>
+where
+    T: Clone + Copy + Debug + Ord + Serialize + Deserialize<'de> + Proposal,
+{
+    fn most_votes(&self) -> u64 {
+        self.counts.values().max().copied().unwrap_or_default()
+    }
+
+    // The super majority ballots over the winning proposals
+    fn super_majority_votes(&self) -> impl Iterator<Item = &'a SignedVote<T>> + '_ {
+        self.votes
+            .iter()
+            .filter(|(vote, proposals)| {
+                vote.vote.is_super_majority_ballot() && **proposals == self.winning_proposals
+            })
+            .map(|(vote, _)| *vote)
     }
 }
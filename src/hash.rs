@@ -0,0 +1,86 @@
+use serde::{Deserialize, Serialize};
+use tiny_keccak::{Hasher, Sha3};
+
+use std::collections::BTreeSet;
+
+use crate::{Generation, Proposal, PublicKey, Result};
+
+/// A SHA3-256 digest
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Hash(pub [u8; 32]);
+
+impl Hash {
+    pub fn of(bytes: &[u8]) -> Self {
+        Self::of_parts([bytes])
+    }
+
+    /// Hash of the concatenation of `parts`
+    pub fn of_parts<'a>(parts: impl IntoIterator<Item = &'a [u8]>) -> Self {
+        let mut hasher = Sha3::v256();
+        for part in parts {
+            hasher.update(part);
+        }
+        let mut digest = [0u8; 32];
+        hasher.finalize(&mut digest);
+        Self(digest)
+    }
+
+    /// Hash of the canonical (bincode) encoding of `value`, streamed into the hasher
+    pub(crate) fn of_encoding<T: Serialize>(value: &T) -> Result<Self> {
+        let mut writer = HashWriter(Sha3::v256());
+        bincode::serialize_into(&mut writer, value)?;
+        let mut digest = [0u8; 32];
+        writer.0.finalize(&mut digest);
+        Ok(Self(digest))
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+struct HashWriter(Sha3);
+
+impl std::io::Write for HashWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl core::fmt::Debug for Hash {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Display::fmt(self, f)
+    }
+}
+
+impl core::fmt::Display for Hash {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "#{}", hex::encode(&self.0[..3]))
+    }
+}
+
+/// Proposals are identified by the hash of their dedup key (by default their canonical encoding),
+/// this way consensus never depends on the proposal's own `Ord` and `Eq` being consistent.
+pub fn proposal_hash<T: Serialize + Proposal>(proposal: &T) -> Result<Hash> {
+    Ok(Hash::of(&proposal.dedup_key()?))
+}
+
+/// Seed for any tie breaking in generation `gen`, derived from the generation and voters
+/// so all honest nodes break ties the same way without coordinating
+pub(crate) fn round_seed(gen: Generation, voters: &BTreeSet<PublicKey>) -> Result<Hash> {
+    Ok(Hash::of_parts([
+        b"sn_handover/round_seed".as_slice(),
+        &gen.to_le_bytes(),
+        &bincode::serialize(voters)?,
+    ]))
+}
+
+/// Among tied proposals, the one with the greatest rank wins
+pub(crate) fn rank(seed: &Hash, proposal_hash: &Hash) -> Hash {
+    Hash::of_parts([seed.as_bytes().as_slice(), proposal_hash.as_bytes()])
+}
@@ -0,0 +1,206 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Bound;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Decision, Generation, PublicKey, SignedVote, VoteMsg, VoteSummary};
+
+/// A terminated round, the decision of its generation along with the voters that decided it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Round<T>
+where
+    T: Ord + Serialize,
+{
+    pub voters: BTreeSet<PublicKey>,
+    pub decision: Decision<T>,
+}
+
+/// The rounds we terminated, by generation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct History<T>
+where
+    T: Ord + Serialize,
+{
+    pub rounds: BTreeMap<Generation, Round<T>>,
+}
+
+impl<T: Ord + Serialize> Default for History<T> {
+    fn default() -> Self {
+        Self {
+            rounds: Default::default(),
+        }
+    }
+}
+
+impl<T: Ord + Serialize> History<T> {
+    pub fn record(&mut self, voters: BTreeSet<PublicKey>, decision: Decision<T>) {
+        self.rounds.insert(decision.gen, Round { voters, decision });
+    }
+
+    /// Forgets all but the latest `retention` rounds
+    pub fn trim(&mut self, retention: usize) {
+        while self.rounds.len() > retention {
+            self.rounds.pop_first();
+        }
+    }
+
+    pub fn round(&self, gen: Generation) -> Option<&Round<T>> {
+        self.rounds.get(&gen)
+    }
+
+    /// The first round we terminated after generation `gen`
+    pub fn round_after(&self, gen: Generation) -> Option<(Generation, &Round<T>)> {
+        self.rounds
+            .range((Bound::Excluded(gen), Bound::Unbounded))
+            .map(|(gen, round)| (*gen, round))
+            .next()
+    }
+
+    /// The decisions of generation `gen` onwards, in the order they were taken
+    pub fn decisions_since(&self, gen: Generation) -> impl Iterator<Item = &Decision<T>> {
+        self.rounds.range(gen..).map(|(_, round)| &round.decision)
+    }
+}
+
+/// How a terminated round went
+/// - duration is the time from the start of the generation until we decided (or moved on)
+/// - rounds are the rounds of voting it took, a unanimous proposal decides in 2
+/// - participants are the voters we heard from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoundStats {
+    pub gen: Generation,
+    pub duration: Duration,
+    pub rounds: usize,
+    pub participants: BTreeSet<PublicKey>,
+}
+
+/// The stats of our latest terminated rounds, oldest first
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryStats {
+    pub rounds: Vec<RoundStats>,
+}
+
+impl HistoryStats {
+    pub fn mean_duration(&self) -> Option<Duration> {
+        let n = u32::try_from(self.rounds.len()).ok().filter(|n| *n > 0)?;
+        Some(self.rounds.iter().map(|r| r.duration).sum::<Duration>() / n)
+    }
+
+    pub fn mean_rounds(&self) -> Option<f64> {
+        if self.rounds.is_empty() {
+            return None;
+        }
+        let total: usize = self.rounds.iter().map(|r| r.rounds).sum();
+        Some(total as f64 / self.rounds.len() as f64)
+    }
+
+    /// How much slower the recent half of the rounds was than the older half,
+    /// above 1 handovers are slowing down. `None` until we have two rounds to compare.
+    pub fn slowdown(&self) -> Option<f64> {
+        let (older, recent) = self.rounds.split_at(self.rounds.len() / 2);
+        let mean = |rounds: &[RoundStats]| {
+            HistoryStats {
+                rounds: rounds.to_vec(),
+            }
+            .mean_duration()
+        };
+        let (older, recent) = (mean(older)?, mean(recent)?);
+        Some(recent.as_secs_f64() / older.as_secs_f64()).filter(|ratio| ratio.is_finite())
+    }
+}
+
+/// How a voter of a terminated round took part in it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Participation {
+    Voted,
+    /// We never heard from it
+    Abstained,
+    /// We caught it misbehaving, see `HandoverState::faults`
+    Faulted,
+}
+
+/// A voter's record over the rounds we retained, section governance data
+/// for e.g. picking the elders to promote
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VoterHistory {
+    pub voter: PublicKey,
+    pub generations: BTreeMap<Generation, Participation>,
+    /// The mean round of voting its first vote of a generation came in, from the decision
+    /// proofs it appears in. `None` if it's in none of them.
+    pub mean_response_round: Option<f64>,
+}
+
+impl VoterHistory {
+    pub fn count(&self, participation: Participation) -> usize {
+        self.generations
+            .values()
+            .filter(|p| **p == participation)
+            .count()
+    }
+}
+
+/// Asks a peer for what we missed since generation `gen`,
+/// or for the rest of a catch up that was cut short
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncRequest {
+    pub gen: Generation,
+    pub requester: PublicKey,
+    pub dest: PublicKey,
+    pub continuation: Option<ContinuationToken>,
+}
+
+/// Where a capped catch up or anti-entropy stopped, the next page starts from generation `gen`,
+/// within the votes of the current round after the one of voter `after`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ContinuationToken {
+    pub gen: Generation,
+    pub after: Option<PublicKey>,
+}
+
+/// A page of our votes for a peer, a page cut short has a continuation for the next one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AntiEntropy<T>
+where
+    T: Ord + Serialize,
+{
+    pub msgs: Vec<VoteMsg<T>>,
+    pub continuation: Option<ContinuationToken>,
+}
+
+/// What a lagging peer needs to fast-forward to our generation:
+/// the chain of decisions from its generation on, then the votes of our current round.
+/// A page cut short by `Config::catch_up_page_size` has a continuation, the decisions
+/// and votes that didn't fit come on the next pages.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CatchUp<T>
+where
+    T: Ord + Serialize,
+{
+    pub decisions: Vec<Decision<T>>,
+    pub summary: VoteSummary<T>,
+    pub source: PublicKey,
+    pub dest: PublicKey,
+    pub continuation: Option<ContinuationToken>,
+}
+
+impl<T: Ord + Serialize> CatchUp<T> {
+    /// How many votes the page carries, decision proofs and nested votes included
+    pub fn vote_count(&self) -> usize {
+        let proofs = self.decisions.iter().flat_map(|d| d.votes.iter());
+        proofs
+            .chain(self.summary.votes.iter())
+            .map(SignedVote::vote_count)
+            .sum()
+    }
+
+    /// The request for the next page, `None` once we got it all
+    pub fn next_request(&self) -> Option<SyncRequest> {
+        self.continuation.map(|token| SyncRequest {
+            gen: token.gen,
+            requester: self.dest,
+            dest: self.source,
+            continuation: Some(token),
+        })
+    }
+}
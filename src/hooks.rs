@@ -0,0 +1,34 @@
+//! Failure injection at precise points of the protocol, for the chaos tests of applications.
+//! Enabled by the `testing` feature; hooks run synchronously, sleeping in one delays the node.
+
+use core::fmt::Debug;
+
+use serde::Serialize;
+
+use crate::{SignedVote, Vote, VoteMsg};
+
+/// What becomes of the vote or message a hook was shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookAction {
+    Continue,
+    /// Signing fails with `StateError::DroppedByHook`, a message is lost, a vote is ignored
+    Drop,
+}
+
+/// Called by a HandoverState at each protocol point, the defaults let everything through
+pub trait Hooks<T: Ord + Serialize>: Debug + Send + Sync {
+    /// Before we sign a vote of ours, mutating it makes us sign something else
+    fn before_sign(&self, _vote: &mut Vote<T>) -> HookAction {
+        HookAction::Continue
+    }
+
+    /// Before a message of ours is handed back to be sent
+    fn before_send(&self, _msg: &mut VoteMsg<T>) -> HookAction {
+        HookAction::Continue
+    }
+
+    /// Before we validate and apply a vote we were given
+    fn before_apply(&self, _signed_vote: &mut SignedVote<T>) -> HookAction {
+        HookAction::Continue
+    }
+}
@@ -1,4 +1,12 @@
 // #![deny(missing_docs)]
+#![allow(clippy::result_large_err)]
+// peers can send us anything, none of it may bring the node down
+#![deny(
+    clippy::unwrap_used,
+    clippy::expect_used,
+    clippy::panic,
+    clippy::indexing_slicing
+)]
 #[cfg(any(
     all(feature = "ed25519", feature = "blsttc"),
     all(feature = "bad_crypto", feature = "ed25519"),
@@ -7,9 +15,42 @@
 ))]
 compile_error!("Must enable either `ed25519`, `blsttc` or `bad_crypto` feature flags");
 
+pub(crate) mod attestation;
+pub(crate) mod compact;
+pub(crate) mod config;
+pub mod conformance;
+pub(crate) mod decision;
+pub(crate) mod descriptor;
+#[cfg(feature = "driver")]
+pub mod driver;
+pub(crate) mod fault;
+pub mod generation;
 pub mod handover;
+pub(crate) mod hash;
+pub(crate) mod history;
+#[cfg(feature = "testing")]
+pub(crate) mod hooks;
+pub mod migrate;
+#[cfg(feature = "testing")]
+pub mod oracle;
+pub(crate) mod outcome;
+#[cfg(feature = "testing")]
+pub mod properties;
 pub(crate) mod proposal;
+pub(crate) mod quorum;
+pub(crate) mod receipt;
+pub(crate) mod relay;
+pub(crate) mod report;
+pub(crate) mod sealed;
+pub(crate) mod signer;
+#[cfg(feature = "testing")]
+pub mod sim;
+pub(crate) mod snapshot;
+pub(crate) mod split;
+pub mod stream;
+pub mod v1;
 pub(crate) mod vote;
+pub mod wire;
 
 #[cfg(feature = "bad_crypto")]
 pub mod bad_crypto;
@@ -18,9 +59,41 @@ pub mod blsttc;
 #[cfg(feature = "ed25519")]
 pub mod ed25519;
 
+pub use crate::attestation::{SectionState, SectionStateProof, StateAttestation};
+pub use crate::compact::{
+    CompactBallot, CompactEntry, CompactVote, CompactVoteMsg, MAX_EXPANDED_VOTES,
+};
+pub use crate::config::{Config, BFT_MINIMUM_ELDERS};
+pub use crate::decision::{Decision, DecisionAnnounce, RehearsalProof};
+pub use crate::descriptor::{
+    ConfigDifference, ConfigHandshake, Limits, ProtocolDescriptor, QuorumRule, PROTOCOL_VERSION,
+};
+pub use crate::fault::Fault;
+pub use crate::generation::{GenerationPolicy, Increment};
 pub use crate::handover::HandoverState;
-pub use crate::proposal::Proposal;
-pub use crate::vote::{Ballot, Generation, SignedVote, Vote, VoteMsg};
+pub use crate::hash::{proposal_hash, Hash};
+pub use crate::history::{
+    AntiEntropy, CatchUp, ContinuationToken, History, HistoryStats, Participation, Round,
+    RoundStats, SyncRequest, VoterHistory,
+};
+#[cfg(feature = "testing")]
+pub use crate::hooks::{HookAction, Hooks};
+pub use crate::outcome::Outcome;
+pub use crate::proposal::{Proposal, ProposalEvent, ProposalSource, ProposalStatus};
+pub use crate::quorum::QuorumPolicy;
+pub use crate::receipt::{ConsensusReceipt, ReceiptSignature, TransitionReceipt, RECEIPT_FORMAT};
+pub use crate::relay::{
+    Relay, RelayId, RelayedVote, DEFAULT_RELAY_TTL, MAX_RELAY_HOPS, MAX_RELAY_SEEN,
+};
+pub use crate::report::{QuorumReport, Simulation};
+pub use crate::sealed::{Opening, Reveal, RevealOutcome, SealedProposal};
+pub use crate::signer::{KeyVerifier, Signer, Verifier, VoteSigner, VoteVerifier};
+pub use crate::snapshot::{InMemoryVoteLog, Snapshot, SnapshotDelta, VoteLog};
+pub use crate::split::{split_genesis, Prefix, Split, SplitPolicy};
+pub use crate::vote::{
+    Ballot, CorrelationId, Generation, Priority, Redacted, SignedVote, SigningDomain, Vote,
+    VoteDigest, VoteMsg, VoteSummary,
+};
 
 #[cfg(feature = "bad_crypto")]
 pub use crate::bad_crypto::{PublicKey, SecretKey, Signature};
@@ -30,5 +103,7 @@ pub use crate::blsttc::{PublicKey, SecretKey, Signature};
 pub use crate::ed25519::{PublicKey, SecretKey, Signature};
 
 pub mod error;
-pub use crate::error::Error;
+pub use crate::error::{
+    ConfigError, Error, ProtocolError, StateError, StorageError, TransportError,
+};
 pub type Result<T> = std::result::Result<T, Error>;
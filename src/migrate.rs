@@ -0,0 +1,165 @@
+//! Reading the state saved by earlier releases, for nodes upgrading in place.
+//!
+//! v0 is the release that first saved snapshots and vote logs: votes were those of the first
+//! release (`v1::SignedVote`), without extensions, snapshots had no watermark and the config
+//! only the tunables of that release. v0 votes are re-verified against the bytes v0 signed,
+//! which are also the bytes we sign for votes without extensions: those that verify carry over
+//! with the faults they back, the others are dropped.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Duration;
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::v1;
+use crate::{
+    Config, Fault, Generation, InMemoryVoteLog, Proposal, ProtocolError, PublicKey, Result,
+    SignedVote, Snapshot,
+};
+use core::fmt::Debug;
+
+/// A v0 snapshot in the current format, along with what couldn't be carried over.
+/// When votes were dropped we may have lost our own, restore the snapshot then
+/// `rebuild_from_peers` before voting again so we don't contradict our v0 vote.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Migrated<T>
+where
+    T: Ord + Serialize,
+{
+    pub snapshot: Snapshot<T>,
+    pub dropped_votes: usize,
+    pub dropped_faults: usize,
+}
+
+impl<T: Ord + Serialize> Migrated<T> {
+    pub fn must_rebuild(&self) -> bool {
+        self.dropped_votes > 0
+    }
+}
+
+/// A v0 vote log in the current format, along with how many of its votes didn't verify
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigratedLog<T>
+where
+    T: Ord + Serialize,
+{
+    pub log: InMemoryVoteLog<T>,
+    pub dropped_votes: usize,
+}
+
+// The v0 layouts, sets are encoded as sequences so we read them as such
+#[allow(clippy::large_enum_variant)]
+#[derive(Deserialize)]
+enum FaultV0<T: Ord> {
+    InvalidProposal {
+        vote: v1::SignedVote<T>,
+    },
+    ConflictingVotes {
+        first: v1::SignedVote<T>,
+        second: v1::SignedVote<T>,
+    },
+}
+
+#[derive(Deserialize)]
+struct ConfigV0 {
+    startup_grace_period: Duration,
+    generation_deadline: Option<Duration>,
+    clock_skew_tolerance: Duration,
+    unanimity_below_bft_minimum: bool,
+    rehearsal: bool,
+}
+
+#[derive(Deserialize)]
+struct SnapshotV0<T: Ord> {
+    gen: Generation,
+    votes: BTreeMap<PublicKey, v1::SignedVote<T>>,
+    voters: Vec<PublicKey>,
+    consensus: Option<T>,
+    faults: Vec<FaultV0<T>>,
+    config: ConfigV0,
+}
+
+#[derive(Deserialize)]
+struct VoteLogV0<T: Ord> {
+    votes: Vec<v1::SignedVote<T>>,
+}
+
+/// Reads a snapshot saved by a v0 release, see `Migrated` for what to do with it
+pub fn from_v0<T>(bytes: &[u8]) -> Result<Migrated<T>>
+where
+    T: Clone + Copy + Debug + Ord + Serialize + DeserializeOwned + Proposal,
+{
+    let v0: SnapshotV0<T> = bincode::deserialize(bytes).map_err(ProtocolError::malformed)?;
+    let config = Config {
+        startup_grace_period: v0.config.startup_grace_period,
+        generation_deadline: v0.config.generation_deadline,
+        clock_skew_tolerance: v0.config.clock_skew_tolerance,
+        unanimity_below_bft_minimum: v0.config.unanimity_below_bft_minimum,
+        rehearsal: v0.config.rehearsal,
+        ..Default::default()
+    };
+
+    let n_votes = v0.votes.len();
+    let votes = BTreeMap::from_iter(
+        v0.votes
+            .into_iter()
+            .filter(|(voter, vote)| *voter == vote.voter)
+            .filter_map(|(voter, vote)| Some((voter, verified(vote)?))),
+    );
+    let n_faults = v0.faults.len();
+    let faults = BTreeSet::from_iter(v0.faults.into_iter().filter_map(|fault| match fault {
+        FaultV0::InvalidProposal { vote } => Some(Fault::InvalidProposal {
+            vote: verified(vote)?,
+        }),
+        FaultV0::ConflictingVotes { first, second } => Some(Fault::ConflictingVotes {
+            first: verified(first)?,
+            second: verified(second)?,
+        }),
+    }));
+
+    Ok(Migrated {
+        dropped_votes: n_votes - votes.len(),
+        dropped_faults: n_faults - faults.len(),
+        snapshot: Snapshot {
+            gen: v0.gen,
+            votes,
+            watermark: None,
+            voters: v0.voters.into_iter().collect(),
+            consensus: v0.consensus,
+            faults,
+            config,
+            history: Default::default(),
+            stats: Default::default(),
+        },
+    })
+}
+
+/// Reads a vote log saved by a v0 release, replay it onto the migrated snapshot
+pub fn from_v0_log<T>(bytes: &[u8]) -> Result<MigratedLog<T>>
+where
+    T: Clone + Copy + Debug + Ord + Serialize + DeserializeOwned + Proposal,
+{
+    let v0: VoteLogV0<T> = bincode::deserialize(bytes).map_err(ProtocolError::malformed)?;
+    let n_votes = v0.votes.len();
+    let votes: Vec<_> = v0.votes.into_iter().filter_map(verified).collect();
+    Ok(MigratedLog {
+        dropped_votes: n_votes - votes.len(),
+        log: InMemoryVoteLog {
+            votes,
+            stats: Default::default(),
+        },
+    })
+}
+
+// The vote in the current format, if it verifies as we verify votes, which are the bytes v0 signed
+fn verified<T>(vote: v1::SignedVote<T>) -> Option<SignedVote<T>>
+where
+    T: Clone + Copy + Debug + Ord + Serialize + DeserializeOwned + Proposal,
+{
+    let vote = SignedVote::from(vote);
+    let verifies = vote
+        .unpack_votes()
+        .into_iter()
+        .all(|vote| vote.validate_signature().is_ok());
+    verifies.then_some(vote)
+}
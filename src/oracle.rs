@@ -0,0 +1,149 @@
+//! A reference implementation of the decision rule, to check `HandoverState` against.
+//! Enabled by the `testing` feature.
+//!
+//! It keeps every voter's latest vote among those delivered and re-counts all of them on each
+//! delivery: a set of proposals is decided once the voters whose latest vote is a valid super
+//! majority ballot for it make a quorum, or, when allowed below `BFT_MINIMUM_ELDERS`, once all
+//! voters voted for it. Slow and simple, it shares none of the bookkeeping of the real path.
+//! It assumes honest voters: conflicting votes are not told apart from older ones.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::hash::{self, Hash};
+use crate::{
+    Ballot, Config, Generation, KeyVerifier, Proposal, PublicKey, QuorumPolicy, Result, SignedVote,
+    SigningDomain, BFT_MINIMUM_ELDERS,
+};
+use core::fmt::Debug;
+
+/// What an elder of generation `gen` should decide, given the votes delivered to it
+#[derive(Debug)]
+pub struct Oracle<T>
+where
+    T: Ord + Serialize,
+{
+    gen: Generation,
+    voters: BTreeSet<PublicKey>,
+    policy: QuorumPolicy,
+    unanimity_below_bft_minimum: bool,
+    domain: SigningDomain,
+    votes: BTreeMap<PublicKey, SignedVote<T>>,
+    decided: Option<Hash>,
+}
+
+impl<T> Oracle<T>
+where
+    T: Clone + Copy + Debug + Ord + Serialize + DeserializeOwned + Proposal,
+{
+    /// The oracle of an elder running with `config`
+    pub fn new(gen: Generation, voters: BTreeSet<PublicKey>, config: &Config) -> Self {
+        Self {
+            gen,
+            voters,
+            policy: config.quorum_policy.clone(),
+            unanimity_below_bft_minimum: config.unanimity_below_bft_minimum,
+            domain: config.signing_domain(),
+            votes: Default::default(),
+            decided: None,
+        }
+    }
+
+    /// Takes in a vote as its elder got it, votes the elder would refuse are ignored
+    pub fn deliver(&mut self, vote: &SignedVote<T>) -> Result<()> {
+        if self.decided.is_some() {
+            return Ok(());
+        }
+        let votes = vote.unpack_votes();
+        if !votes.iter().all(|vote| self.is_valid(vote)) {
+            return Ok(());
+        }
+        for vote in votes {
+            let is_newer = match self.votes.get(&vote.voter) {
+                Some(latest) => vote.supersedes(latest),
+                None => true,
+            };
+            if is_newer {
+                self.votes.insert(vote.voter, vote.clone());
+            }
+        }
+        self.decided = self.decide()?;
+        Ok(())
+    }
+
+    /// The hash of the decided proposal, once the delivered votes decided one
+    pub fn decided(&self) -> Option<Hash> {
+        self.decided
+    }
+
+    fn is_valid(&self, vote: &SignedVote<T>) -> bool {
+        vote.vote.gen == self.gen
+            && self.voters.contains(&vote.voter)
+            && vote
+                .validate_signature_with(&KeyVerifier, self.domain)
+                .is_ok()
+    }
+
+    fn decide(&self) -> Result<Option<Hash>> {
+        let mut backers: BTreeMap<BTreeSet<Hash>, BTreeSet<PublicKey>> = BTreeMap::new();
+        let mut super_majority_backers: BTreeMap<BTreeSet<Hash>, BTreeSet<PublicKey>> =
+            BTreeMap::new();
+        for (voter, vote) in self.votes.iter() {
+            let proposals = vote.proposal_set()?;
+            if self.is_super_majority_ballot(vote)? {
+                super_majority_backers
+                    .entry(proposals.clone())
+                    .or_default()
+                    .insert(*voter);
+            }
+            backers.entry(proposals).or_default().insert(*voter);
+        }
+
+        let unanimous = self.unanimity_below_bft_minimum
+            && self.voters.len() < BFT_MINIMUM_ELDERS
+            && backers.len() == 1
+            && backers.values().all(|voters| voters == &self.voters);
+        if unanimous {
+            return self.winner(backers.keys().next());
+        }
+
+        let decided = super_majority_backers
+            .iter()
+            .find(|(_, voters)| self.is_quorum(voters))
+            .map(|(proposals, _)| proposals);
+        self.winner(decided)
+    }
+
+    // A super majority ballot counts when what it saw has a quorum behind one set of proposals
+    fn is_super_majority_ballot(&self, vote: &SignedVote<T>) -> Result<bool> {
+        let seen = match &vote.vote.ballot {
+            Ballot::SuperMajority(seen) => seen,
+            _ => return Ok(false),
+        };
+        let mut backers: BTreeMap<BTreeSet<Hash>, BTreeSet<PublicKey>> = BTreeMap::new();
+        for seen_vote in seen.iter().flat_map(SignedVote::unpack_votes) {
+            backers
+                .entry(seen_vote.proposal_set()?)
+                .or_default()
+                .insert(seen_vote.voter);
+        }
+        Ok(backers.values().any(|voters| self.is_quorum(voters)))
+    }
+
+    fn is_quorum(&self, voters: &BTreeSet<PublicKey>) -> bool {
+        self.policy
+            .is_quorum(self.policy.weight_of(voters), &self.voters)
+    }
+
+    // Ties are broken by rank, as every elder does
+    fn winner(&self, proposals: Option<&BTreeSet<Hash>>) -> Result<Option<Hash>> {
+        let seed = hash::round_seed(self.gen, &self.voters)?;
+        Ok(proposals.and_then(|proposals| {
+            proposals
+                .iter()
+                .max_by_key(|proposal| hash::rank(&seed, proposal))
+                .copied()
+        }))
+    }
+}
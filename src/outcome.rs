@@ -0,0 +1,47 @@
+use serde::Serialize;
+
+use crate::{Decision, Fault, ProposalEvent, VoteMsg};
+
+/// What came out of handling a vote
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outcome<T>
+where
+    T: Ord + Serialize,
+{
+    /// The votes to send in response
+    pub msgs: Vec<VoteMsg<T>>,
+    /// Set when this vote terminated the round, with the proof of the decision
+    pub decision: Option<Decision<T>>,
+    /// Misbehaviour we caught in this vote, the vote itself was not accepted
+    pub faults: Vec<Fault<T>>,
+    /// How the candidate proposals moved on since the previous outcome, our proposal included
+    pub events: Vec<ProposalEvent<T>>,
+}
+
+impl<T> Default for Outcome<T>
+where
+    T: Ord + Serialize,
+{
+    fn default() -> Self {
+        Self {
+            msgs: Vec::new(),
+            decision: None,
+            faults: Vec::new(),
+            events: Vec::new(),
+        }
+    }
+}
+
+impl<T> From<Vec<VoteMsg<T>>> for Outcome<T>
+where
+    T: Ord + Serialize,
+{
+    fn from(msgs: Vec<VoteMsg<T>>) -> Self {
+        Self {
+            msgs,
+            decision: None,
+            faults: Vec::new(),
+            events: Vec::new(),
+        }
+    }
+}
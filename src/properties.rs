@@ -0,0 +1,278 @@
+//! The BFT safety properties as quickcheck properties over the simulated network, to check this
+//! crate and `Proposal` implementations downstream. Enabled by the `testing` feature.
+//!
+//! They are quickcheck properties rather than proptest ones: quickcheck is what our tests
+//! already run on, `Schedule::shrink` does the shrinking a proptest strategy would.
+//!
+//! A `Schedule` is an arbitrary run: how many elders, how many of them are byzantine (up to f
+//! of n >= 3f + 1), what the link does to the packets and the seed picking the deliveries.
+//! Byzantine elders forge votes on top of taking part, and may equivocate on their proposal.
+//!
+//! `check` runs a property at the scale of `SN_HANDOVER_PROPERTIES_SCALE`: small by default,
+//! for CI, `large` for sections of up to 16 elders and many more schedules.
+
+use std::collections::BTreeSet;
+use std::env;
+
+use quickcheck::{Arbitrary, Gen, QuickCheck, TestResult};
+use rand::prelude::StdRng;
+use rand::SeedableRng;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::oracle::Oracle;
+use crate::sim::{LinkFaults, Net, Packet};
+use crate::{proposal_hash, Ballot, HandoverState, Proposal, PublicKey, Result, Vote, VoteMsg};
+use core::fmt::Debug;
+
+/// Picks the scale the properties are checked at, `large` or anything else for small
+pub const SCALE_VAR: &str = "SN_HANDOVER_PROPERTIES_SCALE";
+
+/// How hard `check` exercises a property
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Scale {
+    pub max_elders: usize,
+    pub schedules: u64,
+}
+
+impl Scale {
+    pub const SMALL: Scale = Scale {
+        max_elders: 5,
+        schedules: 16,
+    };
+    pub const LARGE: Scale = Scale {
+        max_elders: 16,
+        schedules: 256,
+    };
+
+    pub fn from_env() -> Self {
+        match env::var(SCALE_VAR).as_deref() {
+            Ok("large") => Self::LARGE,
+            _ => Self::SMALL,
+        }
+    }
+}
+
+/// An arbitrary run of a round
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schedule {
+    pub elders: usize,
+    pub byzantine: usize,
+    /// Forged votes each byzantine elder sends
+    pub forged: usize,
+    /// Whether byzantine elders propose something else to each elder, their forgeries may then
+    /// verify too. Otherwise they propose once like the others and none of their forgeries verify.
+    pub equivocate: bool,
+    pub faults: LinkFaults,
+    pub seed: u64,
+}
+
+impl Arbitrary for Schedule {
+    fn arbitrary(g: &mut Gen) -> Self {
+        let elders = usize::arbitrary(g) % Scale::from_env().max_elders + 1;
+        let mut rate = || f64::from(u8::arbitrary(g) % 30) / 100.0;
+        let faults = LinkFaults {
+            drop: rate(),
+            duplicate: rate(),
+            reorder: rate(),
+            delay: rate(),
+            max_delay: usize::arbitrary(g) % 8,
+        };
+        Self {
+            elders,
+            byzantine: usize::arbitrary(g) % ((elders - 1) / 3 + 1),
+            forged: usize::arbitrary(g) % 8,
+            equivocate: bool::arbitrary(g),
+            faults,
+            seed: u64::arbitrary(g),
+        }
+    }
+
+    // Towards fewer byzantine elders, fewer elders, no equivocation and a perfect link
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        let mut smaller = Vec::new();
+        if self.byzantine > 0 {
+            smaller.push(Self {
+                byzantine: self.byzantine - 1,
+                ..self.clone()
+            });
+        }
+        if self.elders > 3 * self.byzantine + 1 {
+            smaller.push(Self {
+                elders: self.elders - 1,
+                ..self.clone()
+            });
+        }
+        if self.forged > 0 {
+            smaller.push(Self {
+                forged: 0,
+                ..self.clone()
+            });
+        }
+        if self.equivocate {
+            smaller.push(Self {
+                equivocate: false,
+                ..self.clone()
+            });
+        }
+        if self.faults != LinkFaults::default() {
+            smaller.push(Self {
+                faults: Default::default(),
+                ..self.clone()
+            });
+        }
+        Box::new(smaller.into_iter())
+    }
+}
+
+/// Checks `property` over as many schedules as the scale asks for, panics on a failure
+pub fn check(property: fn(Schedule) -> TestResult) {
+    let scale = Scale::from_env();
+    QuickCheck::new()
+        .tests(scale.schedules)
+        .max_tests(scale.schedules * 10)
+        .quickcheck(property)
+}
+
+/// Runs the round of `schedule`, each elder proposing what `proposal` draws for it
+pub fn run<T>(schedule: &Schedule, mut proposal: impl FnMut(&mut StdRng) -> T) -> Result<Net<T>>
+where
+    T: Clone + Copy + Debug + Ord + Serialize + DeserializeOwned + Proposal + Send,
+{
+    let mut rng = StdRng::seed_from_u64(schedule.seed);
+    let mut net = Net::with_procs(schedule.elders, &mut rng).with_all_voters();
+    net.faulty = BTreeSet::from_iter(
+        net.procs
+            .iter()
+            .take(schedule.byzantine)
+            .map(HandoverState::public_key),
+    );
+
+    let honest_from = if schedule.equivocate {
+        schedule.byzantine
+    } else {
+        0
+    };
+    for i in honest_from..net.procs.len() {
+        let proposal = proposal(&mut rng);
+        net.propose(i, proposal)?;
+    }
+    let dests = Vec::from_iter(net.procs.iter().map(HandoverState::public_key));
+    let mut equivocations = Vec::new();
+    for proc in net.procs.iter().take(honest_from) {
+        for dest in dests.iter() {
+            let proposal = proposal(&mut rng);
+            equivocations.push(propose_to(proc, *dest, proposal)?);
+        }
+    }
+    net.proposals.extend(
+        equivocations
+            .iter()
+            .flat_map(|p| p.vote_msg.vote.proposals())
+            .map(|(_, p)| p),
+    );
+    net.enqueue_packets(equivocations);
+    let forged = schedule.forged * schedule.byzantine;
+    let mut forged =
+        Vec::from_iter((0..forged).map(|_| net.gen_faulty_packet(2, &net.faulty, &mut rng)));
+    if !schedule.equivocate {
+        // a forgery picking its signer as voter is a vote it could contradict
+        forged.retain(|p| p.vote_msg.vote.validate_signature().is_err());
+    }
+    net.enqueue_packets(forged);
+
+    net.drain_with_faults(schedule.faults, &mut rng)?;
+    Ok(net)
+}
+
+// Byzantine elders propose something else to each of the others
+fn propose_to<T>(proc: &HandoverState<T>, dest: PublicKey, proposal: T) -> Result<Packet<T>>
+where
+    T: Clone + Copy + Debug + Ord + Serialize + DeserializeOwned + Proposal + Send,
+{
+    let vote = proc.sign_vote(Vote {
+        gen: proc.gen,
+        ballot: Ballot::Propose(proposal),
+        extensions: Default::default(),
+    })?;
+    Ok(Packet {
+        source: proc.public_key(),
+        vote_msg: VoteMsg {
+            vote,
+            dest,
+            correlation_id: None,
+            priority: Default::default(),
+        },
+    })
+}
+
+/// Agreement, validity and proven decisions across the honest elders, see `Net::check_invariants`
+pub fn safety<T>(schedule: &Schedule, proposal: impl FnMut(&mut StdRng) -> T) -> TestResult
+where
+    T: Clone + Copy + Debug + Ord + Serialize + DeserializeOwned + Proposal + Send,
+{
+    match run(schedule, proposal).map(|net| net.check_invariants()) {
+        Ok(Ok(())) => TestResult::passed(),
+        Ok(Err(violation)) => TestResult::error(violation.to_string()),
+        Err(err) => TestResult::error(err.report()),
+    }
+}
+
+/// On top of `safety`, the elders all decide. Schedules with equivocating elders don't count:
+/// conflicting votes are recorded as faults, not excluded, an equivocating elder can stall a round.
+pub fn termination<T>(schedule: &Schedule, proposal: impl FnMut(&mut StdRng) -> T) -> TestResult
+where
+    T: Clone + Copy + Debug + Ord + Serialize + DeserializeOwned + Proposal + Send,
+{
+    if schedule.byzantine > 0 && schedule.equivocate {
+        return TestResult::discard();
+    }
+    let net = match run(schedule, proposal) {
+        Ok(net) => net,
+        Err(err) => return TestResult::error(err.report()),
+    };
+    match net.check_invariants() {
+        Ok(()) if net.honest_procs_decided() => TestResult::passed(),
+        Ok(()) => TestResult::error("some elders did not decide"),
+        Err(violation) => TestResult::error(violation.to_string()),
+    }
+}
+
+/// The decisions of the elders match what the `Oracle` decides from the votes delivered to
+/// them. Like `termination`, schedules with equivocating elders don't count.
+pub fn matches_oracle<T>(schedule: &Schedule, proposal: impl FnMut(&mut StdRng) -> T) -> TestResult
+where
+    T: Clone + Copy + Debug + Ord + Serialize + DeserializeOwned + Proposal + Send,
+{
+    if schedule.byzantine > 0 && schedule.equivocate {
+        return TestResult::discard();
+    }
+    let net = match run(schedule, proposal) {
+        Ok(net) => net,
+        Err(err) => return TestResult::error(err.report()),
+    };
+    for proc in net.procs.iter() {
+        let mut oracle = Oracle::new(proc.gen, proc.voters.clone(), &proc.config);
+        let delivered = net
+            .delivered_packets
+            .iter()
+            .filter(|p| p.vote_msg.dest == proc.public_key());
+        for packet in delivered {
+            if let Err(err) = oracle.deliver(&packet.vote_msg.vote) {
+                return TestResult::error(err.report());
+            }
+        }
+        let decided = match proc.consensus.as_ref().map(proposal_hash).transpose() {
+            Ok(decided) => decided,
+            Err(err) => return TestResult::error(err.report()),
+        };
+        if decided != oracle.decided() {
+            return TestResult::error(format!(
+                "{:?} decided {:?}, the oracle {:?}",
+                proc.public_key(),
+                decided,
+                oracle.decided()
+            ));
+        }
+    }
+    TestResult::passed()
+}
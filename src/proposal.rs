@@ -1,5 +1,53 @@
-use crate::Result;
+use core::fmt::Debug;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Generation, ProtocolError, Result};
 
 pub trait Proposal {
     fn validate(&self) -> Result<()>;
+
+    /// Proposals with the same key are one and the same candidate, whatever their encoding.
+    /// Defaults to the canonical (bincode) encoding, override it when semantically identical
+    /// proposals can be encoded differently, otherwise they split the vote.
+    fn dedup_key(&self) -> Result<Vec<u8>>
+    where
+        Self: Serialize,
+    {
+        bincode::serialize(self).map_err(ProtocolError::malformed)
+    }
+}
+
+/// Supplies the proposals of the application, the state polls it when it's our turn to propose
+pub trait ProposalSource<T>: Debug + Send + Sync {
+    /// The proposal we would like to see decided in generation `gen`, if any
+    fn next_proposal(&self, gen: Generation) -> Option<T>;
+}
+
+/// Where a candidate proposal of the current round stands, from our point of view.
+/// A proposal only moves forward, up to being decided or abandoned for another one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum ProposalStatus {
+    /// We came across it in a vote
+    Seen,
+    /// Our own vote backs it
+    EndorsedByMe,
+    /// A super majority ballot backs it, the round is converging on it
+    SuperMajorityForming,
+    Decided,
+    /// The round decided another proposal
+    Abandoned,
+}
+
+impl ProposalStatus {
+    pub fn is_final(&self) -> bool {
+        matches!(self, ProposalStatus::Decided | ProposalStatus::Abandoned)
+    }
+}
+
+/// A candidate proposal reached a new status
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ProposalEvent<T> {
+    pub proposal: T,
+    pub status: ProposalStatus,
 }
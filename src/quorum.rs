@@ -0,0 +1,97 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{ConfigError, PublicKey, QuorumRule, Result};
+
+/// How much agreement decides, ballot counting and super majority validation consult it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum QuorumPolicy {
+    /// Strictly more than `numerator / denominator` of the voters
+    SuperMajority { numerator: u64, denominator: u64 },
+    /// Strictly more than 2/3 of the voters' total stake, voters without a stake weigh nothing
+    Weighted(BTreeMap<PublicKey, u64>),
+}
+
+impl Default for QuorumPolicy {
+    fn default() -> Self {
+        Self::SuperMajority {
+            numerator: 2,
+            denominator: 3,
+        }
+    }
+}
+
+impl QuorumPolicy {
+    /// Thresholds below 2/3 can't tolerate a third of faulty voters, we refuse them
+    pub fn validate(&self) -> Result<()> {
+        let invalid =
+            |reason: &str| Err(ConfigError::InvalidQuorumPolicy(reason.to_string()).into());
+        match self {
+            Self::SuperMajority {
+                numerator,
+                denominator,
+            } => {
+                if numerator >= denominator {
+                    return invalid("the threshold must be below all the voters");
+                }
+                if (3 * *numerator as u128) < 2 * *denominator as u128 {
+                    return invalid("the threshold must be at least 2/3");
+                }
+            }
+            Self::Weighted(stakes) => {
+                if stakes.values().all(|stake| *stake == 0) {
+                    return invalid("no voter has a stake");
+                }
+                let total = stakes
+                    .values()
+                    .try_fold(0u64, |total, stake| total.checked_add(*stake));
+                if total.is_none() {
+                    return invalid("the total stake must fit in 64 bits");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The fraction of the voters (or of their stake) a quorum must exceed
+    pub fn rule(&self) -> QuorumRule {
+        match self {
+            Self::SuperMajority {
+                numerator,
+                denominator,
+            } => QuorumRule {
+                numerator: *numerator,
+                denominator: *denominator,
+            },
+            Self::Weighted(_) => QuorumRule {
+                numerator: 2,
+                denominator: 3,
+            },
+        }
+    }
+
+    pub fn weight(&self, voter: &PublicKey) -> u64 {
+        match self {
+            Self::SuperMajority { .. } => 1,
+            Self::Weighted(stakes) => stakes.get(voter).copied().unwrap_or_default(),
+        }
+    }
+
+    /// Total weight of `voters`, saturating for policies that skipped `validate`
+    pub fn weight_of<'a>(&self, voters: impl IntoIterator<Item = &'a PublicKey>) -> u64 {
+        voters
+            .into_iter()
+            .fold(0, |total, voter| total.saturating_add(self.weight(voter)))
+    }
+
+    /// Whether `weight` is a quorum of `voters`
+    pub fn is_quorum(&self, weight: u64, voters: &BTreeSet<PublicKey>) -> bool {
+        let QuorumRule {
+            numerator,
+            denominator,
+        } = self.rule();
+        // stakes can be large, keep the products from overflowing
+        denominator as u128 * weight as u128 > numerator as u128 * self.weight_of(voters) as u128
+    }
+}
@@ -0,0 +1,185 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    proposal_hash, Decision, Generation, Hash, Proposal, ProtocolDescriptor, PublicKey,
+    QuorumPolicy, QuorumRule, Result, Round, Signature, SigningDomain,
+};
+use core::fmt::Debug;
+
+/// Tells audit tooling which receipt layout it's reading
+pub const RECEIPT_FORMAT: &str = "consensus-receipt/v1";
+
+/// A deciding vote as an auditor checks it: `signature` of `message` by `signer`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReceiptSignature {
+    pub signer: PublicKey,
+    pub message: Vec<u8>,
+    pub signature: Signature,
+}
+
+/// A terminated round in the consensus receipt structure generic BFT monitoring ingests:
+/// the participants with their weights, the hash of the decided value, the signatures that
+/// decided it and the fraction of the total weight they had to exceed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConsensusReceipt {
+    pub gen: Generation,
+    pub value_hash: Hash,
+    pub participants: BTreeMap<PublicKey, u64>,
+    pub threshold: QuorumRule,
+    pub signatures: Vec<ReceiptSignature>,
+    pub descriptor: ProtocolDescriptor,
+}
+
+impl ConsensusReceipt {
+    #[allow(clippy::clone_on_copy)] // signatures are only Copy with bad_crypto
+    pub fn of<'de, T>(
+        round: &Round<T>,
+        policy: &QuorumPolicy,
+        domain: SigningDomain,
+    ) -> Result<Self>
+    where
+        T: Clone + Copy + Debug + Ord + Serialize + Deserialize<'de> + Proposal,
+    {
+        let mut signatures = Vec::new();
+        for vote in round.decision.votes.iter() {
+            signatures.push(ReceiptSignature {
+                signer: vote.voter,
+                message: vote.vote.signing_bytes(domain)?,
+                signature: vote.sig.clone(),
+            });
+        }
+        Ok(Self {
+            gen: round.decision.gen,
+            value_hash: proposal_hash(&round.decision.proposal)?,
+            participants: BTreeMap::from_iter(round.voters.iter().map(|v| (*v, policy.weight(v)))),
+            threshold: policy.rule(),
+            signatures,
+            descriptor: ProtocolDescriptor {
+                quorum_rule: policy.rule(),
+                ..Default::default()
+            },
+        })
+    }
+
+    /// The receipt as JSON, without whitespace, in this layout:
+    ///
+    /// ```text
+    /// {"format":"consensus-receipt/v1","protocol":"sn_handover","protocol_version":<integer>,
+    ///  "encoding":<string>,"hash_algorithm":<string>,"signature_scheme":<string>,
+    ///  "height":<integer>,"value_hash":<hex>,
+    ///  "threshold":{"numerator":<integer>,"denominator":<integer>},
+    ///  "participants":[{"key":<hex>,"weight":<integer>},...],
+    ///  "signatures":[{"signer":<hex>,"message":<hex>,"signature":<hex>},...]}
+    /// ```
+    ///
+    /// Keys and signatures are hex of their `encoding`, messages hex of the bytes signed.
+    /// Participants are sorted by key, signatures in the order of the deciding votes.
+    pub fn to_json(&self) -> Result<String> {
+        let mut participants = Vec::new();
+        for (key, weight) in self.participants.iter() {
+            participants.push(format!(
+                r#"{{"key":"{}","weight":{}}}"#,
+                hex_of(key)?,
+                weight
+            ));
+        }
+        let mut signatures = Vec::new();
+        for sig in self.signatures.iter() {
+            signatures.push(format!(
+                r#"{{"signer":"{}","message":"{}","signature":"{}"}}"#,
+                hex_of(&sig.signer)?,
+                hex::encode(&sig.message),
+                hex_of(&sig.signature)?
+            ));
+        }
+
+        let d = &self.descriptor;
+        Ok(format!(
+            concat!(
+                r#"{{"format":"{}","protocol":"sn_handover","protocol_version":{},"#,
+                r#""encoding":"{}","hash_algorithm":"{}","signature_scheme":"{}","#,
+                r#""height":{},"value_hash":"{}","#,
+                r#""threshold":{{"numerator":{},"denominator":{}}},"#,
+                r#""participants":[{}],"signatures":[{}]}}"#
+            ),
+            RECEIPT_FORMAT,
+            d.version,
+            json_escaped(&d.encoding),
+            json_escaped(&d.hash_algorithm),
+            json_escaped(&d.signature_scheme),
+            self.gen,
+            hex::encode(self.value_hash.as_bytes()),
+            self.threshold.numerator,
+            self.threshold.denominator,
+            participants.join(","),
+            signatures.join(","),
+        ))
+    }
+}
+
+/// A completed handover, in the shape of a section state update:
+/// `new_elders` took over from `old_elders` at generation `gen`, deciding `value_hash`.
+/// `proof_hash` identifies the decision proof we hold, elders may hold different proofs.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct TransitionReceipt {
+    pub old_elders: BTreeSet<PublicKey>,
+    pub new_elders: BTreeSet<PublicKey>,
+    pub gen: Generation,
+    pub value_hash: Hash,
+    pub proof_hash: Hash,
+}
+
+impl TransitionReceipt {
+    pub fn of<T: Ord + Serialize + Proposal>(
+        round: &Round<T>,
+        gen: Generation,
+        new_elders: BTreeSet<PublicKey>,
+    ) -> Result<Self> {
+        Ok(Self {
+            old_elders: round.voters.clone(),
+            new_elders,
+            gen,
+            value_hash: proposal_hash(&round.decision.proposal)?,
+            proof_hash: proof_hash(&round.decision)?,
+        })
+    }
+
+    /// Identifies the handover, the same on every elder, platform and version of the crate.
+    /// The proof is left out, only what all elders agree on goes in.
+    pub fn hash(&self) -> Result<Hash> {
+        let agreed = (
+            &self.old_elders,
+            &self.new_elders,
+            self.gen,
+            self.value_hash,
+        );
+        Ok(Hash::of_parts([
+            b"sn_handover/transition/".as_slice(),
+            &bincode::serialize(&agreed)?,
+        ]))
+    }
+}
+
+fn proof_hash<T: Ord + Serialize>(decision: &Decision<T>) -> Result<Hash> {
+    Ok(Hash::of(&bincode::serialize(decision)?))
+}
+
+fn hex_of(value: &impl Serialize) -> Result<String> {
+    Ok(hex::encode(bincode::serialize(value)?))
+}
+
+// `text` as it goes between the quotes of a JSON string
+fn json_escaped(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
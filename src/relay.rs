@@ -0,0 +1,183 @@
+//! Forwards votes between elders that can't reach each other directly (NAT, bridges).
+//! A relay holds no key and never votes, it only passes on what is signed by the elders.
+
+use core::fmt::Debug;
+use std::collections::{BTreeSet, VecDeque};
+
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Generation, Hash, KeyVerifier, Proposal, ProtocolError, PublicKey, Result, SigningDomain,
+    VoteMsg, VoteVerifier,
+};
+
+/// Names a relay along the path of a vote, it's not a key: relays don't sign anything
+pub type RelayId = u64;
+
+/// How many relays a vote goes through before it's dropped, unless the sender says otherwise
+pub const DEFAULT_RELAY_TTL: u8 = 8;
+
+/// No vote goes through more relays than this, whatever its TTL
+pub const MAX_RELAY_HOPS: usize = 32;
+
+/// How many forwarded votes a relay remembers to drop copies of them, the oldest are forgotten first
+pub const MAX_RELAY_SEEN: usize = 1 << 16;
+
+/// A vote message in transit, with the relays it went through and the hops it has left.
+/// Relays forward a vote to a destination once, copies and resends of it are dropped.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Debug)]
+pub struct RelayedVote<T>
+where
+    T: Ord + Serialize,
+{
+    pub msg: VoteMsg<T>,
+    pub ttl: u8,
+    pub path: Vec<RelayId>,
+}
+
+impl<T: Ord + Serialize> RelayedVote<T> {
+    pub fn new(msg: VoteMsg<T>) -> Self {
+        Self::with_ttl(msg, DEFAULT_RELAY_TTL)
+    }
+
+    pub fn with_ttl(msg: VoteMsg<T>, ttl: u8) -> Self {
+        Self {
+            msg,
+            ttl,
+            path: Vec::new(),
+        }
+    }
+
+    /// The message to hand to the destination elder
+    pub fn into_msg(self) -> VoteMsg<T> {
+        self.msg
+    }
+}
+
+/// Validates, deduplicates and forwards votes of a generation between its voters
+#[derive(Debug)]
+pub struct Relay {
+    id: RelayId,
+    gen: Generation,
+    voters: BTreeSet<PublicKey>,
+    relays: BTreeSet<RelayId>,
+    verifier: Box<dyn VoteVerifier>,
+    domain: SigningDomain,
+    seen: BTreeSet<(PublicKey, Hash)>, // (dest, vote) we forwarded this generation
+    seen_order: VecDeque<(PublicKey, Hash)>,
+}
+
+impl Relay {
+    /// A relay between `voters`, passing on only what went through the known `relays`
+    pub fn new(
+        id: RelayId,
+        gen: Generation,
+        voters: BTreeSet<PublicKey>,
+        relays: BTreeSet<RelayId>,
+    ) -> Self {
+        Self {
+            id,
+            gen,
+            voters,
+            relays,
+            verifier: Box::new(KeyVerifier),
+            domain: SigningDomain::Live,
+            seen: Default::default(),
+            seen_order: Default::default(),
+        }
+    }
+
+    /// Check the elders' signatures with another verifier, e.g. to batch them
+    pub fn with_verifier(mut self, verifier: impl VoteVerifier + 'static) -> Self {
+        self.verifier = Box::new(verifier);
+        self
+    }
+
+    /// Relay the votes of a rehearsal instead of live votes
+    pub fn with_signing_domain(mut self, domain: SigningDomain) -> Self {
+        self.domain = domain;
+        self
+    }
+
+    pub fn id(&self) -> RelayId {
+        self.id
+    }
+
+    pub fn gen(&self) -> Generation {
+        self.gen
+    }
+
+    /// Move on to the next generation, the application knows its voters as it does for elders
+    pub fn advance(&mut self, gen: Generation, voters: BTreeSet<PublicKey>) {
+        info!("[MBR] Relay {} moving to gen {}", self.id, gen);
+        self.gen = gen;
+        self.voters = voters;
+        self.seen = Default::default();
+        self.seen_order = Default::default();
+    }
+
+    /// The vote to pass on to the next hop, `None` when we already forwarded it to its destination.
+    /// Votes that are out of hops, went through us or an unknown relay, or don't validate are refused.
+    pub fn relay<T>(&mut self, mut relayed: RelayedVote<T>) -> Result<Option<RelayedVote<T>>>
+    where
+        T: Clone + Copy + Debug + Ord + Serialize + for<'de> Deserialize<'de> + Proposal,
+    {
+        if relayed.ttl == 0 || relayed.path.len() >= MAX_RELAY_HOPS {
+            return Err(ProtocolError::RelayTtlExpired.into());
+        }
+        if relayed.path.contains(&self.id) {
+            return Err(ProtocolError::RelayLoop(self.id).into());
+        }
+        if let Some(hop) = relayed.path.iter().find(|hop| !self.relays.contains(hop)) {
+            return Err(ProtocolError::UnknownRelay(*hop).into());
+        }
+        self.validate(&relayed.msg)?;
+
+        let seen = (relayed.msg.dest, relayed.msg.vote.hash()?);
+        if !self.seen.insert(seen) {
+            return Ok(None);
+        }
+        self.seen_order.push_back(seen);
+        if self.seen_order.len() > MAX_RELAY_SEEN {
+            if let Some(oldest) = self.seen_order.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+
+        relayed.ttl -= 1;
+        relayed.path.push(self.id);
+        Ok(Some(relayed))
+    }
+
+    fn validate<T>(&self, msg: &VoteMsg<T>) -> Result<()>
+    where
+        T: Clone + Copy + Debug + Ord + Serialize + for<'de> Deserialize<'de> + Proposal,
+    {
+        self.validate_is_member(msg.dest)?;
+        for signed_vote in msg.vote.unpack_votes() {
+            if signed_vote.vote.gen != self.gen {
+                return Err(ProtocolError::VoteWithInvalidGeneration {
+                    vote_gen: signed_vote.vote.gen,
+                    gen: self.gen,
+                }
+                .into());
+            }
+            self.validate_is_member(signed_vote.voter)?;
+            signed_vote.validate_signature_with(&*self.verifier, self.domain)?;
+        }
+        Ok(())
+    }
+
+    fn validate_is_member(&self, public_key: PublicKey) -> Result<()> {
+        if !self.voters.contains(&public_key) {
+            Err(ProtocolError::NonMember {
+                public_key,
+                members: self.voters.clone(),
+            }
+            .into())
+        } else {
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,32 @@
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Generation, PublicKey};
+
+/// Who took part in a decision
+/// - the quorum is the set of voters whose super majority votes decided
+/// - outside quorum are the voters we heard from but that didn't sign a deciding super majority
+/// - absent are the eligible voters we never received a vote from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuorumReport<T> {
+    pub gen: Generation,
+    pub decision: T,
+    pub quorum: BTreeSet<PublicKey>,
+    pub outside_quorum: BTreeSet<PublicKey>,
+    pub absent: BTreeSet<PublicKey>,
+}
+
+/// What we predict of the round if the assumed votes came in, see `simulate_peer_votes`
+/// - participants are the voters we'd have heard from
+/// - quorum is whether they weigh enough to terminate the round
+/// - split is whether no proposal could gather a super majority on its own, forcing a merge
+/// - likely winner is the proposal the round would resolve to, if it terminates
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Simulation<T> {
+    pub gen: Generation,
+    pub participants: BTreeSet<PublicKey>,
+    pub quorum: bool,
+    pub split: bool,
+    pub likely_winner: Option<T>,
+}
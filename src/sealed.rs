@@ -0,0 +1,159 @@
+//! Voting on proposals without showing them until they are decided.
+//!
+//! Elders vote on a `SealedProposal`, a hash commitment to their proposal, so an adversary
+//! can't tailor a competing proposal to what honest elders proposed first. Once the round
+//! decided, the proposer reveals the `Opening` and anyone checks it against the decision.
+//! Contents are only validated once revealed. `Reveal` waits for the opening until a deadline,
+//! when it doesn't come in time or doesn't validate, the round is void and its proposers are
+//! to be excluded from the next elders.
+
+use std::collections::BTreeSet;
+use std::time::{Duration, Instant};
+
+use rand::{CryptoRng, Rng};
+use serde::{Deserialize, Serialize};
+
+use crate::{proposal_hash, Decision, Hash, Proposal, ProtocolError, PublicKey, Result};
+
+// Commitments can't be passed off as any other hash of ours
+const COMMITMENT_PREFIX: &[u8] = b"sn_handover/sealed/";
+
+/// A proposal as it's voted on, its contents hidden behind a commitment
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct SealedProposal {
+    pub commitment: Hash,
+}
+
+impl Proposal for SealedProposal {
+    fn validate(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl SealedProposal {
+    /// Seals `proposal` with a fresh blinding nonce, keep the opening until the round decided
+    pub fn seal<T: Serialize>(
+        proposal: T,
+        rng: &mut (impl Rng + CryptoRng),
+    ) -> Result<(Self, Opening<T>)> {
+        let opening = Opening {
+            proposal,
+            nonce: rng.gen(),
+        };
+        Ok((opening.sealed()?, opening))
+    }
+
+    /// The proposal behind the commitment, once it checks out against `opening`
+    pub fn open<T: Serialize + Proposal>(&self, opening: Opening<T>) -> Result<T> {
+        if opening.sealed()? != *self {
+            return Err(ProtocolError::InvalidOpening(self.commitment).into());
+        }
+        opening.proposal.validate()?;
+        Ok(opening.proposal)
+    }
+}
+
+/// What reveals a sealed proposal: the proposal and the nonce that blinded it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Opening<T> {
+    pub proposal: T,
+    pub nonce: [u8; 32],
+}
+
+impl<T: Serialize> Opening<T> {
+    pub fn sealed(&self) -> Result<SealedProposal> {
+        Ok(SealedProposal {
+            commitment: Hash::of_parts([
+                COMMITMENT_PREFIX,
+                &self.nonce,
+                &bincode::serialize(&self.proposal)?,
+            ]),
+        })
+    }
+}
+
+impl Decision<SealedProposal> {
+    /// The decided proposal, revealed by `opening`
+    pub fn reveal<T: Serialize + Proposal>(&self, opening: Opening<T>) -> Result<T> {
+        self.proposal.open(opening)
+    }
+}
+
+/// Where the reveal of a decided sealed proposal stands
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RevealOutcome<'a, T> {
+    /// Still waiting for the opening, the deadline hasn't passed
+    Pending,
+    /// The opening came in time and the proposal validates
+    Revealed(&'a T),
+    /// The round is void, these proposers didn't reveal in time or revealed an invalid proposal
+    Excluded(&'a BTreeSet<PublicKey>),
+}
+
+/// Waits for the opening of a decided sealed proposal, for at most `deadline` after the decision
+#[derive(Debug)]
+pub struct Reveal<T> {
+    decision: Decision<SealedProposal>,
+    proposers: BTreeSet<PublicKey>,
+    deadline: Duration,
+    decided_at: Instant,
+    revealed: Option<T>,
+    invalid: bool,
+}
+
+impl<T: Serialize + Proposal> Reveal<T> {
+    /// Starts the wait for the opening of `decision`, from now
+    pub fn new(decision: Decision<SealedProposal>, deadline: Duration) -> Result<Self> {
+        let decided = proposal_hash(&decision.proposal)?;
+        let mut proposers = BTreeSet::new();
+        for vote in decision.votes.iter() {
+            for (proposer, hash) in vote.proposal_hashes()? {
+                if hash == decided {
+                    proposers.insert(proposer);
+                }
+            }
+        }
+        Ok(Self {
+            decision,
+            proposers,
+            deadline,
+            decided_at: Instant::now(),
+            revealed: None,
+            invalid: false,
+        })
+    }
+
+    /// The elders that proposed the decided commitment, they are the ones expected to open it
+    pub fn proposers(&self) -> &BTreeSet<PublicKey> {
+        &self.proposers
+    }
+
+    /// Takes in an opening from anyone. Openings of other commitments are refused and change
+    /// nothing, one that opens the decided commitment to an invalid proposal voids the round.
+    pub fn handle_opening(&mut self, opening: Opening<T>) -> Result<()> {
+        if self.revealed.is_some() || self.invalid || self.is_expired() {
+            return Ok(());
+        }
+        if opening.sealed()? != self.decision.proposal {
+            return Err(ProtocolError::InvalidOpening(self.decision.proposal.commitment).into());
+        }
+        if let Err(err) = opening.proposal.validate() {
+            self.invalid = true;
+            return Err(err);
+        }
+        self.revealed = Some(opening.proposal);
+        Ok(())
+    }
+
+    pub fn outcome(&self) -> RevealOutcome<'_, T> {
+        match &self.revealed {
+            Some(proposal) => RevealOutcome::Revealed(proposal),
+            None if self.invalid || self.is_expired() => RevealOutcome::Excluded(&self.proposers),
+            None => RevealOutcome::Pending,
+        }
+    }
+
+    fn is_expired(&self) -> bool {
+        self.decided_at.elapsed() >= self.deadline
+    }
+}
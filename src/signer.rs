@@ -0,0 +1,94 @@
+use core::fmt::Debug;
+use std::sync::Arc;
+
+use crate::{PublicKey, Result, SecretKey, Signature};
+
+/// Signs votes on behalf of an elder, the key may live outside of this process (HSM, key store)
+pub trait Signer: Debug + Send + Sync {
+    type PublicKey;
+    type Signature;
+
+    fn public_key(&self) -> Self::PublicKey;
+
+    fn sign(&self, msg: &[u8]) -> Result<Self::Signature>;
+}
+
+/// Checks the signatures voters put on their votes
+pub trait Verifier: Debug + Send + Sync {
+    type PublicKey;
+    type Signature;
+
+    fn verify(&self, voter: &Self::PublicKey, msg: &[u8], sig: &Self::Signature) -> Result<()>;
+}
+
+/// A signer over the keys and signatures of the enabled crypto feature, those votes carry
+pub trait VoteSigner: Signer<PublicKey = PublicKey, Signature = Signature> {}
+
+impl<S: Signer<PublicKey = PublicKey, Signature = Signature> + ?Sized> VoteSigner for S {}
+
+/// A verifier over the keys and signatures of the enabled crypto feature, those votes carry
+pub trait VoteVerifier: Verifier<PublicKey = PublicKey, Signature = Signature> {}
+
+impl<V: Verifier<PublicKey = PublicKey, Signature = Signature> + ?Sized> VoteVerifier for V {}
+
+impl Signer for SecretKey {
+    type PublicKey = PublicKey;
+    type Signature = Signature;
+
+    fn public_key(&self) -> PublicKey {
+        SecretKey::public_key(self)
+    }
+
+    fn sign(&self, msg: &[u8]) -> Result<Signature> {
+        Ok(SecretKey::sign(self, msg))
+    }
+}
+
+impl<S: Signer + ?Sized> Signer for Box<S> {
+    type PublicKey = S::PublicKey;
+    type Signature = S::Signature;
+
+    fn public_key(&self) -> S::PublicKey {
+        (**self).public_key()
+    }
+
+    fn sign(&self, msg: &[u8]) -> Result<S::Signature> {
+        (**self).sign(msg)
+    }
+}
+
+// The instances spawned by a split sign and verify with their parent's
+impl<S: Signer + ?Sized> Signer for Arc<S> {
+    type PublicKey = S::PublicKey;
+    type Signature = S::Signature;
+
+    fn public_key(&self) -> S::PublicKey {
+        (**self).public_key()
+    }
+
+    fn sign(&self, msg: &[u8]) -> Result<S::Signature> {
+        (**self).sign(msg)
+    }
+}
+
+impl<V: Verifier + ?Sized> Verifier for Arc<V> {
+    type PublicKey = V::PublicKey;
+    type Signature = V::Signature;
+
+    fn verify(&self, voter: &V::PublicKey, msg: &[u8], sig: &V::Signature) -> Result<()> {
+        (**self).verify(voter, msg, sig)
+    }
+}
+
+/// Verifies signatures with the voter's public key, as the enabled crypto feature does
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct KeyVerifier;
+
+impl Verifier for KeyVerifier {
+    type PublicKey = PublicKey;
+    type Signature = Signature;
+
+    fn verify(&self, voter: &PublicKey, msg: &[u8], sig: &Signature) -> Result<()> {
+        Ok(voter.verify(msg, sig)?)
+    }
+}
@@ -0,0 +1,776 @@
+//! A simulated network of elders, to test Proposal implementations against lossy networks
+//! and Byzantine voters. Enabled by the `testing` feature.
+//!
+//! Packets sit in per source queues until delivered. Tests either pick the deliveries by
+//! hand or drain the queues through a faulty link, then check the consensus invariants
+//! held across the honest elders.
+//!
+//! A harness misused by a test is that test's bug, it panics instead of returning errors.
+#![allow(clippy::expect_used, clippy::indexing_slicing)]
+
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fs::File;
+use std::io::Write;
+use std::iter;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use log::info;
+use rand::prelude::{IteratorRandom, StdRng};
+use rand::Rng;
+use serde::{de::DeserializeOwned, Serialize};
+use thiserror::Error;
+
+use crate::{
+    Ballot, ConfigError, Error, Generation, HandoverState, Proposal, ProtocolError, PublicKey,
+    Result, SecretKey, SignedVote, Vote, VoteMsg,
+};
+use core::fmt::Debug;
+
+/// Anti-entropy rounds a faulty drain runs to recover lost packets before giving up
+pub const MAX_RESYNC_ROUNDS: usize = 32;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Packet<T: Ord + Serialize> {
+    pub source: PublicKey,
+    pub vote_msg: VoteMsg<T>,
+}
+
+/// How a link mistreats the packets going through it, rates are probabilities per packet
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct LinkFaults {
+    pub drop: f64,
+    pub duplicate: f64,
+    /// Delivers a random packet of the source's queue instead of the oldest one
+    pub reorder: f64,
+    /// Holds a packet back for up to `max_delay` deliveries
+    pub delay: f64,
+    pub max_delay: usize,
+}
+
+/// A consensus invariant the honest elders broke
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    #[error("Honest elders disagree on generation {gen}: {ours} != {theirs}")]
+    Disagreement {
+        gen: Generation,
+        ours: String,
+        theirs: String,
+    },
+    #[error("Generation {gen} decided {proposal}, which no voter proposed")]
+    NotProposed { gen: Generation, proposal: String },
+    #[error("{elder} decided generation {gen} without a valid proof: {reason}")]
+    UnprovenDecision {
+        gen: Generation,
+        elder: PublicKey,
+        reason: String,
+    },
+}
+
+#[derive(Debug)]
+pub struct Net<T: Ord + Serialize> {
+    pub procs: Vec<HandoverState<T>>,
+    pub proposals: BTreeSet<T>,
+    pub packets: BTreeMap<PublicKey, VecDeque<Packet<T>>>,
+    pub delivered_packets: Vec<Packet<T>>,
+    /// The elders we expect to misbehave, invariants are only checked across the others
+    pub faulty: BTreeSet<PublicKey>,
+}
+
+impl<T: Ord + Serialize> Default for Net<T> {
+    fn default() -> Self {
+        Self {
+            procs: Default::default(),
+            proposals: Default::default(),
+            packets: Default::default(),
+            delivered_packets: Default::default(),
+            faulty: Default::default(),
+        }
+    }
+}
+
+impl<T> Net<T>
+where
+    T: Clone + Copy + Debug + Ord + Serialize + DeserializeOwned + Proposal + Send,
+{
+    pub fn with_procs(n: usize, mut rng: &mut StdRng) -> Self {
+        let elders_private_k = Vec::from_iter((0..n).map(|_| SecretKey::random(&mut rng)));
+        let gen = 0;
+
+        let mut procs = Vec::from_iter(
+            elders_private_k
+                .into_iter()
+                .map(|sk| HandoverState::from(sk, gen, Default::default())),
+        );
+        procs.sort_by_key(|p| p.public_key());
+        Self {
+            procs,
+            ..Default::default()
+        }
+    }
+
+    pub fn proc(&self, public_key: PublicKey) -> Option<&HandoverState<T>> {
+        self.procs.iter().find(|p| p.public_key() == public_key)
+    }
+
+    /// Every proc votes with the same elders
+    pub fn with_all_voters(mut self) -> Self {
+        let voters = BTreeSet::from_iter(self.procs.iter().map(HandoverState::public_key));
+        for proc in self.procs.iter_mut() {
+            proc.voters = voters.clone();
+        }
+        self
+    }
+
+    /// Proc `i` proposes `proposal`, its votes are queued
+    pub fn propose(&mut self, i: usize, proposal: T) -> Result<()> {
+        let proc = &mut self.procs[i];
+        let source = proc.public_key();
+        let msgs = proc.propose(proposal)?;
+        self.proposals.insert(proposal);
+        self.enqueue_packets(msgs.into_iter().map(|vote_msg| Packet { source, vote_msg }));
+        Ok(())
+    }
+
+    /// Pick a random public key from the set of procs
+    pub fn gen_public_key(&self, rng: &mut StdRng) -> PublicKey {
+        self.procs
+            .iter()
+            .choose(rng)
+            .map(HandoverState::public_key)
+            .expect("a net has procs")
+    }
+
+    /// Generate a randomized ballot, proposing one of the net's proposals
+    pub fn gen_ballot(
+        &self,
+        recursion: u8,
+        faulty: &BTreeSet<PublicKey>,
+        rng: &mut StdRng,
+    ) -> Ballot<T> {
+        match rng.gen() || recursion == 0 {
+            true => Ballot::Propose(
+                *self
+                    .proposals
+                    .iter()
+                    .choose(rng)
+                    .expect("a net has proposals"),
+            ),
+            false => {
+                let n_votes = rng.gen::<usize>() % self.procs.len().pow(2);
+                let random_votes = BTreeSet::from_iter(
+                    iter::repeat_with(|| self.gen_faulty_vote(recursion - 1, faulty, rng))
+                        .take(n_votes),
+                );
+                match rng.gen() {
+                    true => Ballot::Merge(random_votes),
+                    false => Ballot::SuperMajority(random_votes),
+                }
+            }
+        }
+    }
+
+    /// Generate a random faulty vote
+    pub fn gen_faulty_vote(
+        &self,
+        recursion: u8,
+        faulty_nodes: &BTreeSet<PublicKey>,
+        rng: &mut StdRng,
+    ) -> SignedVote<T> {
+        let faulty_node = faulty_nodes
+            .iter()
+            .choose(rng)
+            .and_then(|pk| self.proc(*pk))
+            .expect("faulty nodes are procs of the net");
+
+        let vote = Vote {
+            gen: rng.gen::<u64>() % 7,
+            ballot: self.gen_ballot(recursion, faulty_nodes, rng),
+            extensions: Default::default(),
+        };
+
+        let mut signed_vote = faulty_node.sign_vote(vote).expect("faulty nodes can sign");
+        signed_vote.voter = self.gen_public_key(rng);
+        signed_vote
+    }
+
+    /// Generate a faulty random packet
+    pub fn gen_faulty_packet(
+        &self,
+        recursion: u8,
+        faulty: &BTreeSet<PublicKey>,
+        rng: &mut StdRng,
+    ) -> Packet<T> {
+        Packet {
+            source: *faulty.iter().choose(rng).expect("some nodes are faulty"),
+            vote_msg: VoteMsg {
+                vote: self.gen_faulty_vote(recursion, faulty, rng),
+                dest: self.gen_public_key(rng),
+                correlation_id: None,
+                priority: Default::default(),
+            },
+        }
+    }
+
+    pub fn genesis(&self) -> Result<PublicKey> {
+        self.procs
+            .first()
+            .map(HandoverState::public_key)
+            .ok_or(Error::Config(ConfigError::NoMembers))
+    }
+
+    pub fn drop_packet_from_source(&mut self, source: PublicKey) {
+        self.packets.get_mut(&source).map(VecDeque::pop_front);
+    }
+
+    pub fn deliver_packet_from_source(&mut self, source: PublicKey) -> Result<()> {
+        let packet = match self.packets.get_mut(&source).map(|ps| ps.pop_front()) {
+            Some(Some(p)) => p,
+            _ => return Ok(()), // nothing to do
+        };
+        self.purge_empty_queues();
+
+        info!(
+            "delivering {:?}->{:?} {:?}",
+            packet.source, packet.vote_msg.dest, packet
+        );
+
+        self.delivered_packets.push(packet.clone());
+
+        let dest_proc_opt = self
+            .procs
+            .iter_mut()
+            .find(|p| p.public_key() == packet.vote_msg.dest);
+
+        let dest_proc = match dest_proc_opt {
+            Some(proc) => proc,
+            None => {
+                info!("[NET] destination proc does not exist, dropping packet");
+                return Ok(());
+            }
+        };
+
+        let resp = deliver(dest_proc, packet)?;
+        self.enqueue_packets(resp);
+        Ok(())
+    }
+
+    pub fn enqueue_packets(&mut self, packets: impl IntoIterator<Item = Packet<T>>) {
+        for packet in packets {
+            self.packets
+                .entry(packet.source)
+                .or_default()
+                .push_back(packet)
+        }
+    }
+
+    pub fn drain_queued_packets(&mut self) -> Result<()> {
+        while let Some(source) = self.packets.keys().next().cloned() {
+            self.deliver_packet_from_source(source)?;
+            self.purge_empty_queues();
+        }
+        Ok(())
+    }
+
+    /// Like `drain_queued_packets` but each proc runs on its own thread, packets go through channels
+    pub fn drain_queued_packets_threaded(&mut self) -> Result<()> {
+        let in_flight = AtomicUsize::new(0);
+        let failed = AtomicBool::new(false);
+        let first_err = Mutex::new(None);
+        let delivered = Mutex::new(Vec::new());
+
+        let mut senders = BTreeMap::new();
+        let mut receivers = Vec::new();
+        for proc in self.procs.iter() {
+            let (tx, rx) = mpsc::channel::<Packet<T>>();
+            senders.insert(proc.public_key(), tx);
+            receivers.push(rx);
+        }
+
+        // a packet is in flight from the time it's sent until its responses are sent
+        let send = |packet: Packet<T>| match senders.get(&packet.vote_msg.dest) {
+            Some(tx) => {
+                in_flight.fetch_add(1, Ordering::SeqCst);
+                if tx.send(packet).is_err() {
+                    in_flight.fetch_sub(1, Ordering::SeqCst);
+                }
+            }
+            None => info!("[NET] destination proc does not exist, dropping packet"),
+        };
+
+        for packet in core::mem::take(&mut self.packets).into_values().flatten() {
+            send(packet);
+        }
+
+        thread::scope(|scope| {
+            for (proc, rx) in self.procs.iter_mut().zip(receivers) {
+                let (send, in_flight, failed) = (&send, &in_flight, &failed);
+                let (first_err, delivered) = (&first_err, &delivered);
+                scope.spawn(move || loop {
+                    match rx.recv_timeout(Duration::from_millis(1)) {
+                        Ok(packet) => {
+                            lock(delivered).push(packet.clone());
+                            match deliver(proc, packet) {
+                                Ok(resp) => resp.into_iter().for_each(send),
+                                Err(err) => {
+                                    failed.store(true, Ordering::SeqCst);
+                                    lock(first_err).get_or_insert(err);
+                                }
+                            }
+                            in_flight.fetch_sub(1, Ordering::SeqCst);
+                        }
+                        Err(RecvTimeoutError::Timeout) => {
+                            if in_flight.load(Ordering::SeqCst) == 0
+                                || failed.load(Ordering::SeqCst)
+                            {
+                                break;
+                            }
+                        }
+                        Err(RecvTimeoutError::Disconnected) => break,
+                    }
+                });
+            }
+        });
+
+        self.delivered_packets.extend(into_inner(delivered));
+        match into_inner(first_err) {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    /// Deliver the queued packets through a link with `faults`, until the honest procs decided.
+    /// Votes the link made stale or out of order are refused by their destination, like they
+    /// would be on a real network. Whenever the queues run dry before the honest procs decided,
+    /// they resynchronize through anti-entropy, at most `MAX_RESYNC_ROUNDS` times.
+    pub fn drain_with_faults(&mut self, faults: LinkFaults, rng: &mut StdRng) -> Result<()> {
+        let mut delayed: Vec<(usize, Packet<T>)> = Vec::new();
+        let mut step = 0;
+        let mut resync_rounds = 0;
+        loop {
+            let (ready, held) = delayed.into_iter().partition(|(at, _)| *at <= step);
+            delayed = held;
+            self.enqueue_packets(ready.into_iter().map(|(_, packet)| packet));
+
+            let source = match self.packets.keys().choose(rng).copied() {
+                Some(source) => source,
+                None if !delayed.is_empty() => {
+                    step = delayed.iter().map(|(at, _)| *at).min().unwrap_or(step);
+                    continue;
+                }
+                None if self.honest_procs_decided() || resync_rounds == MAX_RESYNC_ROUNDS => {
+                    return Ok(())
+                }
+                None => {
+                    resync_rounds += 1;
+                    self.enqueue_honest_anti_entropy();
+                    continue;
+                }
+            };
+            step += 1;
+
+            let packet = match self.packets.get_mut(&source) {
+                Some(queue) => {
+                    let index = match rng.gen_bool(faults.reorder) {
+                        true => rng.gen_range(0, queue.len()),
+                        false => 0,
+                    };
+                    queue.remove(index)
+                }
+                None => None,
+            };
+            self.purge_empty_queues();
+            let packet = match packet {
+                Some(packet) => packet,
+                None => continue,
+            };
+
+            // what an elder sends itself never goes through the link
+            let on_link = packet.source != packet.vote_msg.dest;
+            if on_link && rng.gen_bool(faults.drop) {
+                info!("[NET] dropping {:?}", packet);
+                continue;
+            }
+            if on_link && faults.max_delay > 0 && rng.gen_bool(faults.delay) {
+                delayed.push((step + rng.gen_range(1, faults.max_delay + 1), packet));
+                continue;
+            }
+            if on_link && rng.gen_bool(faults.duplicate) {
+                self.enqueue_packets([packet.clone()]);
+            }
+            self.deliver_through_link(packet)?;
+        }
+    }
+
+    // Protocol errors are what the link gets us, the packet is lost
+    fn deliver_through_link(&mut self, packet: Packet<T>) -> Result<()> {
+        let dest = match self
+            .procs
+            .iter_mut()
+            .find(|p| p.public_key() == packet.vote_msg.dest)
+        {
+            Some(dest) => dest,
+            None => return Ok(()),
+        };
+        self.delivered_packets.push(packet.clone());
+
+        let source = dest.public_key();
+        match dest.handle_vote_msg(packet.vote_msg) {
+            Ok(outcome) => {
+                let packets = outcome.msgs.into_iter();
+                self.enqueue_packets(packets.map(|vote_msg| Packet { source, vote_msg }));
+                Ok(())
+            }
+            Err(Error::Protocol(err)) => {
+                info!("[NET] {:?} refused a packet: {}", source, err);
+                Ok(())
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    fn honest_procs(&self) -> impl Iterator<Item = &HandoverState<T>> {
+        self.procs
+            .iter()
+            .filter(|p| !self.faulty.contains(&p.public_key()))
+    }
+
+    pub fn honest_procs_decided(&self) -> bool {
+        self.honest_procs().all(|p| p.consensus.is_some())
+    }
+
+    fn enqueue_honest_anti_entropy(&mut self) {
+        for i in 0..self.procs.len() {
+            for j in 0..self.procs.len() {
+                let honest = !self.faulty.contains(&self.procs[j].public_key());
+                if i != j && honest {
+                    self.enqueue_anti_entropy(i, j);
+                }
+            }
+        }
+    }
+
+    /// Checks the honest procs upheld consensus:
+    /// - agreement, they decided the same proposal in each generation
+    /// - validity, what they decided was proposed by a voter
+    /// - no split decision, each of their decisions is backed by a valid proof
+    pub fn check_invariants(&self) -> std::result::Result<(), Violation> {
+        let proposed = BTreeSet::from_iter(
+            self.delivered_packets
+                .iter()
+                .flat_map(|p| p.vote_msg.vote.proposals())
+                .map(|(_, proposal)| proposal),
+        );
+
+        let mut decided: BTreeMap<Generation, T> = BTreeMap::new();
+        for proc in self.honest_procs() {
+            let mut decisions = Vec::from_iter(proc.history.rounds.values().map(|r| {
+                let decision = r.decision.clone();
+                (decision, r.voters.clone())
+            }));
+            let current = proc.decision().map_err(|err| Violation::UnprovenDecision {
+                gen: proc.gen,
+                elder: proc.public_key(),
+                reason: err.report(),
+            })?;
+            decisions.extend(current.map(|d| (d, proc.voters.clone())));
+            let decided_now = proc.consensus.map(|c| (proc.gen, c));
+
+            for (decision, voters) in decisions.iter() {
+                decision
+                    .verify_for(&*proc.verifier, &proc.config, voters)
+                    .map_err(|err| Violation::UnprovenDecision {
+                        gen: decision.gen,
+                        elder: proc.public_key(),
+                        reason: err.report(),
+                    })?;
+            }
+
+            let proposals = decisions.iter().map(|(d, _)| (d.gen, d.proposal));
+            for (gen, proposal) in proposals.chain(decided_now) {
+                if !proposed.contains(&proposal) {
+                    return Err(Violation::NotProposed {
+                        gen,
+                        proposal: format!("{:?}", proposal),
+                    });
+                }
+                match decided.get(&gen) {
+                    Some(ours) if ours != &proposal => {
+                        return Err(Violation::Disagreement {
+                            gen,
+                            ours: format!("{:?}", ours),
+                            theirs: format!("{:?}", proposal),
+                        })
+                    }
+                    Some(_) => (),
+                    None => {
+                        decided.insert(gen, proposal);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn purge_empty_queues(&mut self) {
+        self.packets = core::mem::take(&mut self.packets)
+            .into_iter()
+            .filter(|(_, queue)| !queue.is_empty())
+            .collect();
+    }
+
+    pub fn force_join(&mut self, p: PublicKey, q: PublicKey) {
+        if let Some(proc) = self.procs.iter_mut().find(|proc| proc.public_key() == p) {
+            proc.force_join(q);
+        }
+    }
+
+    pub fn enqueue_anti_entropy(&mut self, i: usize, j: usize) {
+        let i_actor = self.procs[i].public_key();
+        let j_actor = self.procs[j].public_key();
+
+        self.enqueue_packets(self.procs[j].full_anti_entropy(i_actor).into_iter().map(
+            |vote_msg| Packet {
+                source: j_actor,
+                vote_msg,
+            },
+        ));
+    }
+
+    /// Writes the delivered packets as an mscgen sequence chart, if asked to
+    pub fn generate_msc(&self, path: impl AsRef<Path>) -> Result<()> {
+        // See: http://www.mcternan.me.uk/mscgen/
+        let mut msc = String::from(
+            "
+msc {\n
+  hscale = \"2\";\n
+",
+        );
+        let procs = self
+            .procs
+            .iter()
+            .map(|p| p.public_key())
+            .collect::<BTreeSet<_>>() // sort by actor id
+            .into_iter()
+            .map(|id| format!("{:?}", id))
+            .collect::<Vec<_>>()
+            .join(",");
+        msc.push_str(&procs);
+        msc.push_str(";\n");
+        for packet in self.delivered_packets.iter() {
+            msc.push_str(&format!(
+                "{} -> {} [ label=\"{:?}\"];\n",
+                packet.source, packet.vote_msg.dest, packet.vote_msg.vote
+            ));
+        }
+
+        msc.push_str("}\n");
+
+        // Replace process identifiers with friendlier numbers
+        // 1, 2, 3 ... instead of i:3b2, i:7def, ...
+        for (idx, proc_id) in self.procs.iter().map(HandoverState::public_key).enumerate() {
+            let proc_id_as_str = format!("{}", proc_id);
+            msc = msc.replace(&proc_id_as_str, &format!("{}", idx + 1));
+        }
+
+        let mut msc_file = File::create(path)?;
+        msc_file.write_all(msc.as_bytes())?;
+        Ok(())
+    }
+
+    /// Writes the delivered packets as a self contained html page, if asked to
+    pub fn generate_html(&self, path: impl AsRef<Path>) -> Result<()> {
+        // The run drawn as an inline svg sequence diagram, the page needs nothing but a browser.
+        // Packets are drawn in delivery order, hovering one shows its whole vote.
+        const LANE: usize = 160;
+        const ROW: usize = 24;
+        const TOP: usize = 50;
+        const LABEL: usize = 36;
+
+        let proc_ids = Vec::from_iter(BTreeSet::from_iter(
+            self.procs.iter().map(HandoverState::public_key),
+        ));
+        let lane = |id: &PublicKey| proc_ids.iter().position(|p| p == id);
+        let x = |idx: usize| LANE / 2 + idx * LANE;
+        let friendly = |text: String| {
+            proc_ids.iter().enumerate().fold(text, |text, (idx, id)| {
+                text.replace(&format!("{}", id), &format!("{}", idx + 1))
+            })
+        };
+
+        let mut rows = String::new();
+        let mut y = TOP;
+        let mut round = 0;
+        for packet in self.delivered_packets.iter() {
+            let (source, dest) = match (lane(&packet.source), lane(&packet.vote_msg.dest)) {
+                (Some(source), Some(dest)) => (source, dest),
+                _ => continue,
+            };
+            y += ROW;
+            let packet_round = packet.vote_msg.vote.round();
+            if packet_round != round {
+                round = packet_round;
+                rows.push_str(&format!(
+                    "<line class=\"round\" x1=\"0\" y1=\"{y}\" x2=\"{w}\" y2=\"{y}\"/>\
+<text class=\"round\" x=\"4\" y=\"{ty}\">round {round}</text>\n",
+                    w = proc_ids.len() * LANE,
+                    ty = y - 4,
+                ));
+                y += ROW;
+            }
+
+            let vote = friendly(format!("{:?}", packet.vote_msg.vote));
+            let mut label: String = vote.chars().take(LABEL).collect();
+            if label.len() < vote.len() {
+                label.push('…');
+            }
+            let (x1, x2) = (x(source), x(dest));
+            let arrow = match source == dest {
+                true => format!(
+                    "<path d=\"M{x1} {y1} h24 v{r} h-24\"/>",
+                    y1 = y - ROW / 2,
+                    r = ROW / 2
+                ),
+                false => format!("<line x1=\"{x1}\" y1=\"{y}\" x2=\"{x2}\" y2=\"{y}\"/>"),
+            };
+            rows.push_str(&format!(
+                "<g class=\"packet\"><title>{title}</title>{arrow}\
+<text x=\"{tx}\" y=\"{ty}\">{label}</text></g>\n",
+                title = html_escape(&vote),
+                tx = (x1 + x2) / 2,
+                ty = y - 4,
+                label = html_escape(&label),
+            ));
+        }
+
+        y += 2 * ROW;
+        let mut lanes = String::new();
+        for (idx, id) in proc_ids.iter().enumerate() {
+            let decided = self
+                .procs
+                .iter()
+                .find(|proc| proc.public_key() == *id)
+                .and_then(|proc| proc.consensus.as_ref().map(|c| format!("{:?}", c)))
+                .unwrap_or_else(|| "undecided".to_string());
+            lanes.push_str(&format!(
+                "<text class=\"proc\" x=\"{x}\" y=\"20\">{n}</text>\
+<text class=\"key\" x=\"{x}\" y=\"34\">{id}</text>\
+<line class=\"lane\" x1=\"{x}\" y1=\"{TOP}\" x2=\"{x}\" y2=\"{y}\"/>\
+<text class=\"decided\" x=\"{x}\" y=\"{ty}\">{decided}</text>\n",
+                x = x(idx),
+                n = idx + 1,
+                id = html_escape(&format!("{}", id)),
+                ty = y + ROW,
+                decided = html_escape(&decided),
+            ));
+        }
+
+        let path = path.as_ref();
+        let title = html_escape(&path.file_name().unwrap_or_default().to_string_lossy());
+        let html = format!(
+            "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>{title}</title>
+<style>
+  svg {{ font: 11px monospace; }}
+  text {{ text-anchor: middle; }}
+  .proc {{ font-weight: bold; font-size: 14px; }}
+  .key {{ fill: #777; }}
+  .lane {{ stroke: #999; stroke-dasharray: 4 4; }}
+  .round {{ stroke: #ccc; fill: #777; text-anchor: start; }}
+  .packet line, .packet path {{ stroke: #236; fill: none; marker-end: url(#arrow); }}
+  .packet:hover {{ fill: #c30; }}
+  .decided {{ font-weight: bold; }}
+</style>
+</head>
+<body>
+<h1>{title}</h1>
+<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\">
+<defs><marker id=\"arrow\" viewBox=\"0 0 8 8\" refX=\"8\" refY=\"4\" markerWidth=\"8\" markerHeight=\"8\" orient=\"auto\">\
+<path d=\"M0 0 L8 4 L0 8 z\" fill=\"#236\"/></marker></defs>
+{lanes}{rows}</svg>
+</body>
+</html>
+",
+            width = proc_ids.len() * LANE,
+            height = y + 2 * ROW,
+        );
+
+        let mut html_file = File::create(path)?;
+        html_file.write_all(html.as_bytes())?;
+        Ok(())
+    }
+}
+
+fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// A proc thread that panicked already failed the run, its data is still good to report
+fn lock<V>(mutex: &Mutex<V>) -> std::sync::MutexGuard<'_, V> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn into_inner<V>(mutex: Mutex<V>) -> V {
+    mutex
+        .into_inner()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Hand a packet to its destination proc, checking that any error is one we expect from the network
+fn deliver<T>(dest_proc: &mut HandoverState<T>, packet: Packet<T>) -> Result<Vec<Packet<T>>>
+where
+    T: Clone + Copy + Debug + Ord + Serialize + DeserializeOwned + Proposal,
+{
+    let source = packet.source;
+    let dest_members = dest_proc.voters.clone();
+    let vote = packet.vote_msg.vote;
+
+    let resp = dest_proc.handle_signed_vote(vote);
+    info!("[NET] resp: {:?}", resp);
+    match resp {
+        Ok(outcome) => {
+            let dest_actor = dest_proc.public_key();
+            return Ok(Vec::from_iter(outcome.msgs.into_iter().map(|vote_msg| {
+                Packet {
+                    source: dest_actor,
+                    vote_msg,
+                }
+            })));
+        }
+        Err(Error::Protocol(ProtocolError::NonMember {
+            public_key: voter,
+            members,
+        })) => {
+            assert_eq!(members, dest_members);
+            assert!(
+                !dest_members.contains(&voter),
+                "{:?} should not be in {:?}",
+                source,
+                dest_members
+            );
+        }
+        Err(Error::Protocol(ProtocolError::VoteNotForNextGeneration {
+            vote_gen,
+            gen,
+            pending_gen,
+        })) => {
+            assert!(vote_gen <= gen || vote_gen > pending_gen);
+            assert_eq!(dest_proc.gen, gen);
+        }
+        Err(err) => return Err(err),
+    }
+
+    Ok(vec![])
+}
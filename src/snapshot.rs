@@ -0,0 +1,153 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Config, Fault, Generation, Hash, History, PublicKey, Result, Round, RoundStats, SignedVote,
+    StorageError,
+};
+
+/// Everything a HandoverState needs to pick up where it left off after a restart,
+/// except its secret key, which is handed back on restore and never stored here.
+/// The generation policy and proposal source are code, they are set again after restoring.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot<T>
+where
+    T: Ord + Serialize,
+{
+    pub gen: Generation,
+    pub votes: BTreeMap<PublicKey, SignedVote<T>>,
+    /// Hash of the last vote saved before the snapshot, deltas are taken against it
+    pub watermark: Option<Hash>,
+    pub voters: BTreeSet<PublicKey>,
+    pub consensus: Option<T>,
+    pub faults: BTreeSet<Fault<T>>,
+    pub config: Config,
+    /// The rounds we terminated and how they went, to keep serving catch ups and stats
+    pub history: History<T>,
+    pub stats: VecDeque<RoundStats>,
+}
+
+/// The changes since a snapshot, it only applies to the snapshot it was taken against.
+/// It carries the votes that changed since its watermark, or all of them when the snapshot
+/// is from another generation or its watermark is unknown, and what was added since it
+/// otherwise, leaving out the voters and config when they didn't change.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotDelta<T>
+where
+    T: Ord + Serialize,
+{
+    pub gen: Generation,
+    /// The generation and watermark of the snapshot the delta was taken against
+    pub base_gen: Generation,
+    pub base: Option<Hash>,
+    pub watermark: Option<Hash>,
+    /// The votes replace those of the snapshot rather than update them
+    pub all_votes: bool,
+    pub votes: BTreeMap<PublicKey, SignedVote<T>>,
+    pub voters: Option<BTreeSet<PublicKey>>,
+    pub consensus: Option<T>,
+    pub faults: BTreeSet<Fault<T>>,
+    pub config: Option<Config>,
+    /// The rounds we terminated since, with their stats
+    pub rounds: BTreeMap<Generation, Round<T>>,
+    pub stats: Vec<RoundStats>,
+}
+
+impl<T: Ord + Serialize> Snapshot<T> {
+    /// Bring this snapshot up to date, the delta must have been taken against it.
+    /// Rounds past `Config::stats_retention` are forgotten as the state forgets them.
+    pub fn apply(&mut self, delta: SnapshotDelta<T>) -> Result<()> {
+        if self.gen != delta.base_gen || self.watermark != delta.base {
+            return Err(StorageError::SnapshotMismatch {
+                base_gen: delta.base_gen,
+                base: delta.base,
+                gen: self.gen,
+                watermark: self.watermark,
+            }
+            .into());
+        }
+        if delta.all_votes {
+            self.votes = delta.votes;
+        } else {
+            self.votes.extend(delta.votes);
+        }
+        self.gen = delta.gen;
+        self.watermark = delta.watermark;
+        if let Some(voters) = delta.voters {
+            self.voters = voters;
+        }
+        self.consensus = delta.consensus;
+        self.faults.extend(delta.faults);
+        if let Some(config) = delta.config {
+            self.config = config;
+        }
+        self.history.rounds.extend(delta.rounds);
+        self.stats.extend(delta.stats);
+
+        let retention = self.config.stats_retention.unwrap_or(usize::MAX);
+        self.history.trim(retention);
+        while self.stats.len() > retention {
+            self.stats.pop_front();
+        }
+        Ok(())
+    }
+}
+
+/// Append-only log of the votes we handled, for integrators to persist as they go.
+/// Replaying it on startup rebuilds the votes we had.
+pub trait VoteLog<T: Ord + Serialize> {
+    fn append(&mut self, signed_vote: &SignedVote<T>) -> Result<()>;
+
+    /// All the logged votes, in the order they were appended
+    fn votes(&self) -> Result<Vec<SignedVote<T>>>;
+
+    /// Keeps the stats of a terminated round, logs that don't persist stats drop them
+    fn append_stats(&mut self, _stats: &RoundStats) -> Result<()> {
+        Ok(())
+    }
+
+    /// All the logged stats, in the order they were appended
+    fn stats(&self) -> Result<Vec<RoundStats>> {
+        Ok(vec![])
+    }
+}
+
+/// A VoteLog kept in memory, for tests and for integrators that persist it wholesale
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InMemoryVoteLog<T>
+where
+    T: Ord + Serialize,
+{
+    pub votes: Vec<SignedVote<T>>,
+    pub stats: Vec<RoundStats>,
+}
+
+impl<T: Ord + Serialize> Default for InMemoryVoteLog<T> {
+    fn default() -> Self {
+        Self {
+            votes: Vec::new(),
+            stats: Vec::new(),
+        }
+    }
+}
+
+impl<T: Ord + Serialize + Clone> VoteLog<T> for InMemoryVoteLog<T> {
+    fn append(&mut self, signed_vote: &SignedVote<T>) -> Result<()> {
+        self.votes.push(signed_vote.clone());
+        Ok(())
+    }
+
+    fn votes(&self) -> Result<Vec<SignedVote<T>>> {
+        Ok(self.votes.clone())
+    }
+
+    fn append_stats(&mut self, stats: &RoundStats) -> Result<()> {
+        self.stats.push(stats.clone());
+        Ok(())
+    }
+
+    fn stats(&self) -> Result<Vec<RoundStats>> {
+        Ok(self.stats.clone())
+    }
+}
@@ -0,0 +1,101 @@
+//! Splitting a section in two, each half running its own handover instance.
+//!
+//! Once the parent decided a split, `HandoverState::split` hands back the instances of its two
+//! children. A child's genesis is derived from the parent's `SectionState`, what every honest
+//! elder holds once the split is decided: the votes backing the decision differ between elders,
+//! the state they decide doesn't. Children sign their votes in the domain of their genesis,
+//! so none of them can be replayed in the parent's rounds or the sibling's.
+
+use core::fmt::{self, Debug};
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Generation, Hash, ProtocolError, PublicKey, Result, SectionState};
+
+// A genesis can't be passed off as any other hash of ours
+const GENESIS_PREFIX: &[u8] = b"sn_handover/split-genesis/";
+
+/// Where a section sits in the hierarchy of splits: the bits of its name it's responsible for
+#[derive(Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Prefix {
+    pub bits: Vec<bool>,
+}
+
+impl Prefix {
+    /// The prefix of the child responsible for the names continuing with `bit`
+    pub fn pushed(&self, bit: bool) -> Self {
+        let mut bits = self.bits.clone();
+        bits.push(bit);
+        Self { bits }
+    }
+
+    pub fn is_ancestor_of(&self, other: &Prefix) -> bool {
+        other.bits.starts_with(&self.bits)
+    }
+}
+
+impl Debug for Prefix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bits: String = self
+            .bits
+            .iter()
+            .map(|b| if *b { '1' } else { '0' })
+            .collect();
+        write!(f, "Prefix({})", bits)
+    }
+}
+
+/// A section splitting in two, the elders of the child at `prefix` + 0 then at `prefix` + 1
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Split {
+    pub prefix: Prefix,
+    pub voters: [BTreeSet<PublicKey>; 2],
+}
+
+impl Split {
+    /// The prefixes of the children, in the order of their voters
+    pub fn children(&self) -> [Prefix; 2] {
+        [self.prefix.pushed(false), self.prefix.pushed(true)]
+    }
+
+    /// Each elder goes to one child, and each child gets elders
+    pub fn validate(&self) -> Result<()> {
+        let [zero, one] = &self.voters;
+        if zero.is_empty() || one.is_empty() {
+            return Err(ProtocolError::InvalidSplit("a child has no elders".to_string()).into());
+        }
+        if let Some(elder) = zero.intersection(one).next() {
+            return Err(
+                ProtocolError::InvalidSplit(format!("{:?} is in both children", elder)).into(),
+            );
+        }
+        Ok(())
+    }
+
+    /// Which child the messages of `elder` go to, `None` if it's an elder of neither
+    pub fn route(&self, elder: &PublicKey) -> Option<Prefix> {
+        let [zero, one] = &self.voters;
+        let [zero_prefix, one_prefix] = self.children();
+        match (zero.contains(elder), one.contains(elder)) {
+            (true, _) => Some(zero_prefix),
+            (_, true) => Some(one_prefix),
+            _ => None,
+        }
+    }
+}
+
+/// Reads the split a decision encodes, if any, the application knows what its proposals mean
+pub trait SplitPolicy<T>: Debug + Send + Sync {
+    fn split(&self, gen: Generation, decision: &T) -> Option<Split>;
+}
+
+/// The genesis of the child at `prefix` spawned by the split `parent` decided,
+/// anyone holding the parent's state (e.g. from a `SectionStateProof`) derives the same
+pub fn split_genesis(parent: &SectionState, prefix: &Prefix) -> Result<Hash> {
+    Ok(Hash::of_parts([
+        GENESIS_PREFIX,
+        parent.hash()?.as_bytes(),
+        &bincode::serialize(prefix)?,
+    ]))
+}
@@ -0,0 +1,235 @@
+//! Verify the signatures of a signed vote straight from its encoding.
+//!
+//! Decoding a large SuperMajority ballot materializes every nested vote before we can
+//! check any of them. Here we read the bincode encoding instead, checking each signature
+//! once the bytes it covers were read, and only decode the vote once they all hold,
+//! so a forged vote costs us no more than reading it up to its first bad signature.
+//! We never read past the limit we're given, but we keep what we read: once in the current
+//! layout and once in the layout of the first release, up to twice the limit, then the
+//! decoded vote on top. Sorting votes nested in a ballot of the first release decodes
+//! them too, while we sort them.
+//! This only checks signatures and generations, the decoded vote still goes through
+//! `HandoverState::validate_signed_vote` before we act on it.
+
+use std::io::{Read, Take};
+use std::ops::Range;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::vote::EXTENDED_VOTE;
+use crate::{
+    v1, Generation, ProtocolError, PublicKey, Result, Signature, SignedVote, SigningDomain,
+    VoteVerifier,
+};
+
+/// Ballots nested deeper than this are refused rather than risking the stack
+pub const MAX_BALLOT_DEPTH: usize = 64;
+
+// Variant indices of `Ballot` in its bincode encoding
+const PROPOSE: u32 = 0;
+const MERGE: u32 = 1;
+const SUPER_MAJORITY: u32 = 2;
+
+/// Reads the `SignedVote<T>` encoded at the start of `reader`, reading at most `limit` bytes,
+/// and decodes it once `verifier` accepted every signature in it, as signed in `domain`.
+/// Keeps up to twice `limit` in bytes until then, see the module doc.
+pub fn read_signed_vote<T>(
+    reader: impl Read,
+    limit: u64,
+    verifier: &dyn VoteVerifier,
+    domain: SigningDomain,
+) -> Result<SignedVote<T>>
+where
+    T: Ord + Serialize + DeserializeOwned,
+{
+    let mut stream = Stream {
+        reader: reader.take(limit),
+        read: Vec::new(),
+        baseline: Vec::new(),
+        verifier,
+        domain,
+    };
+    stream.verify_next::<T>(None, 0)?;
+    bincode::deserialize(&stream.read).map_err(ProtocolError::malformed)
+}
+
+/// Checks every signature in the encoded `SignedVote<T>`, nothing may follow it
+pub fn verify_signed_vote_bytes<T>(
+    bytes: &[u8],
+    verifier: &dyn VoteVerifier,
+    domain: SigningDomain,
+) -> Result<()>
+where
+    T: Ord + Serialize + DeserializeOwned,
+{
+    let mut reader = bytes;
+    read_signed_vote::<T>(&mut reader, bytes.len() as u64, verifier, domain)?;
+    if !reader.is_empty() {
+        return Err(ProtocolError::malformed(format!(
+            "{} trailing bytes after the signed vote",
+            reader.len()
+        )));
+    }
+    Ok(())
+}
+
+struct Stream<'a, R> {
+    reader: Take<R>,
+    read: Vec<u8>,     // what we read so far, in the current layout
+    baseline: Vec<u8>, // the same votes in the layout of the first release, without extensions
+    verifier: &'a dyn VoteVerifier,
+    domain: SigningDomain,
+}
+
+// Copies what bincode reads into the bytes read so far
+struct Recorder<'r, R> {
+    reader: &'r mut Take<R>,
+    read: &'r mut Vec<u8>,
+}
+
+impl<'r, R: Read> Read for Recorder<'r, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.reader.read(buf)?;
+        self.read.extend(buf.get(..n).unwrap_or_default());
+        Ok(n)
+    }
+}
+
+impl<'a, R: Read> Stream<'a, R> {
+    // Reads a value, in both layouts unless it's only part of the current one
+    fn next<V: DeserializeOwned>(&mut self, in_baseline: bool) -> Result<V> {
+        let start = self.read.len();
+        let value = bincode::deserialize_from(Recorder {
+            reader: &mut self.reader,
+            read: &mut self.read,
+        })
+        .map_err(ProtocolError::malformed)?;
+        if in_baseline {
+            self.baseline
+                .extend(self.read.get(start..).unwrap_or_default());
+        }
+        Ok(value)
+    }
+
+    // Verifies the signed vote at the reader's position and reads past it,
+    // tells whether it carried extensions, itself or in a vote nested in it
+    fn verify_next<T: DeserializeOwned + Ord>(
+        &mut self,
+        parent_gen: Option<Generation>,
+        depth: usize,
+    ) -> Result<bool> {
+        if depth > MAX_BALLOT_DEPTH {
+            return Err(ProtocolError::malformed(format!(
+                "ballot nested deeper than {}",
+                MAX_BALLOT_DEPTH
+            )));
+        }
+
+        // the vote is the generation, the ballot, then the extensions
+        let vote_start = self.read.len();
+        let gen: Generation = self.next(true)?;
+        if let Some(merge_gen) = parent_gen.filter(|merge_gen| *merge_gen != gen) {
+            return Err(ProtocolError::MergedVotesMustBeFromSameGen {
+                child_gen: gen,
+                merge_gen,
+            }
+            .into());
+        }
+
+        let ballot_start = self.baseline.len();
+        let mut extended = false;
+        let variant: u32 = self.next(true)?;
+        match variant {
+            PROPOSE => {
+                let _proposal: T = self.next(true)?;
+            }
+            MERGE | SUPER_MAJORITY => {
+                let n_votes: u64 = self.next(true)?;
+                let mut nested = Vec::new();
+                for _ in 0..n_votes {
+                    let start = self.baseline.len();
+                    extended |= self.verify_next::<T>(Some(gen), depth + 1)?;
+                    nested.push(start..self.baseline.len());
+                }
+                if !extended {
+                    self.sort_baseline::<T>(nested)?;
+                }
+            }
+            _ => {
+                return Err(ProtocolError::malformed(format!(
+                    "unknown ballot variant {}",
+                    variant
+                )))
+            }
+        }
+        let ballot_end = self.baseline.len();
+        extended |= self.skip_extensions()?;
+        let vote_end = self.read.len();
+
+        let voter: PublicKey = self.next(true)?;
+        let sig: Signature = self.next(true)?;
+
+        // the bytes `Vote::signing_bytes` gives for the vote we just read
+        let mut signed = self.domain.prefix();
+        if extended {
+            signed.extend(EXTENDED_VOTE);
+            signed.extend(self.read.get(vote_start..vote_end).unwrap_or_default());
+        } else {
+            signed.extend(
+                self.baseline
+                    .get(ballot_start..ballot_end)
+                    .unwrap_or_default(),
+            );
+            signed.extend(gen.to_le_bytes());
+        }
+        self.verifier.verify(&voter, &signed, &sig)?;
+        Ok(extended)
+    }
+
+    // The first release wrote nested votes in the order of its derived `Ord`, which goes by
+    // the proposals' own `Ord`: we sort them by their decoded v1 form to sign the same bytes
+    fn sort_baseline<T: DeserializeOwned + Ord>(
+        &mut self,
+        nested: Vec<Range<usize>>,
+    ) -> Result<()> {
+        let start = match nested.first() {
+            Some(range) if nested.len() > 1 => range.start,
+            _ => return Ok(()),
+        };
+        let mut sorted = Vec::new();
+        for range in nested {
+            let bytes = self.baseline.get(range).unwrap_or_default().to_vec();
+            let vote: v1::SignedVote<T> =
+                bincode::deserialize(&bytes).map_err(ProtocolError::malformed)?;
+            sorted.push((vote, bytes));
+        }
+        sorted.sort_by(|(a, _), (b, _)| a.cmp(b));
+        self.baseline.truncate(start);
+        for (_, bytes) in sorted {
+            self.baseline.extend(bytes);
+        }
+        Ok(())
+    }
+
+    // Reads past the extensions of a vote without collecting them, tells whether there were any
+    fn skip_extensions(&mut self) -> Result<bool> {
+        let n_extensions: u64 = self.next(false)?;
+        for _ in 0..n_extensions {
+            let _id: u16 = self.next(false)?;
+            let len: u64 = self.next(false)?;
+            let start = self.read.len() as u64;
+            (&mut self.reader)
+                .take(len)
+                .read_to_end(&mut self.read)
+                .map_err(ProtocolError::malformed)?;
+            if self.read.len() as u64 - start != len {
+                return Err(ProtocolError::malformed(format!(
+                    "extension of {} bytes is truncated",
+                    len
+                )));
+            }
+        }
+        Ok(n_extensions > 0)
+    }
+}
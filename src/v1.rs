@@ -0,0 +1,538 @@
+//! The v1 API, frozen: downstream crates can depend on it while the core evolves.
+//!
+//! Migrating to the core API:
+//! - `v1::HandoverState` wraps `crate::HandoverState`, `inner`/`inner_mut`/`into_inner` expose it
+//! - the public fields of the v1 state are accessor methods here (`gen()`, `votes()`, ...)
+//! - `handle_signed_vote` returns only the messages, the core returns an `Outcome`
+//!   that also carries the decision and the faults we caught
+//! - the votes, ballots and errors here are those of the first release, they convert to
+//!   and from the core ones. Core votes carrying extensions have no v1 form.
+//! - `v1::VoteMsg` has no correlation id, it converts to and from the core `VoteMsg`
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+
+use rand::{CryptoRng, Rng};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{ConfigError, ProtocolError, StorageError};
+pub use crate::{Generation, Proposal, PublicKey, SecretKey, Signature};
+use core::fmt::Debug;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[allow(clippy::large_enum_variant)]
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("We experienced an IO error")]
+    IO(#[from] std::io::Error),
+    #[error("The operation requested assumes we have at least one member")]
+    NoMembers,
+    #[error("Packet was not destined for this actor: {dest:?} != {actor:?}")]
+    WrongDestination { dest: PublicKey, actor: PublicKey },
+    #[error(
+        "We can not accept any new join requests, network member size is at capacity: {members:?}"
+    )]
+    MembersAtCapacity { members: BTreeSet<PublicKey> },
+    #[error(
+        "An existing member `{requester:?}` can not request to join again. (members: {members:?})"
+    )]
+    JoinRequestForExistingMember {
+        requester: PublicKey,
+        members: BTreeSet<PublicKey>,
+    },
+    #[error("You must be a member to request to leave ({requester:?} not in {members:?})")]
+    LeaveRequestForNonMember {
+        requester: PublicKey,
+        members: BTreeSet<PublicKey>,
+    },
+    #[error("A merged vote must be from the same generation as the child vote: {child_gen} != {merge_gen}")]
+    MergedVotesMustBeFromSameGen {
+        child_gen: Generation,
+        merge_gen: Generation,
+    },
+    #[error("A vote is always for the next generation: vote gen {vote_gen} != {gen} + 1, pending gen: {pending_gen}")]
+    VoteNotForNextGeneration {
+        vote_gen: Generation,
+        gen: Generation,
+        pending_gen: Generation,
+    },
+    #[error("Vote received is from a different generation: vote gen {vote_gen} != {gen}")]
+    VoteWithInvalidGeneration {
+        vote_gen: Generation,
+        gen: Generation,
+    },
+    #[error("({public_key} is not in {members:?})")]
+    NonMember {
+        public_key: PublicKey,
+        members: BTreeSet<PublicKey>,
+    },
+    #[error("Voter changed their mind: {proposal:?}")]
+    VoterChangedMind {
+        proposal: BTreeSet<(PublicKey, String)>,
+    },
+    #[error("Existing vote {existing_vote:?} not compatible with new vote")]
+    ExistingVoteIncompatibleWithNewVote { existing_vote: String },
+    #[error("The super majority ballot does not actually have supermajority: {ballot:?} (members: {members:?})")]
+    SuperMajorityBallotIsNotSuperMajority {
+        ballot: String,
+        members: BTreeSet<PublicKey>,
+    },
+    #[error("Invalid generation {0}")]
+    InvalidGeneration(Generation),
+    #[error("History contains an invalid vote {0:?}")]
+    InvalidVoteInHistory(String),
+    #[error("Failed to encode with bincode")]
+    Encoding(#[from] bincode::Error),
+
+    #[cfg(feature = "ed25519")]
+    #[error("Ed25519 Error {0}")]
+    Ed25519(#[from] crate::ed25519::Error),
+
+    #[cfg(feature = "blsttc")]
+    #[error("Blsttc Error {0}")]
+    Blsttc(#[from] crate::blsttc::Error),
+
+    #[cfg(feature = "bad_crypto")]
+    #[error("Failed Signature Verification")]
+    BadCrypto(#[from] crate::bad_crypto::Error),
+
+    /// The core failed in a way the first release had no error for
+    #[error("{0}")]
+    Core(String),
+}
+
+impl From<crate::Error> for Error {
+    fn from(err: crate::Error) -> Self {
+        match err {
+            crate::Error::Protocol(err) => match err {
+                ProtocolError::WrongDestination { dest, actor } => {
+                    Self::WrongDestination { dest, actor }
+                }
+                ProtocolError::MembersAtCapacity { members } => Self::MembersAtCapacity { members },
+                ProtocolError::JoinRequestForExistingMember { requester, members } => {
+                    Self::JoinRequestForExistingMember { requester, members }
+                }
+                ProtocolError::LeaveRequestForNonMember { requester, members } => {
+                    Self::LeaveRequestForNonMember { requester, members }
+                }
+                ProtocolError::MergedVotesMustBeFromSameGen {
+                    child_gen,
+                    merge_gen,
+                } => Self::MergedVotesMustBeFromSameGen {
+                    child_gen,
+                    merge_gen,
+                },
+                ProtocolError::VoteNotForNextGeneration {
+                    vote_gen,
+                    gen,
+                    pending_gen,
+                } => Self::VoteNotForNextGeneration {
+                    vote_gen,
+                    gen,
+                    pending_gen,
+                },
+                ProtocolError::VoteWithInvalidGeneration { vote_gen, gen } => {
+                    Self::VoteWithInvalidGeneration { vote_gen, gen }
+                }
+                ProtocolError::NonMember {
+                    public_key,
+                    members,
+                } => Self::NonMember {
+                    public_key,
+                    members,
+                },
+                ProtocolError::VoterChangedMind { proposal } => Self::VoterChangedMind { proposal },
+                ProtocolError::ExistingVoteIncompatibleWithNewVote { existing_vote } => {
+                    Self::ExistingVoteIncompatibleWithNewVote { existing_vote }
+                }
+                ProtocolError::SuperMajorityBallotIsNotSuperMajority { ballot, members } => {
+                    Self::SuperMajorityBallotIsNotSuperMajority { ballot, members }
+                }
+                ProtocolError::InvalidGeneration(gen) => Self::InvalidGeneration(gen),
+                ProtocolError::InvalidVoteInHistory(vote) => Self::InvalidVoteInHistory(vote),
+                #[cfg(feature = "ed25519")]
+                ProtocolError::Ed25519(err) => Self::Ed25519(err),
+                #[cfg(feature = "blsttc")]
+                ProtocolError::Blsttc(err) => Self::Blsttc(err),
+                #[cfg(feature = "bad_crypto")]
+                ProtocolError::BadCrypto(err) => Self::BadCrypto(err),
+                err => Self::Core(err.to_string()),
+            },
+            crate::Error::Config(ConfigError::NoMembers) => Self::NoMembers,
+            crate::Error::Storage(StorageError::IO(err)) => Self::IO(err),
+            crate::Error::Storage(StorageError::Encoding(err)) => Self::Encoding(err),
+            err => Self::Core(err.report()),
+        }
+    }
+}
+
+/// A ballot with:
+/// - a proposition vote, all elders that agree on it vote for that proposal
+/// - a merge ballot to inform other elders that there is a split
+/// - a supermajority over supermajority vote, when a proposition has super majority of votes
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Ballot<T>
+where
+    T: Ord,
+{
+    Propose(T),
+    Merge(BTreeSet<SignedVote<T>>),
+    SuperMajority(BTreeSet<SignedVote<T>>),
+}
+
+impl<T> std::fmt::Debug for Ballot<T>
+where
+    T: Debug + Ord,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Ballot::Propose(r) => write!(f, "P({:?})", r),
+            Ballot::Merge(votes) => write!(f, "M{:?}", votes),
+            Ballot::SuperMajority(votes) => write!(f, "SM{:?}", votes),
+        }
+    }
+}
+
+impl<'de, T> Ballot<T>
+where
+    T: Clone + Copy + Ord + Serialize + Deserialize<'de> + Debug,
+{
+    fn simplify_votes(signed_votes: &BTreeSet<SignedVote<T>>) -> BTreeSet<SignedVote<T>> {
+        let mut simpler_votes = BTreeSet::new();
+        for v in signed_votes.iter() {
+            let this_vote_is_superseded = signed_votes
+                .iter()
+                .filter(|other_v| other_v != &v)
+                .any(|other_v| other_v.supersedes(v));
+
+            if !this_vote_is_superseded {
+                simpler_votes.insert(v.clone());
+            }
+        }
+        simpler_votes
+    }
+
+    pub fn simplify(&self) -> Self {
+        match &self {
+            Ballot::Propose(_) => self.clone(), // already in simplest form
+            Ballot::Merge(votes) => Ballot::Merge(Self::simplify_votes(votes)),
+            Ballot::SuperMajority(votes) => Ballot::SuperMajority(Self::simplify_votes(votes)),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Vote<T>
+where
+    T: Ord,
+{
+    pub gen: Generation,
+    pub ballot: Ballot<T>,
+}
+
+impl<T> Debug for Vote<T>
+where
+    T: Ord + Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "G{}-{:?}", self.gen, self.ballot)
+    }
+}
+
+impl<'de, T> Vote<T>
+where
+    T: Clone + Copy + PartialEq + Eq + PartialOrd + Ord + Debug + Serialize + Deserialize<'de>,
+{
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        Ok(bincode::serialize(&(&self.ballot, &self.gen))?)
+    }
+
+    pub fn is_super_majority_ballot(&self) -> bool {
+        matches!(self.ballot, Ballot::SuperMajority(_))
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct SignedVote<T>
+where
+    T: Ord,
+{
+    pub vote: Vote<T>,
+    pub voter: PublicKey,
+    pub sig: Signature,
+}
+
+impl<T> Debug for SignedVote<T>
+where
+    T: Ord + Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}@{}", self.vote, self.voter)
+    }
+}
+
+impl<'de, T> SignedVote<T>
+where
+    T: Clone + Copy + Debug + Ord + Serialize + Deserialize<'de>,
+{
+    pub fn validate_signature(&self) -> Result<()> {
+        Ok(self.voter.verify(&self.vote.to_bytes()?, &self.sig)?)
+    }
+
+    pub fn unpack_votes(&self) -> BTreeSet<&Self> {
+        match &self.vote.ballot {
+            Ballot::Propose(_) => BTreeSet::from_iter([self]),
+            Ballot::Merge(votes) | Ballot::SuperMajority(votes) => BTreeSet::from_iter(
+                std::iter::once(self).chain(votes.iter().flat_map(Self::unpack_votes)),
+            ),
+        }
+    }
+
+    pub fn proposals(&self) -> BTreeSet<(PublicKey, T)> {
+        match &self.vote.ballot {
+            Ballot::Propose(prop) => BTreeSet::from_iter([(self.voter, *prop)]),
+            Ballot::Merge(votes) | Ballot::SuperMajority(votes) => {
+                BTreeSet::from_iter(votes.iter().flat_map(Self::proposals))
+            }
+        }
+    }
+
+    pub fn supersedes(&self, signed_vote: &SignedVote<T>) -> bool {
+        if self == signed_vote {
+            true
+        } else {
+            match &self.vote.ballot {
+                Ballot::Propose(_) => false,
+                Ballot::Merge(votes) | Ballot::SuperMajority(votes) => {
+                    votes.iter().any(|v| v.supersedes(signed_vote))
+                }
+            }
+        }
+    }
+}
+
+// Without extensions the core signs the same bytes as v1 did, signatures carry over both ways
+impl<T: Ord + Serialize> From<Vote<T>> for crate::Vote<T> {
+    fn from(vote: Vote<T>) -> Self {
+        let ballot = match vote.ballot {
+            Ballot::Propose(proposal) => crate::Ballot::Propose(proposal),
+            Ballot::Merge(votes) => crate::Ballot::Merge(BTreeSet::from_iter(
+                votes.into_iter().map(crate::SignedVote::from),
+            )),
+            Ballot::SuperMajority(votes) => crate::Ballot::SuperMajority(BTreeSet::from_iter(
+                votes.into_iter().map(crate::SignedVote::from),
+            )),
+        };
+        Self {
+            gen: vote.gen,
+            ballot,
+            extensions: Default::default(),
+        }
+    }
+}
+
+impl<T: Ord + Serialize> From<SignedVote<T>> for crate::SignedVote<T> {
+    fn from(signed_vote: SignedVote<T>) -> Self {
+        Self {
+            vote: signed_vote.vote.into(),
+            voter: signed_vote.voter,
+            sig: signed_vote.sig,
+        }
+    }
+}
+
+impl<T: Ord + Serialize> TryFrom<crate::Vote<T>> for Vote<T> {
+    type Error = Error;
+
+    fn try_from(vote: crate::Vote<T>) -> Result<Self> {
+        if !vote.extensions.is_empty() {
+            return Err(Error::Core(format!(
+                "a vote of generation {} carries extensions, v1 can't represent them",
+                vote.gen
+            )));
+        }
+        let v1_votes = |votes: BTreeSet<crate::SignedVote<T>>| {
+            votes
+                .into_iter()
+                .map(SignedVote::try_from)
+                .collect::<Result<BTreeSet<_>>>()
+        };
+        let ballot = match vote.ballot {
+            crate::Ballot::Propose(proposal) => Ballot::Propose(proposal),
+            crate::Ballot::Merge(votes) => Ballot::Merge(v1_votes(votes)?),
+            crate::Ballot::SuperMajority(votes) => Ballot::SuperMajority(v1_votes(votes)?),
+        };
+        Ok(Self {
+            gen: vote.gen,
+            ballot,
+        })
+    }
+}
+
+impl<T: Ord + Serialize> TryFrom<crate::SignedVote<T>> for SignedVote<T> {
+    type Error = Error;
+
+    fn try_from(signed_vote: crate::SignedVote<T>) -> Result<Self> {
+        Ok(Self {
+            vote: signed_vote.vote.try_into()?,
+            voter: signed_vote.voter,
+            sig: signed_vote.sig,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Debug)]
+pub struct VoteMsg<T>
+where
+    T: Ord + Serialize,
+{
+    pub vote: SignedVote<T>,
+    pub dest: PublicKey,
+}
+
+impl<T: Ord + Serialize> TryFrom<crate::VoteMsg<T>> for VoteMsg<T> {
+    type Error = Error;
+
+    fn try_from(msg: crate::VoteMsg<T>) -> Result<Self> {
+        Ok(Self {
+            vote: msg.vote.try_into()?,
+            dest: msg.dest,
+        })
+    }
+}
+
+impl<T: Ord + Serialize> From<VoteMsg<T>> for crate::VoteMsg<T> {
+    fn from(msg: VoteMsg<T>) -> Self {
+        let vote = crate::SignedVote::from(msg.vote);
+        Self {
+            priority: crate::Priority::of(&vote.vote.ballot),
+            vote,
+            dest: msg.dest,
+            correlation_id: None,
+        }
+    }
+}
+
+fn v1_msgs<T: Ord + Serialize>(msgs: Vec<crate::VoteMsg<T>>) -> Result<Vec<VoteMsg<T>>> {
+    msgs.into_iter().map(VoteMsg::try_from).collect()
+}
+
+#[derive(Debug)]
+pub struct HandoverState<T>
+where
+    T: Ord + Serialize,
+{
+    core: crate::HandoverState<T>,
+    secret_key: Option<Arc<SecretKey>>, // shared with the core, which signs with it
+}
+
+impl<'de, T> HandoverState<T>
+where
+    T: Clone + Copy + Debug + Ord + PartialEq + Serialize + Deserialize<'de> + Proposal,
+{
+    pub fn from(
+        secret_key: SecretKey,
+        gen: Generation,
+        voters: BTreeSet<PublicKey>,
+    ) -> HandoverState<T> {
+        let secret_key = Arc::new(secret_key);
+        HandoverState {
+            core: crate::HandoverState::from(secret_key.clone(), gen, voters),
+            secret_key: Some(secret_key),
+        }
+    }
+
+    pub fn random(rng: impl Rng + CryptoRng, voters: BTreeSet<PublicKey>) -> HandoverState<T> {
+        Self::from(SecretKey::random(rng), Default::default(), voters)
+    }
+
+    pub fn public_key(&self) -> PublicKey {
+        self.core.public_key()
+    }
+
+    /// `None` when the state was built from a core state, its signer may not hold a key
+    pub fn secret_key(&self) -> Option<&SecretKey> {
+        self.secret_key.as_deref()
+    }
+
+    pub fn gen(&self) -> Generation {
+        self.core.gen
+    }
+
+    /// The votes carrying extensions are left out, v1 can't represent them
+    pub fn votes(&self) -> BTreeMap<PublicKey, SignedVote<T>> {
+        BTreeMap::from_iter(self.core.votes.iter().filter_map(|(voter, vote)| {
+            let vote = SignedVote::try_from(vote.clone()).ok()?;
+            Some((*voter, vote))
+        }))
+    }
+
+    pub fn voters(&self) -> &BTreeSet<PublicKey> {
+        &self.core.voters
+    }
+
+    pub fn consensus(&self) -> Option<T> {
+        self.core.consensus
+    }
+
+    pub fn propose(&mut self, proposition: T) -> Result<Vec<VoteMsg<T>>> {
+        v1_msgs(self.core.propose(proposition)?)
+    }
+
+    pub fn save_reached_consensus(&mut self, consensus: Option<T>) {
+        self.core.save_reached_consensus(consensus)
+    }
+
+    pub fn force_join(&mut self, public_key: PublicKey) {
+        self.core.force_join(public_key)
+    }
+
+    /// The messages carrying extensions are left out, v1 can't represent them
+    pub fn anti_entropy(&self, actor: PublicKey) -> Vec<VoteMsg<T>> {
+        let msgs = self.core.full_anti_entropy(actor).into_iter();
+        msgs.filter_map(|msg| VoteMsg::try_from(msg).ok()).collect()
+    }
+
+    /// Faulty votes are refused with the error the first release gave, the core keeps the evidence
+    pub fn handle_signed_vote(&mut self, signed_vote: SignedVote<T>) -> Result<Vec<VoteMsg<T>>> {
+        let signed_vote = crate::SignedVote::from(signed_vote);
+        let outcome = self.core.handle_signed_vote(signed_vote.clone())?;
+        if !outcome.faults.is_empty() {
+            self.core.validate_signed_vote(&signed_vote)?;
+        }
+        v1_msgs(outcome.msgs)
+    }
+
+    pub fn sign_vote(&self, vote: Vote<T>) -> Result<SignedVote<T>> {
+        self.core.sign_vote(vote.into())?.try_into()
+    }
+
+    pub fn validate_signed_vote(&self, signed_vote: &SignedVote<T>) -> Result<()> {
+        Ok(self
+            .core
+            .validate_signed_vote(&signed_vote.clone().into())?)
+    }
+
+    pub fn inner(&self) -> &crate::HandoverState<T> {
+        &self.core
+    }
+
+    pub fn inner_mut(&mut self) -> &mut crate::HandoverState<T> {
+        &mut self.core
+    }
+
+    pub fn into_inner(self) -> crate::HandoverState<T> {
+        self.core
+    }
+}
+
+impl<T: Ord + Serialize> From<crate::HandoverState<T>> for HandoverState<T> {
+    fn from(core: crate::HandoverState<T>) -> Self {
+        Self {
+            core,
+            secret_key: None,
+        }
+    }
+}
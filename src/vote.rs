@@ -1,9 +1,13 @@
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize, Serializer};
 
-use crate::{PublicKey, Result, Signature};
+use crate::signer::KeyVerifier;
+use crate::{
+    proposal_hash, Hash, Proposal, ProtocolError, PublicKey, Result, Signature, VoteVerifier,
+};
 
+use core::cmp::Ordering;
 use core::fmt::Debug;
 
 /// Probably use id based on sn_membership's member history
@@ -15,19 +19,72 @@ pub type Generation = u64;
 /// - a proposition vote, all elders that agree on it vote for that proposal
 /// - a merge ballot to inform other elders that there is a split
 /// - a supermajority over supermajority vote, when a proposition has super majority of votes
-#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub enum Ballot<T>
 where
-    T: Ord,
+    T: Ord + Serialize,
 {
     Propose(T),
     Merge(BTreeSet<SignedVote<T>>),
     SuperMajority(BTreeSet<SignedVote<T>>),
 }
 
+// Proposals are compared by the hash of their encoding, so ballots, and the vote sets
+// they hold, are ordered the same way on every node whatever the proposal's own `Ord` does
+fn encoding_hash<T: Serialize>(proposal: &T) -> Option<Hash> {
+    Hash::of_encoding(proposal).ok()
+}
+
+impl<T> Ord for Ballot<T>
+where
+    T: Ord + Serialize,
+{
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Ballot::Propose(a), Ballot::Propose(b)) => encoding_hash(a).cmp(&encoding_hash(b)),
+            (Ballot::Merge(a), Ballot::Merge(b))
+            | (Ballot::SuperMajority(a), Ballot::SuperMajority(b)) => a.cmp(b),
+            _ => self.variant().cmp(&other.variant()),
+        }
+    }
+}
+
+impl<T> Ballot<T>
+where
+    T: Ord + Serialize,
+{
+    fn variant(&self) -> u8 {
+        match self {
+            Ballot::Propose(_) => 0,
+            Ballot::Merge(_) => 1,
+            Ballot::SuperMajority(_) => 2,
+        }
+    }
+}
+
+impl<T> PartialOrd for Ballot<T>
+where
+    T: Ord + Serialize,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> PartialEq for Ballot<T>
+where
+    T: Ord + Serialize,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T> Eq for Ballot<T> where T: Ord + Serialize {}
+
 impl<T> std::fmt::Debug for Ballot<T>
 where
-    T: Debug + Ord,
+    T: Debug + Ord + Serialize,
 {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -40,7 +97,7 @@ where
 
 impl<'de, T> Ballot<T>
 where
-    T: Clone + Copy + Ord + Serialize + Deserialize<'de> + Debug,
+    T: Clone + Copy + Ord + Serialize + Deserialize<'de> + Debug + Proposal,
 {
     fn simplify_votes(signed_votes: &BTreeSet<SignedVote<T>>) -> BTreeSet<SignedVote<T>> {
         let mut simpler_votes = BTreeSet::new();
@@ -66,18 +123,157 @@ where
     }
 }
 
-#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+/// What a signature is for, a vote signed in one domain does not verify in another
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum SigningDomain {
+    /// Votes that decide the handover
+    Live,
+    /// Votes of a drill, they can never be passed off as live votes
+    Rehearsal,
+    /// Votes of an instance spawned by a split, bound to its genesis.
+    /// They don't verify in the parent's rounds nor in the sibling's.
+    Split(Hash),
+}
+
+impl SigningDomain {
+    // Live votes sign the bare vote bytes, so those the first release could express
+    // still verify on nodes predating domains
+    pub(crate) fn prefix(&self) -> Vec<u8> {
+        match self {
+            SigningDomain::Live => vec![],
+            SigningDomain::Rehearsal => b"sn_handover/rehearsal/".to_vec(),
+            SigningDomain::Split(genesis) => {
+                [b"sn_handover/split/".as_slice(), genesis.as_bytes()].concat()
+            }
+        }
+    }
+}
+
+/// Leads the signed bytes of extended votes, the baseline signed bytes start with a ballot
+/// variant instead, so the two can't be mistaken for one another
+pub const EXTENDED_VOTE: &[u8] = b"sn_handover/extended/";
+
+// The layout of the first release, votes had no extensions, borrowed to encode without cloning.
+// Nested votes are written in the order of that release's derived `Ord`, it's part of what it signed.
+pub(crate) struct BaselineBallot<'a, T: Ord + Serialize>(pub(crate) &'a Ballot<T>);
+struct BaselineVotes<'a, T: Ord + Serialize>(&'a BTreeSet<SignedVote<T>>);
+struct BaselineSignedVote<'a, T: Ord + Serialize>(&'a SignedVote<T>);
+
+impl<'a, T: Ord + Serialize> Serialize for BaselineBallot<'a, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        match self.0 {
+            Ballot::Propose(proposal) => {
+                serializer.serialize_newtype_variant("Ballot", 0, "Propose", proposal)
+            }
+            Ballot::Merge(votes) => {
+                serializer.serialize_newtype_variant("Ballot", 1, "Merge", &BaselineVotes(votes))
+            }
+            Ballot::SuperMajority(votes) => serializer.serialize_newtype_variant(
+                "Ballot",
+                2,
+                "SuperMajority",
+                &BaselineVotes(votes),
+            ),
+        }
+    }
+}
+
+impl<'a, T: Ord + Serialize> Serialize for BaselineVotes<'a, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_seq(baseline_order(self.0).into_iter().map(BaselineSignedVote))
+    }
+}
+
+// The votes sorted as the first release sorted them: by generation, then by ballot
+// (variant, then the proposal's own `Ord` or the nested votes in this same order), voter, signature
+fn baseline_order<T: Ord + Serialize>(votes: &BTreeSet<SignedVote<T>>) -> Vec<&SignedVote<T>> {
+    let mut sorted = Vec::from_iter(votes);
+    sorted.sort_by(|a, b| baseline_cmp(a, b));
+    sorted
+}
+
+fn baseline_cmp<T: Ord + Serialize>(a: &SignedVote<T>, b: &SignedVote<T>) -> Ordering {
+    let ballots = |a: &Ballot<T>, b: &Ballot<T>| match (a, b) {
+        (Ballot::Propose(a), Ballot::Propose(b)) => a.cmp(b),
+        (Ballot::Merge(a), Ballot::Merge(b))
+        | (Ballot::SuperMajority(a), Ballot::SuperMajority(b)) => {
+            let (a, b) = (baseline_order(a), baseline_order(b));
+            a.iter()
+                .zip(b.iter())
+                .map(|(a, b)| baseline_cmp(a, b))
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or_else(|| a.len().cmp(&b.len()))
+        }
+        _ => a.variant().cmp(&b.variant()),
+    };
+    a.vote
+        .gen
+        .cmp(&b.vote.gen)
+        .then_with(|| ballots(&a.vote.ballot, &b.vote.ballot))
+        .then_with(|| a.voter.cmp(&b.voter))
+        .then_with(|| a.sig.cmp(&b.sig))
+}
+
+impl<'a, T: Ord + Serialize> Serialize for BaselineSignedVote<'a, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let signed_vote = self.0;
+        // a vote was a generation and a ballot, then came the voter and the signature
+        let vote = (
+            signed_vote.vote.gen,
+            BaselineBallot(&signed_vote.vote.ballot),
+        );
+        (vote, &signed_vote.voter, &signed_vote.sig).serialize(serializer)
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Vote<T>
 where
-    T: Ord,
+    T: Ord + Serialize,
 {
     pub gen: Generation,
     pub ballot: Ballot<T>,
+    /// Metadata by extension id, signed along with the vote.
+    /// We ignore them, later versions can add vote metadata without a protocol flag day.
+    pub extensions: BTreeMap<u16, Vec<u8>>,
 }
 
+impl<T> Ord for Vote<T>
+where
+    T: Ord + Serialize,
+{
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.gen, &self.ballot, &self.extensions).cmp(&(
+            other.gen,
+            &other.ballot,
+            &other.extensions,
+        ))
+    }
+}
+
+impl<T> PartialOrd for Vote<T>
+where
+    T: Ord + Serialize,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> PartialEq for Vote<T>
+where
+    T: Ord + Serialize,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T> Eq for Vote<T> where T: Ord + Serialize {}
+
 impl<T> Debug for Vote<T>
 where
-    T: Ord + Debug,
+    T: Ord + Serialize + Debug,
 {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "G{}-{:?}", self.gen, self.ballot)
@@ -86,10 +282,47 @@ where
 
 impl<'de, T> Vote<T>
 where
-    T: Clone + Copy + PartialEq + Eq + PartialOrd + Ord + Debug + Serialize + Deserialize<'de>,
+    T: Clone
+        + Copy
+        + PartialEq
+        + Eq
+        + PartialOrd
+        + Ord
+        + Debug
+        + Serialize
+        + Deserialize<'de>
+        + Proposal,
 {
+    /// The bytes a voter signs. A vote the first release could express, with no extensions
+    /// anywhere in it, signs the ballot and generation in that release's layout so its nodes
+    /// still verify it. Any other vote signs its own encoding behind `EXTENDED_VOTE`.
     pub fn to_bytes(&self) -> Result<Vec<u8>> {
-        Ok(bincode::serialize(&(&self.ballot, &self.gen))?)
+        if self.is_extended() {
+            let mut bytes = EXTENDED_VOTE.to_vec();
+            bincode::serialize_into(&mut bytes, self).map_err(ProtocolError::malformed)?;
+            Ok(bytes)
+        } else {
+            bincode::serialize(&(BaselineBallot(&self.ballot), self.gen))
+                .map_err(ProtocolError::malformed)
+        }
+    }
+
+    /// Whether this vote or one nested in it carries extensions
+    pub fn is_extended(&self) -> bool {
+        !self.extensions.is_empty()
+            || match &self.ballot {
+                Ballot::Propose(_) => false,
+                Ballot::Merge(votes) | Ballot::SuperMajority(votes) => {
+                    votes.iter().any(|vote| vote.vote.is_extended())
+                }
+            }
+    }
+
+    /// The bytes a voter signs in `domain`
+    pub fn signing_bytes(&self, domain: SigningDomain) -> Result<Vec<u8>> {
+        let mut bytes = domain.prefix();
+        bytes.extend(self.to_bytes()?);
+        Ok(bytes)
     }
 
     pub fn is_super_majority_ballot(&self) -> bool {
@@ -97,19 +330,61 @@ where
     }
 }
 
-#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct SignedVote<T>
 where
-    T: Ord,
+    T: Ord + Serialize,
 {
     pub vote: Vote<T>,
     pub voter: PublicKey,
     pub sig: Signature,
 }
 
+impl<T: Ord + Serialize> SignedVote<T> {
+    /// This vote and every vote nested in it, counted each time it's nested
+    pub fn vote_count(&self) -> usize {
+        let nested = match &self.vote.ballot {
+            Ballot::Propose(_) => 0,
+            Ballot::Merge(votes) | Ballot::SuperMajority(votes) => {
+                votes.iter().map(Self::vote_count).sum()
+            }
+        };
+        1 + nested
+    }
+}
+
+impl<T> Ord for SignedVote<T>
+where
+    T: Ord + Serialize,
+{
+    fn cmp(&self, other: &Self) -> Ordering {
+        (&self.vote, &self.voter, &self.sig).cmp(&(&other.vote, &other.voter, &other.sig))
+    }
+}
+
+impl<T> PartialOrd for SignedVote<T>
+where
+    T: Ord + Serialize,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> PartialEq for SignedVote<T>
+where
+    T: Ord + Serialize,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T> Eq for SignedVote<T> where T: Ord + Serialize {}
+
 impl<T> Debug for SignedVote<T>
 where
-    T: Ord + Debug,
+    T: Ord + Serialize + Debug,
 {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{:?}@{}", self.vote, self.voter)
@@ -118,26 +393,133 @@ where
 
 impl<'de, T> SignedVote<T>
 where
-    T: Clone + Copy + Debug + Ord + Serialize + Deserialize<'de>,
+    T: Clone + Copy + Debug + Ord + Serialize + Deserialize<'de> + Proposal,
 {
     pub fn validate_signature(&self) -> Result<()> {
-        Ok(self.voter.verify(&self.vote.to_bytes()?, &self.sig)?)
+        self.validate_signature_in(SigningDomain::Live)
+    }
+
+    pub fn validate_signature_in(&self, domain: SigningDomain) -> Result<()> {
+        self.validate_signature_with(&KeyVerifier, domain)
+    }
+
+    pub fn validate_signature_with(
+        &self,
+        verifier: &dyn VoteVerifier,
+        domain: SigningDomain,
+    ) -> Result<()> {
+        verifier.verify(&self.voter, &self.vote.signing_bytes(domain)?, &self.sig)
     }
 
     pub fn unpack_votes(&self) -> BTreeSet<&Self> {
-        match &self.vote.ballot {
-            Ballot::Propose(_) => BTreeSet::from_iter([self]),
-            Ballot::Merge(votes) | Ballot::SuperMajority(votes) => BTreeSet::from_iter(
-                std::iter::once(self).chain(votes.iter().flat_map(Self::unpack_votes)),
-            ),
+        let mut votes = BTreeSet::new();
+        self.unpack_into(&mut votes);
+        votes
+    }
+
+    // Walks the nested votes into a single set, instead of a set per level
+    fn unpack_into<'a>(&'a self, votes: &mut BTreeSet<&'a Self>) {
+        if !votes.insert(self) {
+            return;
+        }
+        if let Ballot::Merge(nested) | Ballot::SuperMajority(nested) = &self.vote.ballot {
+            for vote in nested.iter() {
+                vote.unpack_into(votes);
+            }
         }
     }
 
     pub fn proposals(&self) -> BTreeSet<(PublicKey, T)> {
+        let mut proposals = BTreeSet::new();
+        self.proposals_into(&mut proposals);
+        proposals
+    }
+
+    fn proposals_into(&self, proposals: &mut BTreeSet<(PublicKey, T)>) {
         match &self.vote.ballot {
-            Ballot::Propose(prop) => BTreeSet::from_iter([(self.voter, *prop)]),
+            Ballot::Propose(prop) => {
+                proposals.insert((self.voter, *prop));
+            }
+            Ballot::Merge(votes) | Ballot::SuperMajority(votes) => {
+                for vote in votes.iter() {
+                    vote.proposals_into(proposals);
+                }
+            }
+        }
+    }
+
+    /// Like `proposals` but identifying each proposal by its hash,
+    /// consensus critical paths use this so they never rely on the proposal's `Ord`
+    pub fn proposal_hashes(&self) -> Result<BTreeSet<(PublicKey, Hash)>> {
+        let mut hashes = BTreeSet::new();
+        self.for_each_proposal(&mut |voter, prop| {
+            hashes.insert((voter, proposal_hash(prop)?));
+            Ok(())
+        })?;
+        Ok(hashes)
+    }
+
+    /// The hashes of the proposals this vote is for, regardless of who proposed them
+    pub fn proposal_set(&self) -> Result<BTreeSet<Hash>> {
+        let mut hashes = BTreeSet::new();
+        self.for_each_proposal(&mut |_, prop| {
+            hashes.insert(proposal_hash(prop)?);
+            Ok(())
+        })?;
+        Ok(hashes)
+    }
+
+    // Visits the proposals of the nested votes without collecting them level by level
+    pub(crate) fn for_each_proposal(
+        &self,
+        f: &mut impl FnMut(PublicKey, &T) -> Result<()>,
+    ) -> Result<()> {
+        match &self.vote.ballot {
+            Ballot::Propose(prop) => f(self.voter, prop),
             Ballot::Merge(votes) | Ballot::SuperMajority(votes) => {
-                BTreeSet::from_iter(votes.iter().flat_map(Self::proposals))
+                for vote in votes.iter() {
+                    vote.for_each_proposal(f)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Identifies this exact signed vote
+    pub fn hash(&self) -> Result<Hash> {
+        let bytes = bincode::serialize(self).map_err(ProtocolError::malformed)?;
+        Ok(Hash::of(&bytes))
+    }
+
+    /// A loggable form of this vote, proposals are shown by their hash instead of their contents
+    pub fn redacted(&self) -> Redacted<'_, T> {
+        Redacted(self)
+    }
+
+    /// Where `nested` sits in this vote: its index in the ballot at each level down,
+    /// ballots list their votes in order, as they are encoded
+    pub fn path_to(&self, nested: &SignedVote<T>) -> Option<Vec<usize>> {
+        if self == nested {
+            return Some(Vec::new());
+        }
+        match &self.vote.ballot {
+            Ballot::Propose(_) => None,
+            Ballot::Merge(votes) | Ballot::SuperMajority(votes) => {
+                votes.iter().enumerate().find_map(|(i, vote)| {
+                    let mut path = vote.path_to(nested)?;
+                    path.insert(0, i);
+                    Some(path)
+                })
+            }
+        }
+    }
+
+    /// Propose votes are cast in round 0, each level of nested votes adds a round
+    pub fn round(&self) -> usize {
+        match &self.vote.ballot {
+            Ballot::Propose(_) => 0,
+            Ballot::Merge(votes) | Ballot::SuperMajority(votes) => {
+                1 + votes.iter().map(Self::round).max().unwrap_or_default()
             }
         }
     }
@@ -156,11 +538,115 @@ where
     }
 }
 
+/// Debug printing of a vote that doesn't leak the proposals, which may be sensitive (e.g. key material)
+#[derive(Clone, Copy)]
+pub struct Redacted<'a, T: Ord + Serialize>(&'a SignedVote<T>);
+
+impl<'a, T> Debug for Redacted<'a, T>
+where
+    T: Ord + Serialize + Proposal,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let signed_vote = self.0;
+        write!(f, "G{}-", signed_vote.vote.gen)?;
+        match &signed_vote.vote.ballot {
+            Ballot::Propose(prop) => match proposal_hash(prop) {
+                Ok(hash) => write!(f, "P({:?})", hash)?,
+                Err(_) => write!(f, "P(<unencodable>)")?,
+            },
+            Ballot::Merge(votes) => {
+                write!(f, "M")?;
+                f.debug_set()
+                    .entries(votes.iter().map(|v| Redacted(v)))
+                    .finish()?;
+            }
+            Ballot::SuperMajority(votes) => {
+                write!(f, "SM")?;
+                f.debug_set()
+                    .entries(votes.iter().map(|v| Redacted(v)))
+                    .finish()?;
+            }
+        }
+        write!(f, "@{}", signed_vote.voter)
+    }
+}
+
+/// Lets the application match the responses to the message that triggered them,
+/// responses echo the correlation id of the message they answer
+pub type CorrelationId = u64;
+
+/// How urgently a message should be delivered, from least to most urgent.
+/// QoS-aware transports schedule the higher classes first.
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub enum Priority {
+    /// Our view of the votes resent to a peer, it may well know of them already
+    #[default]
+    AntiEntropy,
+    Propose,
+    Merge,
+    SuperMajority,
+    /// Votes that complete a super majority over super majorities, peers may decide on them
+    Decision,
+}
+
+impl Priority {
+    /// The class of a message carrying `ballot`
+    pub fn of<T: Ord + Serialize>(ballot: &Ballot<T>) -> Self {
+        match ballot {
+            Ballot::Propose(_) => Self::Propose,
+            Ballot::Merge(_) => Self::Merge,
+            Ballot::SuperMajority(_) => Self::SuperMajority,
+        }
+    }
+}
+
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Debug)]
 pub struct VoteMsg<T>
 where
-    T: Ord,
+    T: Ord + Serialize,
 {
     pub vote: SignedVote<T>,
     pub dest: PublicKey,
+    pub correlation_id: Option<CorrelationId>,
+    pub priority: Priority,
+}
+
+impl<T> VoteMsg<T>
+where
+    T: Ord + Serialize,
+{
+    pub fn with_correlation_id(mut self, correlation_id: CorrelationId) -> Self {
+        self.correlation_id = Some(correlation_id);
+        self
+    }
+}
+
+/// All the votes a replica knows of for a generation
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Debug)]
+pub struct VoteSummary<T>
+where
+    T: Ord + Serialize,
+{
+    pub gen: Generation,
+    pub votes: BTreeSet<SignedVote<T>>,
+}
+
+/// The hashes of the votes a replica knows of, nested ones included, by voter.
+/// A peer answers with the votes missing from it instead of resending all of its votes.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Debug)]
+pub struct VoteDigest {
+    pub gen: Generation,
+    pub hashes: BTreeMap<PublicKey, BTreeSet<Hash>>,
+    pub requester: PublicKey,
+    pub dest: PublicKey,
+}
+
+impl VoteDigest {
+    pub fn knows(&self, voter: &PublicKey, hash: &Hash) -> bool {
+        self.hashes
+            .get(voter)
+            .is_some_and(|hashes| hashes.contains(hash))
+    }
 }
@@ -0,0 +1,202 @@
+//! The encoding of vote messages between nodes, whatever crate version they run.
+//!
+//! A message is a wire version byte followed by the bincode encoding of the message in the
+//! layout of that version. We decode each version we ever shipped, so nodes can be upgraded
+//! one at a time, and answer an older peer in its version with `to_bytes_in`.
+//! Signatures cover the encoding of the vote alone, re-framing a message keeps them valid.
+//!
+//! The `estimated_wire_size` of votes and proofs adds up the sizes of their parts instead of
+//! encoding them, only the proposals are measured. It is the exact size in version 2.
+
+use std::collections::BTreeSet;
+use std::io::Read;
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    stream, v1, Ballot, CompactVoteMsg, Decision, Proposal, ProtocolError, Result, SignedVote,
+    SigningDomain, Vote, VoteMsg, VoteVerifier,
+};
+use core::fmt::Debug;
+
+/// The layout of the first release: the vote as it was then, see `v1::VoteMsg`,
+/// and its destination. Votes carrying extensions have no encoding in it.
+pub const WIRE_V1: u8 = 1;
+/// Adds the correlation id and the priority class
+pub const WIRE_V2: u8 = 2;
+/// Sends the vote compacted, each distinct nested vote once, see `CompactVote`
+pub const WIRE_V3: u8 = 3;
+
+/// The version we encode in unless asked otherwise
+pub const WIRE_VERSION: u8 = WIRE_V3;
+pub const OLDEST_WIRE_VERSION: u8 = WIRE_V1;
+
+fn unsupported(version: u8) -> crate::Error {
+    ProtocolError::UnsupportedWireVersion {
+        version,
+        oldest: OLDEST_WIRE_VERSION,
+        newest: WIRE_VERSION,
+    }
+    .into()
+}
+
+impl<T> VoteMsg<T>
+where
+    T: Ord + Serialize + DeserializeOwned,
+{
+    pub fn to_bytes(&self) -> Result<Vec<u8>>
+    where
+        T: Clone,
+    {
+        self.to_bytes_in(WIRE_VERSION)
+    }
+
+    /// Encodes in an older `version` for a peer that wasn't upgraded yet,
+    /// what that version has no field for is left out
+    pub fn to_bytes_in(&self, version: u8) -> Result<Vec<u8>>
+    where
+        T: Clone,
+    {
+        let mut bytes = vec![version];
+        match version {
+            WIRE_V1 => {
+                let msg = v1::VoteMsg::try_from(self.clone()).map_err(|err| {
+                    ProtocolError::NotInWireVersion {
+                        version,
+                        reason: err.to_string(),
+                    }
+                })?;
+                bincode::serialize_into(&mut bytes, &msg)?
+            }
+            WIRE_V2 => bincode::serialize_into(&mut bytes, self)?,
+            WIRE_V3 => bincode::serialize_into(&mut bytes, &self.compact())?,
+            _ => return Err(unsupported(version)),
+        }
+        Ok(bytes)
+    }
+
+    /// Decodes a message of any version we support, upgraded to the current layout
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self>
+    where
+        T: Clone,
+    {
+        let (version, msg) = match bytes.split_first() {
+            Some((version, msg)) => (*version, msg),
+            None => return Err(ProtocolError::MissingWireVersion.into()),
+        };
+        match version {
+            WIRE_V1 => Ok(bincode::deserialize::<v1::VoteMsg<T>>(msg)
+                .map_err(ProtocolError::malformed)?
+                .into()),
+            WIRE_V2 => bincode::deserialize(msg).map_err(ProtocolError::malformed),
+            WIRE_V3 => bincode::deserialize::<CompactVoteMsg<T>>(msg)
+                .map_err(ProtocolError::malformed)?
+                .expand(),
+            _ => Err(unsupported(version)),
+        }
+    }
+
+    /// Reads a message of any version we support off `reader`, reading at most `limit` bytes,
+    /// and checks the signatures of its votes with `verifier` in `domain` before returning it.
+    /// In version 2 they are checked before any of the vote is decoded, see `stream`.
+    pub fn read_verified(
+        reader: impl Read,
+        limit: u64,
+        verifier: &dyn VoteVerifier,
+        domain: SigningDomain,
+    ) -> Result<Self>
+    where
+        T: Clone + Copy + Debug + Proposal,
+    {
+        let mut reader = reader.take(limit);
+        let mut version = [0u8];
+        if reader
+            .read(&mut version)
+            .map_err(ProtocolError::malformed)?
+            == 0
+        {
+            return Err(ProtocolError::MissingWireVersion.into());
+        }
+        match version {
+            [WIRE_V2] => {
+                let vote = stream::read_signed_vote(&mut reader, limit, verifier, domain)?;
+                let (dest, correlation_id, priority) =
+                    bincode::deserialize_from(&mut reader).map_err(ProtocolError::malformed)?;
+                Ok(VoteMsg {
+                    vote,
+                    dest,
+                    correlation_id,
+                    priority,
+                })
+            }
+            _ => {
+                let mut bytes = version.to_vec();
+                reader
+                    .read_to_end(&mut bytes)
+                    .map_err(ProtocolError::malformed)?;
+                let msg = Self::from_bytes(&bytes)?;
+                for vote in msg.vote.unpack_votes() {
+                    vote.validate_signature_with(verifier, domain)?;
+                }
+                Ok(msg)
+            }
+        }
+    }
+}
+
+// Bincode's fixed sizes, of lengths, enum variants and option tags
+const LEN: usize = 8;
+const VARIANT: usize = 4;
+const TAG: usize = 1;
+
+fn measured(value: &impl Serialize) -> Result<usize> {
+    Ok(bincode::serialized_size(value)? as usize)
+}
+
+fn votes_size<T: Ord + Serialize>(votes: &BTreeSet<SignedVote<T>>) -> Result<usize> {
+    votes
+        .iter()
+        .try_fold(LEN, |size, vote| Ok(size + vote.estimated_wire_size()?))
+}
+
+impl<T: Ord + Serialize> Ballot<T> {
+    pub fn estimated_wire_size(&self) -> Result<usize> {
+        Ok(VARIANT
+            + match self {
+                Ballot::Propose(proposal) => measured(proposal)?,
+                Ballot::Merge(votes) | Ballot::SuperMajority(votes) => votes_size(votes)?,
+            })
+    }
+}
+
+impl<T: Ord + Serialize> Vote<T> {
+    pub fn estimated_wire_size(&self) -> Result<usize> {
+        let extensions: usize = self
+            .extensions
+            .values()
+            .map(|ext| 2 + LEN + ext.len())
+            .sum();
+        Ok(measured(&self.gen)? + self.ballot.estimated_wire_size()? + LEN + extensions)
+    }
+}
+
+impl<T: Ord + Serialize> SignedVote<T> {
+    pub fn estimated_wire_size(&self) -> Result<usize> {
+        Ok(self.vote.estimated_wire_size()? + measured(&self.voter)? + measured(&self.sig)?)
+    }
+}
+
+impl<T: Ord + Serialize> VoteMsg<T> {
+    /// The length of the message in version 2, to size the buffer or refuse the message
+    /// before encoding it. The compact encoding of nested ballots is shorter.
+    pub fn estimated_wire_size(&self) -> Result<usize> {
+        let correlation_id = TAG + self.correlation_id.map_or(0, |_| LEN);
+        Ok(1 + self.vote.estimated_wire_size()? + measured(&self.dest)? + correlation_id + VARIANT)
+    }
+}
+
+impl<T: Ord + Serialize> Decision<T> {
+    pub fn estimated_wire_size(&self) -> Result<usize> {
+        Ok(measured(&self.gen)? + measured(&self.proposal)? + votes_size(&self.votes)?)
+    }
+}
@@ -0,0 +1,59 @@
+// test-env-log has been renamed to test-log, keep using it until we upgrade
+#![allow(deprecated)]
+
+use rand::{prelude::StdRng, SeedableRng};
+
+mod net;
+use net::DummyProposal;
+
+use test_env_log::test;
+
+use serde::{Deserialize, Serialize};
+use sn_handover::{conformance, v1, ConfigError, Error, Hash, Proposal, Result};
+
+#[test]
+fn test_reference_implementation_conforms() -> eyre::Result<()> {
+    let rng = StdRng::from_seed([0u8; 32]);
+    let samples = Vec::from_iter((0..4).map(DummyProposal));
+    conformance::run_all(&samples, rng)?;
+    Ok(())
+}
+
+#[test]
+fn test_vote_vectors_are_the_bytes_the_first_release_signs() -> eyre::Result<()> {
+    conformance::check_vote_vectors()?;
+
+    // a vote without extensions signs in the layout of the first release
+    let vote = v1::Vote {
+        gen: 7,
+        ballot: v1::Ballot::Propose(DummyProposal(1)),
+    };
+    let (ballot, expected) = conformance::SCHEME_VECTORS.votes[0];
+    assert_eq!(ballot, "propose");
+    assert_eq!(
+        hex::encode(Hash::of(&vote.to_bytes()?).as_bytes()),
+        expected
+    );
+    Ok(())
+}
+
+// a proposal whose encoding forgets part of it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+struct Lossy(u64, #[serde(skip)] u64);
+
+impl Proposal for Lossy {
+    fn validate(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_lossy_proposal_encoding_is_caught() {
+    assert!(matches!(
+        conformance::check_proposal_encoding(&[Lossy(1, 2)]),
+        Err(Error::Config(ConfigError::NonConformant {
+            check: "proposal_encoding",
+            ..
+        }))
+    ));
+}
@@ -0,0 +1,340 @@
+#![cfg(feature = "driver")]
+// test-env-log has been renamed to test-log, keep using it until we upgrade
+#![allow(deprecated, clippy::result_large_err)]
+
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::future::{poll_fn, Future};
+use std::pin::pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+use std::time::{Duration, Instant};
+
+use rand::{prelude::StdRng, SeedableRng};
+
+mod net;
+use net::DummyProposal;
+
+use test_env_log::test;
+
+use sn_handover::driver::{DriverConfig, Handover, Transport};
+use sn_handover::{
+    Error, HandoverState, Priority, PublicKey, Result, StateError, TransportError, VoteMsg,
+};
+
+// Runs a future to completion on the current thread
+fn block_on<F: Future>(future: F) -> F::Output {
+    struct Unpark(Thread);
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark()
+        }
+    }
+
+    let mut future = pin!(future);
+    let waker = Waker::from(Arc::new(Unpark(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+        thread::park();
+    }
+}
+
+#[derive(Default)]
+struct Inbox {
+    msgs: VecDeque<VoteMsg<DummyProposal>>,
+    waker: Option<Waker>,
+}
+
+// Delivers messages between the elders of one process, losing some of the first ones
+#[derive(Clone)]
+struct LossyTransport {
+    us: PublicKey,
+    inboxes: Arc<Mutex<BTreeMap<PublicKey, Inbox>>>,
+    sent: Arc<AtomicUsize>,
+}
+
+impl Transport<DummyProposal> for LossyTransport {
+    fn send(&self, msg: VoteMsg<DummyProposal>) -> impl Future<Output = Result<()>> + Send {
+        let n = self.sent.fetch_add(1, Ordering::SeqCst);
+        let inboxes = self.inboxes.clone();
+        async move {
+            if n < 40 && n % 4 == 3 {
+                return Ok(());
+            }
+            let mut inboxes = inboxes.lock().unwrap();
+            let inbox = inboxes.entry(msg.dest).or_default();
+            inbox.msgs.push_back(msg);
+            if let Some(waker) = inbox.waker.take() {
+                waker.wake();
+            }
+            Ok(())
+        }
+    }
+
+    fn recv(&self) -> impl Future<Output = Option<VoteMsg<DummyProposal>>> + Send {
+        poll_fn(move |cx| {
+            let mut inboxes = self.inboxes.lock().unwrap();
+            let inbox = inboxes.entry(self.us).or_default();
+            match inbox.msgs.pop_front() {
+                Some(msg) => Poll::Ready(Some(msg)),
+                None => {
+                    inbox.waker = Some(cx.waker().clone());
+                    Poll::Pending
+                }
+            }
+        })
+    }
+
+    fn sleep(&self, duration: Duration) -> impl Future<Output = ()> + Send {
+        sleep(duration)
+    }
+}
+
+// Records what we send to peers that never answer, closes once we sent `limit` messages
+#[derive(Clone)]
+struct SilentPeers {
+    sent: Arc<Mutex<Vec<VoteMsg<DummyProposal>>>>,
+    limit: usize,
+}
+
+impl Transport<DummyProposal> for SilentPeers {
+    fn send(&self, msg: VoteMsg<DummyProposal>) -> impl Future<Output = Result<()>> + Send {
+        self.sent.lock().unwrap().push(msg);
+        async { Ok(()) }
+    }
+
+    fn recv(&self) -> impl Future<Output = Option<VoteMsg<DummyProposal>>> + Send {
+        let (sent, limit) = (self.sent.clone(), self.limit);
+        poll_fn(move |_| match sent.lock().unwrap().len() >= limit {
+            true => Poll::Ready(None),
+            false => Poll::Pending,
+        })
+    }
+
+    fn sleep(&self, duration: Duration) -> impl Future<Output = ()> + Send {
+        sleep(duration)
+    }
+}
+
+fn sleep(duration: Duration) -> impl Future<Output = ()> + Send {
+    let deadline = Instant::now() + duration;
+    let mut timer_started = false;
+    poll_fn(move |cx| {
+        if Instant::now() >= deadline {
+            return Poll::Ready(());
+        }
+        if !timer_started {
+            timer_started = true;
+            let waker = cx.waker().clone();
+            thread::spawn(move || {
+                thread::sleep(duration);
+                waker.wake();
+            });
+        }
+        Poll::Pending
+    })
+}
+
+#[test]
+fn test_driver_decides_over_a_lossy_transport() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut procs = Vec::from_iter(
+        (0..4).map(|_| HandoverState::<DummyProposal>::random(&mut rng, Default::default())),
+    );
+    let voters = BTreeSet::from_iter(procs.iter().map(HandoverState::public_key));
+    for proc in procs.iter_mut() {
+        proc.voters = voters.clone();
+    }
+
+    let inboxes = Arc::new(Mutex::new(BTreeMap::new()));
+    let config = DriverConfig {
+        resend_interval: Duration::from_millis(10),
+        anti_entropy_interval: Duration::from_millis(100),
+        ..Default::default()
+    };
+    let nodes = Vec::from_iter(procs.into_iter().enumerate().map(|(i, state)| {
+        let transport = LossyTransport {
+            us: state.public_key(),
+            inboxes: inboxes.clone(),
+            sent: Default::default(),
+        };
+        thread::spawn(move || {
+            block_on(async move {
+                let mut node = Handover::new(state, transport, config);
+                node.propose(DummyProposal(i as u64 % 2)).await?;
+                node.run().await
+            })
+        })
+    }));
+
+    let decisions = nodes
+        .into_iter()
+        .map(|node| node.join().unwrap())
+        .collect::<Result<Vec<_>>>()?;
+    for decision in decisions.iter() {
+        decision.verify(&voters)?;
+        assert_eq!(decision.proposal, decisions[0].proposal);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_driver_adapts_resend_intervals_to_response_times() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([1u8; 32]);
+    let mut procs = Vec::from_iter(
+        (0..4).map(|_| HandoverState::<DummyProposal>::random(&mut rng, Default::default())),
+    );
+    let voters = BTreeSet::from_iter(procs.iter().map(HandoverState::public_key));
+    for proc in procs.iter_mut() {
+        proc.voters = voters.clone();
+    }
+
+    // peers answer in the time it takes them to check our votes, far quicker than our guess
+    let inboxes = Arc::new(Mutex::new(BTreeMap::new()));
+    let config = DriverConfig {
+        resend_interval: Duration::from_secs(2),
+        min_resend_interval: Duration::from_millis(1),
+        max_resend_interval: Duration::from_secs(5),
+        anti_entropy_interval: Duration::from_secs(5),
+    };
+    let nodes = Vec::from_iter(procs.into_iter().enumerate().map(|(i, state)| {
+        // past the messages it loses, resent messages aren't timed
+        let transport = LossyTransport {
+            us: state.public_key(),
+            inboxes: inboxes.clone(),
+            sent: Arc::new(AtomicUsize::new(40)),
+        };
+        let voters = voters.clone();
+        thread::spawn(move || {
+            block_on(async move {
+                let mut node = Handover::new(state, transport, config);
+                node.propose(DummyProposal(i as u64 % 2)).await?;
+                node.run().await?;
+                let us = node.state().public_key();
+                let intervals = voters
+                    .iter()
+                    .filter(|peer| **peer != us)
+                    .map(|peer| node.resend_interval(peer));
+                Ok::<_, sn_handover::Error>(Vec::from_iter(intervals))
+            })
+        })
+    }));
+
+    let intervals = Vec::from_iter(
+        nodes
+            .into_iter()
+            .map(|node| node.join().unwrap())
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .flatten(),
+    );
+    let adapted = Vec::from_iter(intervals.iter().filter(|i| **i != config.resend_interval));
+    assert!(!adapted.is_empty());
+    assert!(adapted
+        .iter()
+        .all(|i| **i >= config.min_resend_interval && **i < config.resend_interval));
+    Ok(())
+}
+
+#[test]
+fn test_driver_runs_rehearsals_to_their_end() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([2u8; 32]);
+    let mut procs = Vec::from_iter(
+        (0..4).map(|_| HandoverState::<DummyProposal>::random(&mut rng, Default::default())),
+    );
+    let voters = BTreeSet::from_iter(procs.iter().map(HandoverState::public_key));
+    for proc in procs.iter_mut() {
+        proc.voters = voters.clone();
+        proc.config.rehearsal = true;
+    }
+
+    let inboxes = Arc::new(Mutex::new(BTreeMap::new()));
+    let config = DriverConfig {
+        resend_interval: Duration::from_millis(10),
+        anti_entropy_interval: Duration::from_millis(100),
+        ..Default::default()
+    };
+    let nodes = Vec::from_iter(procs.into_iter().enumerate().map(|(i, state)| {
+        let transport = LossyTransport {
+            us: state.public_key(),
+            inboxes: inboxes.clone(),
+            sent: Default::default(),
+        };
+        thread::spawn(move || {
+            block_on(async move {
+                let mut node = Handover::new(state, transport, config);
+                assert!(matches!(
+                    node.run().await,
+                    Err(Error::State(StateError::RehearsalIsNonBinding))
+                ));
+                node.propose(DummyProposal(i as u64 % 2)).await?;
+                node.run_rehearsal().await
+            })
+        })
+    }));
+
+    let proofs = nodes
+        .into_iter()
+        .map(|node| node.join().unwrap())
+        .collect::<Result<Vec<_>>>()?;
+    for proof in proofs.iter() {
+        proof.verify(&voters)?;
+        assert_eq!(proof.rehearsed.proposal, proofs[0].rehearsed.proposal);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_driver_numbers_the_anti_entropy_it_sends() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([2u8; 32]);
+    let mut procs = Vec::from_iter(
+        (0..4).map(|_| HandoverState::<DummyProposal>::random(&mut rng, Default::default())),
+    );
+    let voters = BTreeSet::from_iter(procs.iter().map(HandoverState::public_key));
+    let mut state = procs.remove(0);
+    state.voters = voters;
+
+    // our peers never answer, we send them anti-entropy at every tick until the transport closes
+    let transport = SilentPeers {
+        sent: Default::default(),
+        limit: 12,
+    };
+    let config = DriverConfig {
+        resend_interval: Duration::from_millis(5),
+        min_resend_interval: Duration::from_millis(1),
+        anti_entropy_interval: Duration::ZERO,
+        ..Default::default()
+    };
+    let mut node = Handover::new(state, transport.clone(), config);
+    let result = block_on(async {
+        node.propose(DummyProposal(1)).await?;
+        node.run().await
+    });
+    assert!(matches!(
+        result,
+        Err(Error::Transport(TransportError::Closed))
+    ));
+
+    // it's numbered like our votes, and counts in the backpressure of the peers until they answer
+    let sent = transport.sent.lock().unwrap();
+    let anti_entropy = Vec::from_iter(
+        sent.iter()
+            .filter(|msg| msg.priority == Priority::AntiEntropy),
+    );
+    assert!(!anti_entropy.is_empty());
+    assert!(anti_entropy.iter().all(|msg| msg.correlation_id.is_some()));
+    let numbered = BTreeSet::from_iter(
+        sent.iter()
+            .filter_map(|msg| Some((msg.dest, msg.correlation_id?))),
+    );
+    assert!(numbered.len() > 3);
+    assert_eq!(
+        node.state().backpressure().values().sum::<usize>(),
+        numbered.len()
+    );
+    Ok(())
+}
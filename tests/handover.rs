@@ -1,17 +1,31 @@
-use rand::{
-    prelude::{IteratorRandom, StdRng},
-    Rng, SeedableRng,
-};
+// test-env-log has been renamed to test-log, keep using it until we upgrade
+#![allow(deprecated)]
+
+use rand::{prelude::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::time::Duration;
 
 mod net;
 use net::{DummyProposal, Net, Packet};
 
-use std::collections::BTreeSet;
-use quickcheck::TestResult;
-use quickcheck_macros::quickcheck;
 use test_env_log::test;
 
-use sn_handover::{Ballot, Error, HandoverState, Proposal, PublicKey, SecretKey, SignedVote, Vote};
+use sn_handover::conformance::check_signer;
+use sn_handover::properties;
+use sn_handover::sim::{LinkFaults, Violation};
+use sn_handover::v1;
+use sn_handover::wire;
+use sn_handover::{
+    proposal_hash, split_genesis, Ballot, CompactBallot, CompactEntry, CompactVote, Config,
+    ConfigError, ConfigHandshake, Decision, Error, Fault, Generation, GenerationPolicy,
+    HandoverState, HistoryStats, HookAction, Hooks, InMemoryVoteLog, KeyVerifier, Outcome,
+    Participation, Prefix, Priority, Proposal, ProposalSource, ProposalStatus, ProtocolDescriptor,
+    ProtocolError, PublicKey, QuorumPolicy, Relay, RelayedVote, Reveal, RevealOutcome,
+    SealedProposal, SecretKey, Signature, SignedVote, Signer, SigningDomain, Snapshot, Split,
+    SplitPolicy, StateError, StorageError, TransitionReceipt, Verifier, Vote, VoteDigest, VoteLog,
+    VoteMsg, BFT_MINIMUM_ELDERS, MAX_RELAY_HOPS,
+};
 
 #[test]
 fn test_reject_changing_reconfig_when_one_is_in_progress() -> Result<(), Error> {
@@ -21,7 +35,9 @@ fn test_reject_changing_reconfig_when_one_is_in_progress() -> Result<(), Error>
     proc.propose(DummyProposal(rng.gen()))?;
     assert!(matches!(
         proc.propose(DummyProposal(rng.gen())),
-        Err(Error::ExistingVoteIncompatibleWithNewVote { .. })
+        Err(Error::Protocol(
+            ProtocolError::ExistingVoteIncompatibleWithNewVote { .. }
+        ))
     ));
     Ok(())
 }
@@ -75,10 +91,10 @@ fn test_handle_vote_rejects_packet_from_bad_gen() {
 
     assert!(matches!(
         res,
-        Err(Error::VoteWithInvalidGeneration {
+        Err(Error::Protocol(ProtocolError::VoteWithInvalidGeneration {
             vote_gen: 0,
             gen: 1,
-        })
+        }))
     ));
 }
 
@@ -91,17 +107,21 @@ fn test_reject_votes_with_invalid_signatures() -> Result<(), Error> {
     let voter = PublicKey::random(&mut rng);
     let bytes = bincode::serialize(&(&ballot, &gen))?;
     let sig = SecretKey::random(&mut rng).sign(&bytes);
-    let vote = Vote { gen, ballot };
+    let vote = Vote {
+        gen,
+        ballot,
+        extensions: Default::default(),
+    };
     let resp = proc.handle_signed_vote(SignedVote { vote, voter, sig });
 
     #[cfg(feature = "blsttc")]
-    assert!(matches!(resp, Err(Error::Blsttc(_))));
+    assert!(matches!(resp, Err(Error::Protocol(ProtocolError::Blsttc(_)))));
 
     #[cfg(feature = "ed25519")]
-    assert!(matches!(resp, Err(Error::Ed25519(_))));
+    assert!(matches!(resp, Err(Error::Protocol(ProtocolError::Ed25519(_)))));
 
     #[cfg(feature = "bad_crypto")]
-    assert!(matches!(resp, Err(Error::BadCrypto(_))));
+    assert!(matches!(resp, Err(Error::Protocol(ProtocolError::BadCrypto(_)))));
     Ok(())
 }
 
@@ -199,11 +219,15 @@ fn test_round_robin_split_vote() -> eyre::Result<()> {
         net.drain_queued_packets()?;
 
         // generate msc file
-        net.generate_msc(&format!("round_robin_split_vote_{}.msc", nprocs))?;
+        let out = std::env::temp_dir();
+        net.generate_msc(out.join(format!("round_robin_split_vote_{}.msc", nprocs)))?;
+        net.generate_html(out.join(format!("round_robin_split_vote_{}.html", nprocs)))?;
 
         // make sure they all reach the same conclusion
-        let max_proposed_value = nprocs-1;
-        let expected_consensus_value = Some(DummyProposal(max_proposed_value as u64));
+        // ties are broken by picking the proposal with the greatest rank
+        let expected_consensus_value = (0..nprocs as u64)
+            .map(DummyProposal)
+            .max_by_key(|p| net.procs[0].tie_break_rank(p).unwrap());
         for i in 0..nprocs {
             println!("[TEST] checking voter {}'s consensus value: {:?}", i, net.procs[i].consensus);
             assert_eq!(net.procs[i].consensus, expected_consensus_value);
@@ -235,7 +259,9 @@ fn test_simple_proposal() {
     net.enqueue_packets(packets);
     net.drain_queued_packets().unwrap();
 
-    net.generate_msc("simple_join.msc").unwrap();
+    let out = std::env::temp_dir();
+    net.generate_msc(out.join("simple_join.msc")).unwrap();
+    net.generate_html(out.join("simple_join.html")).unwrap();
 
     // make sure they all reach the same conclusion
     let first_voters_value = net.procs[0].consensus;
@@ -245,6 +271,3376 @@ fn test_simple_proposal() {
     }
 }
 
+#[test]
+fn test_absorb_vote_summary_after_netsplit() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut net = Net::with_procs(4, &mut rng);
+    for i in 0..4 {
+        let a_i = net.procs[i].public_key();
+        for j in 0..4 {
+            let a_j = net.procs[j].public_key();
+            net.force_join(a_i, a_j);
+        }
+    }
+
+    // the last voter is cut off from the rest of the network
+    let isolated = net.procs[3].public_key();
+    let proc_0 = net.procs[0].public_key();
+    let packets = net.procs[0]
+        .propose(DummyProposal(3))?
+        .into_iter()
+        .map(|vote_msg| Packet {
+            source: proc_0,
+            vote_msg,
+        });
+    net.enqueue_packets(packets);
+    while let Some(source) = net.packets.keys().next().cloned() {
+        let dest = net.packets[&source].front().map(|p| p.vote_msg.dest);
+        if dest == Some(isolated) {
+            net.drop_packet_from_source(source);
+            net.purge_empty_queues();
+        } else {
+            net.deliver_packet_from_source(source)?;
+        }
+    }
+    assert_eq!(net.procs[0].consensus, Some(DummyProposal(3)));
+    assert_eq!(net.procs[3].consensus, None);
+
+    // the decision was reached without the isolated voter
+    let report = net.procs[0].quorum_report().unwrap();
+    assert_eq!(report.decision, DummyProposal(3));
+    assert_eq!(report.quorum.len(), 3);
+    assert!(report.outside_quorum.is_empty());
+    assert_eq!(report.absent, BTreeSet::from_iter([isolated]));
+
+    // once the split heals, one summary is enough to catch up
+    let summary = net.procs[0].vote_summary();
+    let resp = net.procs[3].absorb(summary.clone())?;
+    assert!(resp.msgs.is_empty());
+    assert_eq!(net.procs[3].consensus, Some(DummyProposal(3)));
+
+    // absorbing the same summary again is a no-op
+    assert_eq!(net.procs[3].absorb(summary)?, Outcome::default());
+    Ok(())
+}
+
+#[derive(Debug)]
+struct EpochFromProposal;
+
+impl GenerationPolicy<DummyProposal> for EpochFromProposal {
+    fn next_gen(&self, _gen: Generation, decision: &DummyProposal) -> Generation {
+        decision.0
+    }
+}
+
+// An elder whose secret key also lives in its own keystore, to restore the elder with
+fn elder_with_keystore(rng: &mut StdRng) -> eyre::Result<(HandoverState<DummyProposal>, Vec<u8>)> {
+    let secret_key = SecretKey::random(rng);
+    let keystore = bincode::serialize(&secret_key)?;
+    let elder = HandoverState::from(secret_key, 0, Default::default());
+    Ok((elder, keystore))
+}
+
+fn decide_alone(net: &mut Net, proposal: DummyProposal) -> eyre::Result<()> {
+    let a_0 = net.procs[0].public_key();
+    let packets = net.procs[0]
+        .propose(proposal)?
+        .into_iter()
+        .map(|vote_msg| Packet {
+            source: a_0,
+            vote_msg,
+        });
+    net.enqueue_packets(packets);
+    net.drain_queued_packets()?;
+    assert_eq!(net.procs[0].consensus, Some(proposal));
+    Ok(())
+}
+
+#[test]
+fn test_generation_policy() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut net = Net::with_procs(1, &mut rng);
+    let a_0 = net.procs[0].public_key();
+    net.force_join(a_0, a_0);
+    let voters = net.procs[0].voters.clone();
+
+    // can't move on before deciding
+    assert!(matches!(
+        net.procs[0].advance(voters.clone()),
+        Err(Error::State(StateError::NoDecision(0)))
+    ));
+
+    // by default we move to the next generation
+    decide_alone(&mut net, DummyProposal(7))?;
+    assert_eq!(net.procs[0].next_gen(), Some(1));
+    assert_eq!(net.procs[0].advance(voters.clone())?, 1);
+    assert_eq!(net.procs[0].gen, 1);
+    assert_eq!(net.procs[0].consensus, None);
+    assert!(net.procs[0].votes.is_empty());
+
+    // the application can jump to an epoch carried in the proposal
+    net.procs[0].set_generation_policy(EpochFromProposal);
+    decide_alone(&mut net, DummyProposal(42))?;
+    assert_eq!(net.procs[0].advance(voters.clone())?, 42);
+
+    // but never go back in time
+    decide_alone(&mut net, DummyProposal(3))?;
+    assert!(matches!(
+        net.procs[0].advance(voters),
+        Err(Error::Protocol(ProtocolError::InvalidGeneration(3)))
+    ));
+    Ok(())
+}
+
+// A proposal whose Eq and Ord consider every value to be the same
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+struct AlwaysEqual(u64);
+
+impl PartialEq for AlwaysEqual {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for AlwaysEqual {}
+
+impl PartialOrd for AlwaysEqual {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for AlwaysEqual {
+    fn cmp(&self, _: &Self) -> std::cmp::Ordering {
+        std::cmp::Ordering::Equal
+    }
+}
+
+impl Proposal for AlwaysEqual {
+    fn validate(&self) -> sn_handover::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_consensus_does_not_rely_on_proposal_ord() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let nprocs = 4;
+    let mut procs = Vec::from_iter(
+        (0..nprocs).map(|_| HandoverState::<AlwaysEqual>::random(&mut rng, Default::default())),
+    );
+    let voters = BTreeSet::from_iter(procs.iter().map(HandoverState::public_key));
+    for proc in procs.iter_mut() {
+        proc.voters = voters.clone();
+    }
+
+    let mut msgs = VecDeque::new();
+    for (i, proc) in procs.iter_mut().enumerate() {
+        msgs.extend(proc.propose(AlwaysEqual(i as u64))?);
+    }
+    for _ in 0..nprocs {
+        while let Some(msg) = msgs.pop_front() {
+            let dest = procs
+                .iter_mut()
+                .find(|p| p.public_key() == msg.dest)
+                .unwrap();
+            msgs.extend(dest.handle_signed_vote(msg.vote)?.msgs);
+        }
+        if procs.iter().all(|p| p.consensus.is_some()) {
+            break;
+        }
+        for i in 0..nprocs {
+            for j in 0..nprocs {
+                msgs.extend(procs[j].anti_entropy(procs[i].public_key()));
+            }
+        }
+    }
+
+    let expected = (0..nprocs as u64)
+        .map(AlwaysEqual)
+        .max_by_key(|p| procs[0].tie_break_rank(p).unwrap())
+        .map(|p| p.0);
+    for proc in procs.iter() {
+        assert_eq!(proc.consensus.map(|p| p.0), expected);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_votes_are_told_apart_by_proposal_hash() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let proc = HandoverState::<AlwaysEqual>::random(&mut rng, Default::default());
+    let first = proc.sign_vote(Vote {
+        gen: 0,
+        ballot: Ballot::Propose(AlwaysEqual(0)),
+        extensions: Default::default(),
+    })?;
+    let second = proc.sign_vote(Vote {
+        gen: 0,
+        ballot: Ballot::Propose(AlwaysEqual(1)),
+        extensions: Default::default(),
+    })?;
+
+    assert_ne!(first, second);
+    assert!(!first.supersedes(&second) && !second.supersedes(&first));
+    assert_eq!(BTreeSet::from_iter([first.clone(), second.clone()]).len(), 2);
+
+    let merge = proc.sign_vote(Vote {
+        gen: 0,
+        ballot: Ballot::Merge(BTreeSet::from_iter([first.clone()])),
+        extensions: Default::default(),
+    })?;
+    assert!(merge.supersedes(&first));
+    assert!(!merge.supersedes(&second));
+    Ok(())
+}
+
+#[test]
+fn test_responses_echo_correlation_id() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut net = Net::with_procs(3, &mut rng);
+    let a_0 = net.procs[0].public_key();
+    let a_1 = net.procs[1].public_key();
+    let a_2 = net.procs[2].public_key();
+    for p in [a_0, a_1, a_2] {
+        net.force_join(a_0, p);
+        net.force_join(a_1, p);
+        net.force_join(a_2, p);
+    }
+
+    let msg = net.procs[0]
+        .propose(DummyProposal(1))?
+        .into_iter()
+        .find(|msg| msg.dest == a_1)
+        .unwrap()
+        .with_correlation_id(7);
+
+    assert!(matches!(
+        net.procs[0].handle_vote_msg(msg.clone()),
+        Err(Error::Protocol(ProtocolError::WrongDestination { .. }))
+    ));
+
+    // only the reply to the voter echoes its id, our other messages are numbered by us
+    let resp = net.procs[1].handle_vote_msg(msg)?.msgs;
+    let (replies, mut others): (Vec<_>, Vec<_>) =
+        resp.into_iter().partition(|msg| msg.dest == a_0);
+    others.retain(|msg| msg.dest != a_1);
+    assert!(!replies.is_empty() && !others.is_empty());
+    assert!(replies.iter().all(|msg| msg.correlation_id == Some(7)));
+    let ids = BTreeSet::from_iter(others.iter().map(|msg| msg.correlation_id));
+    assert_eq!(ids.len(), others.len());
+    assert!(!ids.contains(&None) && !ids.contains(&Some(7)));
+
+    let resp = net.procs[1].anti_entropy_reply(a_0, Some(8));
+    assert!(!resp.is_empty());
+    assert!(resp.iter().all(|msg| msg.correlation_id == Some(8)));
+    assert!(net.procs[1]
+        .anti_entropy(a_0)
+        .iter()
+        .all(|msg| msg.correlation_id.is_none()));
+    Ok(())
+}
+
+#[test]
+fn test_startup_grace_period_only_collects_votes() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut net = Net::with_procs(3, &mut rng);
+    let a_0 = net.procs[0].public_key();
+    let a_1 = net.procs[1].public_key();
+    let a_2 = net.procs[2].public_key();
+    for p in [a_0, a_1, a_2] {
+        net.force_join(a_0, p);
+        net.force_join(a_1, p);
+        net.force_join(a_2, p);
+    }
+
+    // the second voter just restarted
+    net.procs[1].set_config(Config {
+        startup_grace_period: Duration::from_secs(3600),
+        ..Default::default()
+    });
+    assert!(net.procs[1].in_grace_period());
+    assert!(matches!(
+        net.procs[1].propose(DummyProposal(2)),
+        Err(Error::State(StateError::InGracePeriod))
+    ));
+
+    let vote = net.procs[0].propose(DummyProposal(1))?[0].vote.clone();
+    assert!(net.procs[1].handle_signed_vote(vote.clone())?.msgs.is_empty());
+    assert!(net.procs[1].votes.contains_key(&a_0));
+    assert_eq!(net.procs[1].anti_entropy(a_0).len(), 1);
+
+    // once the grace period is over, we take part again
+    net.procs[1].set_config(Config::default());
+    assert!(!net.procs[1].in_grace_period());
+    assert!(!net.procs[1].handle_signed_vote(vote)?.msgs.is_empty());
+    assert!(net.procs[1].votes.contains_key(&a_1));
+    Ok(())
+}
+
+#[test]
+fn test_round_seed_is_shared_by_all_voters() {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut net = Net::with_procs(3, &mut rng);
+    let voters = BTreeSet::from_iter(net.procs.iter().map(HandoverState::public_key));
+    for proc in net.procs.iter_mut() {
+        proc.voters = voters.clone();
+    }
+
+    let seed = net.procs[0].round_seed(0).unwrap();
+    for proc in net.procs.iter() {
+        assert_eq!(proc.round_seed(0).unwrap(), seed);
+        assert_eq!(
+            proc.tie_break_rank(&DummyProposal(1)).unwrap(),
+            net.procs[0].tie_break_rank(&DummyProposal(1)).unwrap()
+        );
+    }
+    assert_ne!(net.procs[0].round_seed(1).unwrap(), seed);
+
+    // a different set of voters breaks ties differently
+    net.procs[0].voters.remove(&voters.iter().next().copied().unwrap());
+    assert_ne!(net.procs[0].round_seed(0).unwrap(), seed);
+}
+
+#[derive(Debug)]
+struct ProposeGen;
+
+impl ProposalSource<DummyProposal> for ProposeGen {
+    fn next_proposal(&self, gen: Generation) -> Option<DummyProposal> {
+        Some(DummyProposal(gen))
+    }
+}
+
+#[test]
+fn test_proposer_polls_its_proposal_source() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut net = Net::with_procs(4, &mut rng);
+    for i in 0..net.procs.len() {
+        let a_i = net.procs[i].public_key();
+        for j in 0..net.procs.len() {
+            net.force_join(a_i, net.procs[j].public_key());
+        }
+        net.procs[i].set_proposal_source(ProposeGen);
+    }
+
+    // everyone agrees on whose turn it is
+    let proposer = net.procs[0].proposer(0).unwrap();
+    assert!(net.procs.iter().all(|p| p.proposer(0) == Some(proposer)));
+
+    // only the proposer originates the round
+    for i in 0..net.procs.len() {
+        let source = net.procs[i].public_key();
+        let vote_msgs = net.procs[i].poll_proposal_source()?;
+        assert_eq!(vote_msgs.is_empty(), source != proposer);
+        net.enqueue_packets(
+            vote_msgs
+                .into_iter()
+                .map(|vote_msg| Packet { source, vote_msg }),
+        );
+    }
+    net.drain_queued_packets()?;
+
+    assert!(net
+        .procs
+        .iter()
+        .all(|p| p.consensus == Some(DummyProposal(0))));
+
+    // once we have voted there is nothing left to poll
+    let proposer_idx = net.procs.iter().position(|p| p.public_key() == proposer);
+    assert!(net.procs[proposer_idx.unwrap()]
+        .poll_proposal_source()?
+        .is_empty());
+
+    // and the turn moves on with the generation
+    assert_ne!(net.procs[0].proposer(1), Some(proposer));
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+struct Secret(u64);
+
+impl Proposal for Secret {
+    fn validate(&self) -> sn_handover::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_redacted_votes_hide_proposals() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut proc = HandoverState::random(&mut rng, Default::default());
+    proc.force_join(proc.public_key());
+    let vote_msgs = proc.propose(Secret(0xdeadbeef))?;
+    let signed_vote = &vote_msgs[0].vote;
+
+    let logged = format!("{:?}", signed_vote.redacted());
+    assert!(!logged.contains("Secret"));
+    assert!(!logged.contains(&0xdeadbeefu64.to_string()));
+    let hash = sn_handover::proposal_hash(&Secret(0xdeadbeef))?;
+    assert!(logged.contains(&format!("{:?}", hash)));
+    assert!(format!("{:?}", signed_vote).contains("Secret"));
+    Ok(())
+}
+
+#[test]
+fn test_threaded_delivery_reaches_consensus() -> eyre::Result<()> {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<HandoverState<DummyProposal>>();
+
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    for nprocs in 1..5 {
+        let mut net = Net::with_procs(nprocs, &mut rng);
+        for i in 0..nprocs {
+            let i_actor = net.procs[i].public_key();
+            for j in 0..nprocs {
+                net.procs[j].force_join(i_actor);
+            }
+        }
+
+        // every voter proposes something different, and they all race to process the votes
+        for i in 0..nprocs {
+            let a_i = net.procs[i].public_key();
+            let packets = net.procs[i]
+                .propose(DummyProposal(i as u64))?
+                .into_iter()
+                .map(|vote_msg| Packet {
+                    source: a_i,
+                    vote_msg,
+                });
+            net.enqueue_packets(packets);
+        }
+        net.drain_queued_packets_threaded()?;
+
+        // the concurrent run may leave stragglers, anti-entropy brings them up to date
+        for i in 0..nprocs {
+            for j in 0..nprocs {
+                net.enqueue_anti_entropy(i, j);
+            }
+        }
+        net.drain_queued_packets_threaded()?;
+
+        let decision = net.procs[0].consensus;
+        assert!(decision.is_some());
+        assert!(net.procs.iter().all(|p| p.consensus == decision));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_protocol_descriptor_compatibility() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let a = HandoverState::<DummyProposal>::random(&mut rng, Default::default());
+    let b = HandoverState::<DummyProposal>::random(&mut rng, Default::default());
+
+    // descriptors go over the wire when connecting
+    let theirs: ProtocolDescriptor =
+        bincode::deserialize(&bincode::serialize(&b.protocol_descriptor())?)?;
+    a.protocol_descriptor().check_compatible(&theirs)?;
+
+    let mut theirs = theirs;
+    theirs.version += 1;
+    assert!(matches!(
+        a.protocol_descriptor().check_compatible(&theirs),
+        Err(Error::Config(ConfigError::IncompatibleProtocol {
+            field: "version",
+            ..
+        }))
+    ));
+
+    let mut theirs = b.protocol_descriptor();
+    theirs.quorum_rule.denominator = 2;
+    assert!(matches!(
+        a.protocol_descriptor().check_compatible(&theirs),
+        Err(Error::Config(ConfigError::IncompatibleProtocol {
+            field: "quorum_rule",
+            ..
+        }))
+    ));
+
+    // so do the limits we enforce
+    let mut config = Config::default();
+    assert_eq!(config.protocol_descriptor(), ProtocolDescriptor::default());
+    config.catch_up_page_size = Some(16);
+    assert_eq!(
+        config.protocol_descriptor().limits.catch_up_page_size,
+        Some(16)
+    );
+    assert!(matches!(
+        a.protocol_descriptor()
+            .check_compatible(&config.protocol_descriptor()),
+        Err(Error::Config(ConfigError::IncompatibleProtocol {
+            field: "limits.catch_up_page_size",
+            ..
+        }))
+    ));
+    let mut theirs = b.protocol_descriptor();
+    theirs.limits.max_ballot_depth += 1;
+    assert!(matches!(
+        a.protocol_descriptor().check_compatible(&theirs),
+        Err(Error::Config(ConfigError::IncompatibleProtocol {
+            field: "limits.max_ballot_depth",
+            ..
+        }))
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_lagging_elder_accepts_announced_decision() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut net = Net::with_procs(4, &mut rng);
+    for i in 0..4 {
+        let a_i = net.procs[i].public_key();
+        for j in 0..4 {
+            net.procs[j].force_join(a_i);
+        }
+    }
+
+    // the last elder is cut off, the others decide without it
+    let mut lagging = net.procs.pop().unwrap();
+    let a_0 = net.procs[0].public_key();
+    assert_eq!(net.procs[0].announce_decision(lagging.public_key())?, None);
+    let packets = net.procs[0]
+        .propose(DummyProposal(5))?
+        .into_iter()
+        .map(|vote_msg| Packet {
+            source: a_0,
+            vote_msg,
+        });
+    net.enqueue_packets(packets);
+    net.drain_queued_packets()?;
+    assert_eq!(net.procs[0].consensus, Some(DummyProposal(5)));
+    assert_eq!(lagging.consensus, None);
+
+    let announce = net.procs[0]
+        .announce_decision(lagging.public_key())?
+        .unwrap();
+
+    // a proof short of a super majority is refused
+    let mut forged = announce.clone();
+    let first_vote = forged.decision.votes.iter().next().cloned().unwrap();
+    forged.decision.votes.remove(&first_vote);
+    assert!(matches!(
+        lagging.handle_decision_announce(forged),
+        Err(Error::Protocol(ProtocolError::InvalidDecision(_)))
+    ));
+    assert_eq!(lagging.consensus, None);
+
+    // so is a proof for another proposal
+    let mut forged = announce.clone();
+    forged.decision.proposal = DummyProposal(6);
+    assert!(matches!(
+        lagging.handle_decision_announce(forged),
+        Err(Error::Protocol(ProtocolError::InvalidDecision(_)))
+    ));
+
+    lagging.handle_decision_announce(announce)?;
+    assert_eq!(lagging.consensus, Some(DummyProposal(5)));
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+struct AtMostTen(u64);
+
+impl Proposal for AtMostTen {
+    fn validate(&self) -> sn_handover::Result<()> {
+        match self.0 {
+            0..=10 => Ok(()),
+            _ => Err(ProtocolError::InvalidVoteInHistory(format!("{:?}", self)).into()),
+        }
+    }
+}
+
+#[test]
+fn test_invalid_proposal_signed_by_elder_is_a_fault() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut elder = HandoverState::<AtMostTen>::random(&mut rng, Default::default());
+    let outsider = HandoverState::<AtMostTen>::random(&mut rng, Default::default());
+    let mut proc = HandoverState::<AtMostTen>::random(&mut rng, Default::default());
+    let elders = [elder.public_key(), proc.public_key()];
+    for p in [&mut elder, &mut proc] {
+        elders.into_iter().for_each(|e| p.force_join(e));
+    }
+
+    let invalid_ballot = Vote {
+        gen: proc.gen,
+        ballot: Ballot::Propose(AtMostTen(11)),
+        extensions: Default::default(),
+    };
+
+    // an outsider's garbage is only an error
+    let garbage = outsider.sign_vote(invalid_ballot.clone())?;
+    assert!(proc.handle_signed_vote(garbage).is_err());
+    assert!(proc.faults.is_empty());
+
+    // an elder signing it is evidence against them
+    let signed_invalid = elder.sign_vote(invalid_ballot)?;
+    let fault = Fault::InvalidProposal {
+        vote: signed_invalid.clone(),
+    };
+    let outcome = proc.handle_signed_vote(signed_invalid.clone())?;
+    assert_eq!(outcome.faults, vec![fault.clone()]);
+    assert_eq!(Vec::from_iter(proc.faults().iter().cloned()), vec![fault]);
+    assert!(proc.votes.is_empty());
+
+    // we already hold that evidence, the vote is only refused
+    assert!(proc.handle_signed_vote(signed_invalid).is_err());
+    assert_eq!(proc.faults().len(), 1);
+    Ok(())
+}
+
+#[test]
+fn test_generation_deadline_refuses_fresh_proposals() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut net = Net::with_procs(2, &mut rng);
+    let (a_0, a_1) = (net.procs[0].public_key(), net.procs[1].public_key());
+    for p in net.procs.iter_mut() {
+        p.force_join(a_0);
+        p.force_join(a_1);
+    }
+
+    let closed_round = Config {
+        generation_deadline: Some(Duration::ZERO),
+        ..Default::default()
+    };
+    net.procs[1].set_config(Config {
+        clock_skew_tolerance: Duration::from_secs(3600),
+        ..closed_round.clone()
+    });
+    std::thread::sleep(Duration::from_millis(1));
+    assert!(!net.procs[1].deadline_passed());
+
+    net.procs[1].set_config(closed_round);
+    assert!(net.procs[1].deadline_passed());
+    assert!(matches!(
+        net.procs[1].propose(DummyProposal(1)),
+        Err(Error::Protocol(ProtocolError::GenerationDeadlinePassed(0)))
+    ));
+
+    // a first proposal we never heard of is refused
+    let vote_msgs = net.procs[0].propose(DummyProposal(0))?;
+    let to_a_1 = vote_msgs.into_iter().find(|m| m.dest == a_1).unwrap();
+    assert!(matches!(
+        net.procs[1].handle_vote_msg(to_a_1.clone()),
+        Err(Error::Protocol(ProtocolError::GenerationDeadlinePassed(0)))
+    ));
+    assert!(net.procs[1].votes.is_empty());
+
+    // so is one smuggled in a later ballot
+    let merge = net.procs[0].sign_vote(Vote {
+        gen: 0,
+        ballot: Ballot::Merge(BTreeSet::from_iter([to_a_1.vote])),
+        extensions: Default::default(),
+    })?;
+    assert!(matches!(
+        net.procs[1].handle_signed_vote(merge),
+        Err(Error::Protocol(ProtocolError::GenerationDeadlinePassed(0)))
+    ));
+    assert!(net.procs[1].votes.is_empty());
+    Ok(())
+}
+
+// Streams the signatures of a live vote, as a node with the default verifier checks them
+fn verify_vote_bytes(bytes: &[u8]) -> eyre::Result<()> {
+    use sn_handover::stream::verify_signed_vote_bytes;
+    Ok(verify_signed_vote_bytes::<DummyProposal>(
+        bytes,
+        &KeyVerifier,
+        SigningDomain::Live,
+    )?)
+}
+
+#[test]
+fn test_streaming_verification_of_nested_votes() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut net = Net::with_procs(4, &mut rng);
+    for i in 0..4 {
+        let a_i = net.procs[i].public_key();
+        for j in 0..4 {
+            net.procs[j].force_join(a_i);
+        }
+    }
+    for i in 0..4 {
+        let a_i = net.procs[i].public_key();
+        let packets = net.procs[i]
+            .propose(DummyProposal(i as u64))?
+            .into_iter()
+            .map(|vote_msg| Packet {
+                source: a_i,
+                vote_msg,
+            });
+        net.enqueue_packets(packets);
+    }
+    net.drain_queued_packets()?;
+
+    let super_majority_vote = net.procs[0]
+        .votes
+        .values()
+        .find(|v| v.vote.is_super_majority_ballot())
+        .cloned()
+        .unwrap();
+    let bytes = bincode::serialize(&super_majority_vote)?;
+    verify_vote_bytes(&bytes)?;
+
+    // any tampering shows, be it with the outer signature or deep inside the ballot
+    for i in [bytes.len() - 1, bytes.len() / 2, 12] {
+        let mut tampered = bytes.clone();
+        tampered[i] ^= 1;
+        assert!(verify_vote_bytes(&tampered).is_err());
+    }
+
+    // and so do trailing bytes
+    let mut padded = bytes;
+    padded.push(0);
+    assert!(verify_vote_bytes(&padded).is_err());
+
+    // a merge signed by a first release node, its nested votes sorted as that release sorted them
+    let keys = Vec::from_iter((0..4).map(|_| SecretKey::random(&mut rng)));
+    let v1_vote = |key: &SecretKey, ballot| -> eyre::Result<v1::SignedVote<DummyProposal>> {
+        let vote = v1::Vote { gen: 0, ballot };
+        let sig = key.sign(&vote.to_bytes()?);
+        Ok(v1::SignedVote {
+            vote,
+            voter: key.public_key(),
+            sig,
+        })
+    };
+    let mut proposals = BTreeSet::new();
+    for (i, key) in keys.iter().enumerate() {
+        proposals.insert(v1_vote(key, v1::Ballot::Propose(DummyProposal(i as u64)))?);
+    }
+    let merge = v1_vote(&keys[0], v1::Ballot::Merge(proposals))?;
+    verify_vote_bytes(&bincode::serialize(&SignedVote::from(merge))?)?;
+    Ok(())
+}
+
+#[test]
+fn test_messages_are_verified_as_they_are_read() -> eyre::Result<()> {
+    // refuses every signature, to show which verifier checks the streamed votes
+    #[derive(Debug)]
+    struct RefuseAll;
+    impl Verifier for RefuseAll {
+        type PublicKey = PublicKey;
+        type Signature = Signature;
+
+        fn verify(&self, voter: &PublicKey, _: &[u8], _: &Signature) -> sn_handover::Result<()> {
+            Err(ProtocolError::NonMember {
+                public_key: *voter,
+                members: Default::default(),
+            }
+            .into())
+        }
+    }
+
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut procs = Vec::from_iter(
+        (0..4).map(|_| HandoverState::<DummyProposal>::random(&mut rng, Default::default())),
+    );
+    let voters = BTreeSet::from_iter(procs.iter().map(HandoverState::public_key));
+    for proc in procs.iter_mut() {
+        proc.voters = voters.clone();
+    }
+    let msg = procs[0]
+        .propose(DummyProposal(2))?
+        .into_iter()
+        .find(|msg| msg.dest == procs[1].public_key())
+        .unwrap();
+    let bytes = msg.to_bytes_in(wire::WIRE_V2)?;
+
+    // votes the first release could express are signed as it signed them
+    assert_eq!(
+        msg.vote.vote.to_bytes()?,
+        bincode::serialize(&(&msg.vote.vote.ballot, msg.vote.vote.gen))?
+    );
+
+    // the node's own verifier and domain check the votes
+    procs[1].verifier = Box::new(RefuseAll);
+    assert!(matches!(
+        procs[1].handle_vote_msg_bytes(bytes.as_slice()),
+        Err(Error::Protocol(ProtocolError::NonMember { .. }))
+    ));
+    assert!(procs[1]
+        .handle_vote_msg_bytes(msg.to_bytes()?.as_slice())
+        .is_err());
+    procs[1].verifier = Box::new(KeyVerifier);
+    procs[1].config.rehearsal = true;
+    assert!(procs[1].handle_vote_msg_bytes(bytes.as_slice()).is_err());
+    procs[1].config.rehearsal = false;
+
+    // we don't read past the limit, what's cut off is malformed, as is any garbage
+    procs[1].config.max_message_size = Some(bytes.len() as u64 - 1);
+    assert!(matches!(
+        procs[1].handle_vote_msg_bytes(bytes.as_slice()),
+        Err(Error::Protocol(ProtocolError::Malformed(_)))
+    ));
+    assert!(matches!(
+        procs[1].handle_vote_msg_bytes([2, 0, 0, 0].as_slice()),
+        Err(Error::Protocol(ProtocolError::Malformed(_)))
+    ));
+    procs[1].config.max_message_size = Some(bytes.len() as u64);
+    assert!(!procs[1]
+        .handle_vote_msg_bytes(bytes.as_slice())?
+        .msgs
+        .is_empty());
+    assert_eq!(procs[1].votes[&procs[0].public_key()], msg.vote);
+    Ok(())
+}
+
+#[test]
+fn test_round_termination_yields_verifiable_decision() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut net = Net::with_procs(4, &mut rng);
+    for i in 0..4 {
+        let a_i = net.procs[i].public_key();
+        for j in 0..4 {
+            net.procs[j].force_join(a_i);
+        }
+    }
+    let voters = net.procs[0].voters.clone();
+
+    let mut msgs = VecDeque::from(net.procs[0].propose(DummyProposal(3))?);
+    let mut decisions = Vec::new();
+    while let Some(msg) = msgs.pop_front() {
+        let dest = net.procs.iter_mut().find(|p| p.public_key() == msg.dest);
+        let outcome = dest.unwrap().handle_vote_msg(msg)?;
+        msgs.extend(outcome.msgs);
+        decisions.extend(outcome.decision);
+    }
+
+    // every elder terminated once, with the same proof worthy decision
+    assert_eq!(decisions.len(), 4);
+    for decision in decisions {
+        assert_eq!(decision.gen, 0);
+        assert_eq!(decision.proposal, DummyProposal(3));
+
+        // anyone knowing the elders can check it
+        decision.verify(&voters)?;
+
+        let mut other_voters = voters.clone();
+        other_voters.insert(SecretKey::random(&mut rng).public_key());
+        assert!(decision.verify(&other_voters).is_err());
+
+        let mut forged = decision.clone();
+        forged.proposal = DummyProposal(4);
+        assert!(matches!(
+            forged.verify(&voters),
+            Err(Error::Protocol(ProtocolError::InvalidDecision(_)))
+        ));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_decisions_backed_by_super_majorities_with_minority_proposals_verify() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut procs = Vec::from_iter(
+        (0..4).map(|_| HandoverState::<DummyProposal>::random(&mut rng, Default::default())),
+    );
+    let voters = BTreeSet::from_iter(procs.iter().map(HandoverState::public_key));
+    for proc in procs.iter_mut() {
+        proc.voters = voters.clone();
+    }
+
+    // the first elder sees every proposal before voting, its super majority ballot
+    // carries the minority proposal along with the three votes backing the winner
+    let mut msgs = VecDeque::new();
+    for (i, proc) in procs.iter_mut().enumerate().skip(1) {
+        msgs.extend(proc.propose(DummyProposal((i / 3) as u64))?);
+    }
+    let first = procs[0].public_key();
+    let (to_first, others): (VecDeque<_>, VecDeque<_>) =
+        msgs.into_iter().partition(|m| m.dest == first);
+    let mut msgs = others;
+    for msg in to_first {
+        msgs.extend(procs[0].handle_vote_msg(msg)?.msgs);
+    }
+    msgs.extend(procs[0].propose(DummyProposal(0)).unwrap_or_default());
+    while let Some(msg) = msgs.pop_front() {
+        let dest = procs.iter_mut().find(|p| p.public_key() == msg.dest);
+        msgs.extend(dest.unwrap().handle_vote_msg(msg)?.msgs);
+    }
+
+    for proc in procs.iter() {
+        let decision = proc.decision()?.unwrap();
+        assert_eq!(Some(decision.proposal), procs[0].consensus);
+        decision.verify(&voters)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn test_small_elder_sets_decide_by_unanimity() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    for nprocs in 1..BFT_MINIMUM_ELDERS {
+        let mut net = Net::with_procs(nprocs, &mut rng);
+        for i in 0..nprocs {
+            let a_i = net.procs[i].public_key();
+            for j in 0..nprocs {
+                net.procs[j].force_join(a_i);
+            }
+            net.procs[i].set_config(Config {
+                unanimity_below_bft_minimum: true,
+                ..Default::default()
+            });
+        }
+
+        for i in 0..nprocs {
+            let a_i = net.procs[i].public_key();
+            // our own votes are not delivered back to us, peers' votes alone must decide
+            let packets = net.procs[i]
+                .propose(DummyProposal(1))?
+                .into_iter()
+                .filter(|vote_msg| vote_msg.dest != a_i)
+                .map(|vote_msg| Packet {
+                    source: a_i,
+                    vote_msg,
+                });
+            net.enqueue_packets(packets);
+        }
+        net.drain_queued_packets()?;
+
+        // nobody needed to go through super majority votes
+        let voters = net.procs[0].voters.clone();
+        for proc in net.procs.iter() {
+            assert_eq!(proc.consensus, Some(DummyProposal(1)));
+            assert!(proc.votes.values().all(|v| !v.vote.is_super_majority_ballot()));
+            let decision = proc.decision()?.unwrap();
+            decision.verify_for(&KeyVerifier, &proc.config, &voters)?;
+            // unanimity only decides for those who agreed to it
+            assert!(decision.verify(&voters).is_err());
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_conflicting_votes_are_a_fault() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut net = Net::with_procs(3, &mut rng);
+    for i in 0..3 {
+        let a_i = net.procs[i].public_key();
+        for j in 0..3 {
+            net.procs[j].force_join(a_i);
+        }
+    }
+    let a_0 = net.procs[0].public_key();
+
+    // the first elder equivocates, voting for two proposals
+    let first = net.procs[0].sign_vote(Vote {
+        gen: 0,
+        ballot: Ballot::Propose(DummyProposal(0)),
+        extensions: Default::default(),
+    })?;
+    let second = net.procs[0].sign_vote(Vote {
+        gen: 0,
+        ballot: Ballot::Propose(DummyProposal(1)),
+        extensions: Default::default(),
+    })?;
+
+    assert!(net.procs[1].handle_signed_vote(first.clone())?.faults.is_empty());
+    let outcome = net.procs[1].handle_signed_vote(second.clone())?;
+    let fault = Fault::ConflictingVotes {
+        first,
+        second: second.clone(),
+    };
+    assert_eq!(outcome.faults, vec![fault.clone()]);
+    assert!(outcome.msgs.is_empty());
+    assert!(net.procs[1].faults().contains(&fault));
+
+    // delivered again, the vote is refused like any invalid vote, not reported twice
+    assert!(matches!(
+        net.procs[1].handle_signed_vote(second),
+        Err(Error::Protocol(
+            ProtocolError::ExistingVoteIncompatibleWithNewVote { .. }
+        ))
+    ));
+    assert_eq!(net.procs[1].faults().len(), 1);
+
+    // the evidence stands on its own: both votes are signed by the same elder
+    if let Fault::ConflictingVotes { first, second } = fault {
+        assert_eq!((first.voter, second.voter), (a_0, a_0));
+        first.validate_signature()?;
+        second.validate_signature()?;
+    }
+    Ok(())
+}
+
+#[test]
+fn test_rehearsal_is_non_binding() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut net = Net::with_procs(4, &mut rng);
+    for i in 0..4 {
+        let a_i = net.procs[i].public_key();
+        for j in 0..4 {
+            net.procs[j].force_join(a_i);
+        }
+        net.procs[i].set_config(Config {
+            rehearsal: true,
+            ..Default::default()
+        });
+    }
+    let voters = net.procs[0].voters.clone();
+
+    let a_0 = net.procs[0].public_key();
+    let packets = net.procs[0]
+        .propose(DummyProposal(9))?
+        .into_iter()
+        .map(|vote_msg| Packet {
+            source: a_0,
+            vote_msg,
+        });
+    net.enqueue_packets(packets);
+    net.drain_queued_packets()?;
+
+    // the drill goes all the way, but only yields a rehearsal proof
+    let proc = &mut net.procs[0];
+    assert_eq!(proc.consensus, Some(DummyProposal(9)));
+    assert_eq!(proc.decision()?, None);
+    let proof = proc.rehearsal_proof()?.unwrap();
+    proof.verify(&voters)?;
+    assert!(matches!(
+        proc.advance(voters.clone()),
+        Err(Error::State(StateError::RehearsalIsNonBinding))
+    ));
+
+    // rehearsal votes can't be passed off as live ones
+    assert!(proof.rehearsed.verify(&voters).is_err());
+    let mut live = HandoverState::<DummyProposal>::random(&mut rng, voters);
+    let rehearsal_vote = proof.rehearsed.votes.iter().next().cloned().unwrap();
+    assert!(live.handle_signed_vote(rehearsal_vote).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_restart_mid_round_from_snapshot_or_vote_log() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut net = Net::with_procs(4, &mut rng);
+    // the secret key of the last elder lives in its own keystore
+    let (elder, keystore) = elder_with_keystore(&mut rng)?;
+    net.procs[3] = elder;
+    for i in 0..4 {
+        let a_i = net.procs[i].public_key();
+        for j in 0..4 {
+            net.procs[j].force_join(a_i);
+        }
+    }
+
+    // the last elder logs the votes it handles, until it crashes mid-round
+    let mut log = InMemoryVoteLog::default();
+    let a_3 = net.procs[3].public_key();
+    let mut msgs = VecDeque::new();
+    for i in 0..3 {
+        msgs.extend(net.procs[i].propose(DummyProposal(i as u64))?);
+    }
+    let mut handled_by_a_3 = 0;
+    while handled_by_a_3 < 3 {
+        let msg = msgs.pop_front().unwrap();
+        if msg.dest == a_3 {
+            log.append(&msg.vote)?;
+            handled_by_a_3 += 1;
+        }
+        let dest = net.procs.iter_mut().find(|p| p.public_key() == msg.dest);
+        msgs.extend(dest.unwrap().handle_vote_msg(msg)?.msgs);
+    }
+    let snapshot: Snapshot<DummyProposal> =
+        bincode::deserialize(&bincode::serialize(&net.procs[3].snapshot())?)?;
+    let secret_key: SecretKey = bincode::deserialize(&keystore)?;
+    let votes_before_crash = net.procs[3].votes.clone();
+
+    // restoring the snapshot or replaying the log both get our votes back
+    let restored = HandoverState::restore(snapshot, secret_key)?;
+    assert_eq!(restored.public_key(), a_3);
+    assert_eq!(restored.votes, votes_before_crash);
+
+    let secret_key: SecretKey = bincode::deserialize(&keystore)?;
+    let mut replayed =
+        HandoverState::<DummyProposal>::from(secret_key, 0, net.procs[3].voters.clone());
+    replayed.replay(&log)?;
+    assert_eq!(
+        BTreeSet::from_iter(replayed.votes.keys()),
+        BTreeSet::from_iter(votes_before_crash.keys())
+    );
+
+    // and the round goes on with the restored elder
+    net.procs[3] = restored;
+    for msg in msgs {
+        net.enqueue_packets([Packet {
+            source: msg.vote.voter,
+            vote_msg: msg,
+        }]);
+    }
+    net.drain_queued_packets()?;
+    let decision = net.procs[0].consensus;
+    assert!(decision.is_some());
+    assert!(net.procs.iter().all(|p| p.consensus == decision));
+    Ok(())
+}
+
+#[test]
+fn test_delta_snapshots_catch_up_a_persisted_snapshot() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut net = Net::with_procs(7, &mut rng);
+    let (elder, keystore) = elder_with_keystore(&mut rng)?;
+    net.procs[6] = elder;
+    for i in 0..7 {
+        let a_i = net.procs[i].public_key();
+        for j in 0..7 {
+            net.procs[j].force_join(a_i);
+        }
+    }
+
+    // the last elder persists a full snapshot part way through the round
+    let a_6 = net.procs[6].public_key();
+    let mut msgs = VecDeque::new();
+    for i in 0..6 {
+        msgs.extend(net.procs[i].propose(DummyProposal(i as u64))?);
+    }
+    let mut handled_by_a_6 = 0;
+    while handled_by_a_6 < 5 {
+        let msg = msgs.pop_front().unwrap();
+        handled_by_a_6 += (msg.dest == a_6) as usize;
+        let dest = net.procs.iter_mut().find(|p| p.public_key() == msg.dest);
+        msgs.extend(dest.unwrap().handle_vote_msg(msg)?.msgs);
+    }
+    let mut persisted = net.procs[6].snapshot();
+
+    // a single vote later, the delta only carries that voter's vote and any vote of ours it caused
+    let msg = msgs.iter().position(|m| m.dest == a_6).unwrap();
+    let msg = msgs.remove(msg).unwrap();
+    let voter = msg.vote.voter;
+    msgs.extend(net.procs[6].handle_vote_msg(msg)?.msgs);
+
+    let delta = net.procs[6].snapshot_since(&persisted);
+    assert_eq!(delta.base, persisted.watermark);
+    assert!(!delta.all_votes);
+    assert!(delta.votes.contains_key(&voter));
+    assert!(delta.votes.keys().all(|v| [voter, a_6].contains(v)));
+
+    // nor does it carry what didn't change since
+    assert_eq!(delta.voters, None);
+    assert_eq!(delta.config, None);
+    assert!(delta.faults.is_empty());
+    assert!(delta.rounds.is_empty());
+    assert!(delta.stats.is_empty());
+    assert!(bincode::serialize(&delta)?.len() < bincode::serialize(&persisted)?.len());
+
+    persisted.apply(delta.clone())?;
+    assert_eq!(persisted, net.procs[6].snapshot());
+
+    // the same delta no longer applies to the updated snapshot
+    assert!(matches!(
+        persisted.apply(delta),
+        Err(Error::Storage(StorageError::SnapshotMismatch { .. }))
+    ));
+
+    // an unknown watermark gets the full set of votes back
+    let unknown = Snapshot {
+        watermark: None,
+        ..persisted.clone()
+    };
+    let delta = net.procs[6].snapshot_since(&unknown);
+    assert!(delta.all_votes);
+    assert_eq!(delta.votes, net.procs[6].votes);
+
+    // a config change comes along with the next delta
+    let mut config = net.procs[6].config.clone();
+    config.stats_retention = Some(5);
+    net.procs[6].set_config(config.clone());
+    let delta = net.procs[6].snapshot_since(&persisted);
+    assert_eq!(delta.config, Some(config));
+    persisted.apply(delta)?;
+    assert_eq!(persisted, net.procs[6].snapshot());
+
+    // deltas can also be taken against the snapshot a restored elder came back from
+    let secret_key: SecretKey = bincode::deserialize(&keystore)?;
+    let mut restored = HandoverState::restore(persisted.clone(), secret_key)?;
+    let msg = msgs.iter().position(|m| m.dest == a_6).unwrap();
+    restored.handle_vote_msg(msgs.remove(msg).unwrap())?;
+    persisted.apply(restored.snapshot_since(&persisted))?;
+    assert_eq!(persisted, restored.snapshot());
+    Ok(())
+}
+
+#[test]
+fn test_external_signer_and_verifier() -> eyre::Result<()> {
+    // stands in for a key kept in an HSM, the state never gets to see the raw key
+    #[derive(Debug)]
+    struct Hsm {
+        key: SecretKey,
+    }
+    impl Signer for Hsm {
+        type PublicKey = PublicKey;
+        type Signature = Signature;
+
+        fn public_key(&self) -> PublicKey {
+            self.key.public_key()
+        }
+        fn sign(&self, msg: &[u8]) -> sn_handover::Result<Signature> {
+            Ok(self.key.sign(msg))
+        }
+    }
+
+    // refuses the signatures of a voter the application distrusts
+    #[derive(Debug)]
+    struct Distrust(PublicKey);
+    impl Verifier for Distrust {
+        type PublicKey = PublicKey;
+        type Signature = Signature;
+
+        fn verify(&self, voter: &PublicKey, msg: &[u8], sig: &Signature) -> sn_handover::Result<()> {
+            if voter == &self.0 {
+                return Err(ProtocolError::NonMember {
+                    public_key: *voter,
+                    members: Default::default(),
+                }
+                .into());
+            }
+            KeyVerifier.verify(voter, msg, sig)
+        }
+    }
+
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut net = Net::with_procs(3, &mut rng);
+    let hsm = Hsm {
+        key: SecretKey::random(&mut rng),
+    };
+    check_signer(
+        Hsm {
+            key: bincode::deserialize(&bincode::serialize(&hsm.key)?)?,
+        },
+        &KeyVerifier,
+        DummyProposal(0),
+    )?;
+    net.procs.push(HandoverState::from(hsm, 0, Default::default()));
+
+    for i in 0..4 {
+        let a_i = net.procs[i].public_key();
+        for j in 0..4 {
+            net.procs[j].force_join(a_i);
+        }
+    }
+
+    // votes signed through the HSM verify like any other vote
+    let signed_vote = net.procs[3].sign_vote(Vote {
+        gen: 1,
+        ballot: Ballot::Propose(DummyProposal(3)),
+        extensions: Default::default(),
+    })?;
+    signed_vote.validate_signature()?;
+
+    let distrusted = net.procs[3].public_key();
+    let proc = &mut net.procs[0];
+    proc.set_verifier(Distrust(distrusted));
+    assert!(proc.validate_signed_vote(&signed_vote).is_err());
+    proc.set_verifier(KeyVerifier);
+
+    let packets = net.procs[3].propose(DummyProposal(3))?.into_iter().map(|vote_msg| Packet {
+        source: distrusted,
+        vote_msg,
+    });
+    net.enqueue_packets(packets);
+    net.drain_queued_packets()?;
+    assert!(net.procs.iter().all(|p| p.consensus == Some(DummyProposal(3))));
+    Ok(())
+}
+
+// An unordered pair of elders, (a, b) and (b, a) are the same pair
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+struct ElderPair(u8, u8);
+
+impl Proposal for ElderPair {
+    fn validate(&self) -> sn_handover::Result<()> {
+        Ok(())
+    }
+
+    fn dedup_key(&self) -> sn_handover::Result<Vec<u8>> {
+        Ok(vec![self.0.min(self.1), self.0.max(self.1)])
+    }
+}
+
+#[test]
+fn test_proposals_with_the_same_dedup_key_are_one_candidate() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let nprocs = 4;
+    let mut procs = Vec::from_iter(
+        (0..nprocs).map(|_| HandoverState::<ElderPair>::random(&mut rng, Default::default())),
+    );
+    let voters = BTreeSet::from_iter(procs.iter().map(HandoverState::public_key));
+    for proc in procs.iter_mut() {
+        proc.voters = voters.clone();
+    }
+
+    // half the elders encode the pair one way, the other half the other way
+    let mut msgs = VecDeque::new();
+    for (i, proc) in procs.iter_mut().enumerate() {
+        let pair = if i % 2 == 0 { ElderPair(1, 2) } else { ElderPair(2, 1) };
+        msgs.extend(proc.propose(pair)?);
+    }
+    while let Some(msg) = msgs.pop_front() {
+        let dest = procs
+            .iter_mut()
+            .find(|p| p.public_key() == msg.dest)
+            .unwrap();
+        msgs.extend(dest.handle_signed_vote(msg.vote)?.msgs);
+    }
+
+    // no split vote to merge, and every elder settles on the same encoding
+    for proc in procs.iter() {
+        assert_eq!(proc.consensus, Some(ElderPair(1, 2)));
+        let votes = proc.votes.values().flat_map(SignedVote::unpack_votes);
+        assert!(votes.into_iter().all(|v| !matches!(v.vote.ballot, Ballot::Merge(_))));
+    }
+    assert_eq!(
+        sn_handover::proposal_hash(&ElderPair(1, 2))?,
+        sn_handover::proposal_hash(&ElderPair(2, 1))?
+    );
+    Ok(())
+}
+
+// Delivers messages among the procs given, messages to anyone else are lost
+fn deliver_among(
+    procs: &mut [HandoverState<DummyProposal>],
+    mut msgs: VecDeque<VoteMsg<DummyProposal>>,
+) -> eyre::Result<()> {
+    while let Some(msg) = msgs.pop_front() {
+        if let Some(dest) = procs.iter_mut().find(|p| p.public_key() == msg.dest) {
+            msgs.extend(dest.handle_vote_msg(msg)?.msgs);
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_lagging_elder_fast_forwards_through_missed_generations() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut procs = Vec::from_iter(
+        (0..4).map(|_| HandoverState::<DummyProposal>::random(&mut rng, Default::default())),
+    );
+    let voters = BTreeSet::from_iter(procs.iter().map(HandoverState::public_key));
+    for proc in procs.iter_mut() {
+        proc.voters = voters.clone();
+    }
+    let voters_after = |_: &Decision<DummyProposal>| voters.clone();
+
+    // the last elder is offline for generations 0 and 1
+    let (online, lagging) = procs.split_at_mut(3);
+    let lagging = &mut lagging[0];
+    for gen in 0..2 {
+        let mut msgs = VecDeque::new();
+        for proc in online.iter_mut() {
+            msgs.extend(proc.propose(DummyProposal(gen))?);
+        }
+        deliver_among(online, msgs)?;
+        for proc in online.iter_mut() {
+            assert_eq!(proc.consensus, Some(DummyProposal(gen)));
+            proc.advance(voters.clone())?;
+        }
+    }
+    assert_eq!(online[0].decision_for(0).map(|d| d.proposal), Some(DummyProposal(0)));
+    assert_eq!(online[0].decision_for(2), None);
+
+    // back online in the middle of generation 2, its votes are from the future
+    let mut msgs = VecDeque::from_iter(online[0].propose(DummyProposal(2))?);
+    let to_lagging = msgs.iter().find(|m| m.dest == lagging.public_key()).unwrap();
+    assert!(matches!(
+        lagging.handle_vote_msg(to_lagging.clone()),
+        Err(Error::Protocol(ProtocolError::VoteWithInvalidGeneration { vote_gen: 2, gen: 0 }))
+    ));
+
+    // so it asks for what it missed, and gets the chain of decisions along with the round's votes
+    let catch_up = online[0].handle_sync_request(lagging.sync_request(online[0].public_key()))?;
+    assert_eq!(catch_up.decisions.len(), 2);
+    let outcome = lagging.handle_catch_up(catch_up, voters_after)?;
+    assert_eq!(lagging.gen, 2);
+    assert_eq!(lagging.decision_for(1).map(|d| d.proposal), Some(DummyProposal(1)));
+
+    // and takes part in the round it caught up with
+    msgs.extend(outcome.msgs);
+    deliver_among(&mut procs, msgs)?;
+    assert!(procs.iter().all(|p| p.consensus == Some(DummyProposal(2))));
+
+    // forged decisions don't get a node past its generation
+    let mut forged = HandoverState::<DummyProposal>::random(&mut rng, voters.clone());
+    let mut catch_up = procs[0].handle_sync_request(forged.sync_request(procs[0].public_key()))?;
+    catch_up.decisions[0].proposal = DummyProposal(42);
+    assert!(forged.handle_catch_up(catch_up, voters_after).is_err());
+    assert_eq!(forged.gen, 0);
+    Ok(())
+}
+
+#[test]
+fn test_quorum_policy_decides_what_a_super_majority_is() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut procs = Vec::from_iter(
+        (0..4).map(|_| HandoverState::<DummyProposal>::random(&mut rng, Default::default())),
+    );
+    let voters = BTreeSet::from_iter(procs.iter().map(HandoverState::public_key));
+    for proc in procs.iter_mut() {
+        proc.voters = voters.clone();
+    }
+    let keys = Vec::from_iter(procs.iter().map(HandoverState::public_key));
+
+    // an unsafe threshold is refused
+    assert!(matches!(
+        procs[0].set_quorum_policy(QuorumPolicy::SuperMajority {
+            numerator: 1,
+            denominator: 2
+        }),
+        Err(Error::Config(ConfigError::InvalidQuorumPolicy(_)))
+    ));
+
+    // three of four elders are a super majority by default, a super majority ballot shows it
+    let mut online = Vec::from_iter(procs.iter_mut().skip(1));
+    let mut msgs = VecDeque::new();
+    for proc in online.iter_mut() {
+        msgs.extend(proc.propose(DummyProposal(1))?);
+    }
+    let mut super_majority_vote = None;
+    while let Some(msg) = msgs.pop_front() {
+        if msg.vote.vote.is_super_majority_ballot() {
+            super_majority_vote = Some(msg.vote.clone());
+        }
+        if let Some(dest) = online.iter_mut().find(|p| p.public_key() == msg.dest) {
+            msgs.extend(dest.handle_vote_msg(msg)?.msgs);
+        }
+    }
+    assert!(online.iter().all(|p| p.consensus == Some(DummyProposal(1))));
+
+    // with a stricter 3/4 threshold the same ballot is not a super majority
+    let mut strict = HandoverState::<DummyProposal>::random(&mut rng, voters.clone());
+    strict.set_quorum_policy(QuorumPolicy::SuperMajority {
+        numerator: 3,
+        denominator: 4,
+    })?;
+    assert_eq!(strict.protocol_descriptor().quorum_rule.denominator, 4);
+    assert!(matches!(
+        strict.validate_signed_vote(super_majority_vote.as_ref().unwrap()),
+        Err(Error::Protocol(ProtocolError::SuperMajorityBallotIsNotSuperMajority { .. }))
+    ));
+
+    // nor is it when the first elder holds most of the stake
+    let stakes = BTreeMap::from_iter(keys.iter().copied().zip([10, 1, 1, 1]));
+    let mut weighted = HandoverState::<DummyProposal>::random(&mut rng, voters.clone());
+    weighted.set_quorum_policy(QuorumPolicy::Weighted(stakes.clone()))?;
+    assert!(matches!(
+        weighted.validate_signed_vote(super_majority_vote.as_ref().unwrap()),
+        Err(Error::Protocol(ProtocolError::SuperMajorityBallotIsNotSuperMajority { .. }))
+    ));
+
+    // where the stakeholder's proposal wins over everyone else's
+    let mut procs = Vec::from_iter(procs.into_iter().map(|p| {
+        let mut proc = HandoverState::<DummyProposal>::from(p.signer, 0, voters.clone());
+        proc.set_quorum_policy(QuorumPolicy::Weighted(stakes.clone())).unwrap();
+        proc
+    }));
+    let mut msgs = VecDeque::new();
+    for (i, proc) in procs.iter_mut().enumerate() {
+        let proposal = if i == 0 { DummyProposal(7) } else { DummyProposal(8) };
+        msgs.extend(proc.propose(proposal)?);
+    }
+    deliver_among(&mut procs, msgs)?;
+    assert!(procs.iter().all(|p| p.consensus == Some(DummyProposal(7))));
+    let decision = procs[1].decision()?.unwrap();
+    decision.verify_with(&KeyVerifier, &QuorumPolicy::Weighted(stakes), &voters)?;
+
+    // stakes too large to add up are refused, and weigh in full when unchecked
+    let huge_stakes = keys.iter().copied().zip([u64::MAX, u64::MAX, 1, 1]);
+    let huge = QuorumPolicy::Weighted(BTreeMap::from_iter(huge_stakes));
+    assert!(matches!(
+        procs[0].set_quorum_policy(huge.clone()),
+        Err(Error::Config(ConfigError::InvalidQuorumPolicy(_)))
+    ));
+    assert_eq!(huge.weight_of(&voters), u64::MAX);
+    assert!(huge.is_quorum(u64::MAX, &voters));
+    // large thresholds are checked without overflowing
+    procs[0].set_quorum_policy(QuorumPolicy::SuperMajority {
+        numerator: u64::MAX - 1,
+        denominator: u64::MAX,
+    })?;
+    Ok(())
+}
+
+#[test]
+fn test_messages_are_tagged_with_a_priority_class() -> eyre::Result<()> {
+    assert!(Priority::Decision > Priority::SuperMajority);
+    assert!(Priority::SuperMajority > Priority::Merge);
+    assert!(Priority::Merge > Priority::Propose);
+    assert!(Priority::Propose > Priority::AntiEntropy);
+
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut procs = Vec::from_iter(
+        (0..4).map(|_| HandoverState::<DummyProposal>::random(&mut rng, Default::default())),
+    );
+    let voters = BTreeSet::from_iter(procs.iter().map(HandoverState::public_key));
+    for proc in procs.iter_mut() {
+        proc.voters = voters.clone();
+    }
+
+    let mut msgs = VecDeque::new();
+    for (i, proc) in procs.iter_mut().enumerate() {
+        msgs.extend(proc.propose(DummyProposal(i as u64 % 2))?);
+    }
+    assert!(msgs.iter().all(|m| m.priority == Priority::Propose));
+
+    // delivered depth first, so some super majority votes complete a super majority over them
+    let mut seen = BTreeSet::new();
+    while let Some(msg) = msgs.pop_back() {
+        seen.insert(msg.priority);
+        let dest = procs.iter_mut().find(|p| p.public_key() == msg.dest).unwrap();
+        let outcome = dest.handle_vote_msg(msg)?;
+        for resp in outcome.msgs.iter() {
+            match resp.priority {
+                Priority::Decision => assert!(resp.vote.vote.is_super_majority_ballot()),
+                priority => assert_eq!(priority, Priority::of(&resp.vote.vote.ballot)),
+            }
+        }
+        msgs.extend(outcome.msgs);
+    }
+    assert!(procs.iter().all(|p| p.consensus.is_some()));
+    assert!(seen.contains(&Priority::Merge));
+    assert!(seen.contains(&Priority::Decision));
+
+    let resent = procs[0].anti_entropy(procs[1].public_key());
+    assert!(resent.iter().all(|m| m.priority == Priority::AntiEntropy));
+    Ok(())
+}
+
+#[test]
+fn test_corrupted_state_is_rebuilt_from_peers() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut procs = Vec::from_iter(
+        (0..4).map(|_| HandoverState::<DummyProposal>::random(&mut rng, Default::default())),
+    );
+    let voters = BTreeSet::from_iter(procs.iter().map(HandoverState::public_key));
+    for proc in procs.iter_mut() {
+        proc.voters = voters.clone();
+    }
+    let voters_after = |_: &Decision<DummyProposal>| voters.clone();
+
+    // the last elder stores the first proposal it heard of, then that vote gets corrupted on disk
+    let mut msgs = VecDeque::from_iter(procs[0].propose(DummyProposal(1))?);
+    let to_last = msgs.iter().position(|m| m.dest == procs[3].public_key()).unwrap();
+    let to_last = msgs.remove(to_last).unwrap();
+    procs[3].handle_vote_msg(to_last)?;
+    procs[3].check_integrity()?;
+    let stored = procs[3].votes.values_mut().next().unwrap();
+    stored.vote.ballot = Ballot::Propose(DummyProposal(2));
+    assert!(matches!(
+        procs[3].check_integrity(),
+        Err(Error::Storage(StorageError::Corrupted(_)))
+    ));
+
+    // it drops its votes and refuses to sign until it heard back from a super majority
+    let requests = procs[3].rebuild_from_peers();
+    assert_eq!(requests.len(), 3);
+    assert!(procs[3].votes.is_empty());
+    assert!(matches!(
+        procs[3].propose(DummyProposal(2)),
+        Err(Error::State(StateError::Rebuilding))
+    ));
+
+    for (i, helper) in [0, 1].into_iter().enumerate() {
+        let request = requests.iter().find(|r| r.dest == procs[helper].public_key());
+        let catch_up = procs[helper].handle_sync_request(*request.unwrap())?;
+        let outcome = procs[3].handle_catch_up(catch_up, voters_after)?;
+        assert_eq!(procs[3].is_rebuilding(), i == 0);
+        msgs.extend(outcome.msgs);
+    }
+    procs[3].check_integrity()?;
+
+    // consistent again, it takes part in the round
+    deliver_among(&mut procs, msgs)?;
+    assert!(procs.iter().all(|p| p.consensus == Some(DummyProposal(1))));
+    Ok(())
+}
+
+#[test]
+fn test_vote_digest_only_gets_the_missing_votes() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut procs = Vec::from_iter(
+        (0..4).map(|_| HandoverState::<DummyProposal>::random(&mut rng, Default::default())),
+    );
+    let voters = BTreeSet::from_iter(procs.iter().map(HandoverState::public_key));
+    for proc in procs.iter_mut() {
+        proc.voters = voters.clone();
+    }
+
+    // the last elder's proposal gets out, but it hears nothing back
+    let mut msgs = VecDeque::new();
+    for proc in procs.iter_mut() {
+        msgs.extend(proc.propose(DummyProposal(1))?);
+    }
+    let (online, lagging) = procs.split_at_mut(3);
+    let lagging = &mut lagging[0];
+    deliver_among(online, msgs)?;
+    assert!(online.iter().all(|p| p.consensus == Some(DummyProposal(1))));
+
+    // it's only sent the votes it doesn't know of
+    let digest = lagging.vote_digest(online[0].public_key())?;
+    assert_eq!(digest.hashes.len(), 1);
+    let missing = online[0].handle_vote_digest(digest.clone())?;
+    assert_eq!(missing.len(), online[0].anti_entropy(lagging.public_key()).len() - 1);
+    assert!(missing.iter().all(|m| m.vote.voter != lagging.public_key()));
+    for msg in missing {
+        lagging.handle_vote_msg(msg)?;
+    }
+    assert_eq!(lagging.consensus, Some(DummyProposal(1)));
+
+    // a peer knowing all of our votes has nothing left to be sent
+    let digest = VoteDigest {
+        requester: lagging.public_key(),
+        ..online[0].vote_digest(online[0].public_key())?
+    };
+    assert!(online[0].handle_vote_digest(digest.clone())?.is_empty());
+
+    // a digest of another generation falls back to sending all the votes
+    let digest = VoteDigest { gen: 1, ..digest };
+    assert_eq!(
+        online[0].handle_vote_digest(digest)?,
+        online[0].anti_entropy(lagging.public_key())
+    );
+    Ok(())
+}
+
+#[test]
+fn test_terminated_rounds_export_a_consensus_receipt() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut procs = Vec::from_iter(
+        (0..4).map(|_| HandoverState::<DummyProposal>::random(&mut rng, Default::default())),
+    );
+    let voters = BTreeSet::from_iter(procs.iter().map(HandoverState::public_key));
+    for proc in procs.iter_mut() {
+        proc.voters = voters.clone();
+    }
+
+    let mut msgs = VecDeque::new();
+    for proc in procs.iter_mut() {
+        msgs.extend(proc.propose(DummyProposal(7))?);
+    }
+    deliver_among(&mut procs, msgs)?;
+    assert!(matches!(
+        procs[0].receipt(0),
+        Err(Error::State(StateError::NoDecision(0)))
+    ));
+    procs[0].advance(voters.clone())?;
+
+    // every deciding signature can be checked with nothing but the receipt
+    let receipt = procs[0].receipt(0)?;
+    assert_eq!(receipt.value_hash, proposal_hash(&DummyProposal(7))?);
+    assert_eq!(BTreeSet::from_iter(receipt.participants.keys().copied()), voters);
+    assert!(!receipt.signatures.is_empty());
+    for sig in receipt.signatures.iter() {
+        assert!(receipt.participants.contains_key(&sig.signer));
+        sig.signer.verify(&sig.message, &sig.signature)?;
+    }
+
+    let json = receipt.to_json()?;
+    assert!(json.starts_with(r#"{"format":"consensus-receipt/v1","protocol":"sn_handover""#));
+    assert!(json.contains(&format!(r#""value_hash":"{}""#, hex::encode(receipt.value_hash.0))));
+    assert!(json.contains(r#""threshold":{"numerator":2,"denominator":3}"#));
+    assert_eq!(json.matches(r#""weight":1"#).count(), 4);
+    assert_eq!(json.matches(r#""signature":""#).count(), receipt.signatures.len());
+
+    // and it reads back as the layout `to_json` documents
+    fn hex_of(value: &impl Serialize) -> eyre::Result<Json> {
+        Ok(Json::Str(hex::encode(bincode::serialize(value)?)))
+    }
+    let mut participants = Vec::new();
+    for (key, weight) in receipt.participants.iter() {
+        participants.push(Json::object([
+            ("key", hex_of(key)?),
+            ("weight", Json::Number(*weight)),
+        ]));
+    }
+    let mut signatures = Vec::new();
+    for sig in receipt.signatures.iter() {
+        signatures.push(Json::object([
+            ("signer", hex_of(&sig.signer)?),
+            ("message", Json::Str(hex::encode(&sig.message))),
+            ("signature", hex_of(&sig.signature)?),
+        ]));
+    }
+    let d = &receipt.descriptor;
+    let expected = Json::object([
+        ("format", Json::Str("consensus-receipt/v1".to_string())),
+        ("protocol", Json::Str("sn_handover".to_string())),
+        ("protocol_version", Json::Number(d.version as u64)),
+        ("encoding", Json::Str(d.encoding.clone())),
+        ("hash_algorithm", Json::Str(d.hash_algorithm.clone())),
+        ("signature_scheme", Json::Str(d.signature_scheme.clone())),
+        ("height", Json::Number(receipt.gen)),
+        ("value_hash", Json::Str(hex::encode(receipt.value_hash.0))),
+        (
+            "threshold",
+            Json::object([
+                ("numerator", Json::Number(2)),
+                ("denominator", Json::Number(3)),
+            ]),
+        ),
+        ("participants", Json::Array(participants)),
+        ("signatures", Json::Array(signatures)),
+    ]);
+    assert_eq!(parse_json(&json)?, expected);
+
+    // names we don't control are escaped
+    let mut odd = receipt.clone();
+    odd.descriptor.encoding = "bin\"code\\\n".to_string();
+    match parse_json(&odd.to_json()?)? {
+        Json::Object(fields) => {
+            assert_eq!(
+                fields.get("encoding"),
+                Some(&Json::Str(odd.descriptor.encoding))
+            )
+        }
+        other => panic!("not an object: {:?}", other),
+    }
+    Ok(())
+}
+
+// Just enough of JSON to read receipts back: no floats, negatives, booleans or nulls
+#[derive(Debug, PartialEq)]
+enum Json {
+    Number(u64),
+    Str(String),
+    Array(Vec<Json>),
+    Object(BTreeMap<String, Json>),
+}
+
+impl Json {
+    fn object<const N: usize>(fields: [(&str, Json); N]) -> Self {
+        Json::Object(BTreeMap::from_iter(
+            fields
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value)),
+        ))
+    }
+}
+
+type JsonChars<'a> = std::iter::Peekable<std::str::Chars<'a>>;
+
+fn parse_json(text: &str) -> eyre::Result<Json> {
+    let mut chars = text.chars().peekable();
+    let value = parse_json_value(&mut chars)?;
+    skip_json_whitespace(&mut chars);
+    eyre::ensure!(chars.next().is_none(), "trailing characters");
+    Ok(value)
+}
+
+fn skip_json_whitespace(chars: &mut JsonChars) {
+    while chars.next_if(|c| c.is_ascii_whitespace()).is_some() {}
+}
+
+fn parse_json_value(chars: &mut JsonChars) -> eyre::Result<Json> {
+    skip_json_whitespace(chars);
+    match chars.peek().copied() {
+        Some('{') => {
+            let mut fields = BTreeMap::new();
+            for_each_json_item(chars, '}', &mut |chars| {
+                skip_json_whitespace(chars);
+                let key = parse_json_string(chars)?;
+                skip_json_whitespace(chars);
+                eyre::ensure!(
+                    chars.next() == Some(':'),
+                    "expected a colon after {:?}",
+                    key
+                );
+                let value = parse_json_value(chars)?;
+                eyre::ensure!(fields.insert(key, value).is_none(), "duplicate key");
+                Ok(())
+            })?;
+            Ok(Json::Object(fields))
+        }
+        Some('[') => {
+            let mut items = Vec::new();
+            for_each_json_item(chars, ']', &mut |chars| {
+                items.push(parse_json_value(chars)?);
+                Ok(())
+            })?;
+            Ok(Json::Array(items))
+        }
+        Some('"') => Ok(Json::Str(parse_json_string(chars)?)),
+        Some(c) if c.is_ascii_digit() => {
+            let mut digits = String::new();
+            while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                digits.push(digit);
+            }
+            Ok(Json::Number(digits.parse()?))
+        }
+        other => eyre::bail!("unexpected {:?}", other),
+    }
+}
+
+// the comma separated items of an array or object, from its opening bracket to `close`
+fn for_each_json_item(
+    chars: &mut JsonChars,
+    close: char,
+    item: &mut dyn FnMut(&mut JsonChars) -> eyre::Result<()>,
+) -> eyre::Result<()> {
+    chars.next();
+    skip_json_whitespace(chars);
+    if chars.next_if_eq(&close).is_some() {
+        return Ok(());
+    }
+    loop {
+        item(chars)?;
+        skip_json_whitespace(chars);
+        match chars.next() {
+            Some(',') => continue,
+            Some(c) if c == close => return Ok(()),
+            other => eyre::bail!("expected a comma or {:?}, got {:?}", close, other),
+        }
+    }
+}
+
+fn parse_json_string(chars: &mut JsonChars) -> eyre::Result<String> {
+    eyre::ensure!(chars.next() == Some('"'), "expected a string");
+    let mut text = String::new();
+    loop {
+        match chars.next() {
+            Some('"') => return Ok(text),
+            Some('\\') => match chars.next() {
+                Some(c @ ('"' | '\\' | '/')) => text.push(c),
+                Some('n') => text.push('\n'),
+                Some('t') => text.push('\t'),
+                Some('r') => text.push('\r'),
+                Some('u') => {
+                    let code = String::from_iter(chars.by_ref().take(4));
+                    let c = char::from_u32(u32::from_str_radix(&code, 16)?);
+                    text.push(c.ok_or_else(|| eyre::eyre!("not a character: {}", code))?);
+                }
+                other => eyre::bail!("unknown escape {:?}", other),
+            },
+            Some(c) if (c as u32) < 0x20 => eyre::bail!("unescaped control character"),
+            Some(c) => text.push(c),
+            None => eyre::bail!("unterminated string"),
+        }
+    }
+}
+
+#[test]
+fn test_vote_extensions_are_signed_and_ignored() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut procs = Vec::from_iter(
+        (0..4).map(|_| HandoverState::<DummyProposal>::random(&mut rng, Default::default())),
+    );
+    let voters = BTreeSet::from_iter(procs.iter().map(HandoverState::public_key));
+    for proc in procs.iter_mut() {
+        proc.voters = voters.clone();
+    }
+
+    // a newer node tags its proposal with metadata we don't know of
+    let signed_vote = procs[0].sign_vote(Vote {
+        gen: 0,
+        ballot: Ballot::Propose(DummyProposal(3)),
+        extensions: BTreeMap::from_iter([(7, b"from the future".to_vec())]),
+    })?;
+
+    // tampering with it breaks the signature
+    let mut tampered = signed_vote.clone();
+    tampered.vote.extensions.insert(7, b"forged".to_vec());
+    assert!(verify_vote_bytes(&bincode::serialize(&tampered)?).is_err());
+    assert!(procs[1].handle_signed_vote(tampered).is_err());
+    verify_vote_bytes(&bincode::serialize(&signed_vote)?)?;
+
+    // otherwise the vote counts like any other
+    let mut msgs = VecDeque::new();
+    for proc in procs.iter_mut() {
+        msgs.extend(proc.handle_signed_vote(signed_vote.clone())?.msgs);
+    }
+    deliver_among(&mut procs, msgs)?;
+    assert!(procs.iter().all(|p| p.consensus == Some(DummyProposal(3))));
+    // and is relayed as it was signed
+    assert!(procs[1]
+        .votes
+        .values()
+        .any(|v| v.unpack_votes().contains(&signed_vote)));
+    // the ballots nesting it are signed over their own encoding, and stream verify as such
+    for vote in procs[1].votes.values() {
+        assert!(vote.vote.is_extended());
+        verify_vote_bytes(&bincode::serialize(vote)?)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn test_compact_votes_send_each_nested_vote_once() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut procs = Vec::from_iter(
+        (0..7).map(|_| HandoverState::<DummyProposal>::random(&mut rng, Default::default())),
+    );
+    let voters = BTreeSet::from_iter(procs.iter().map(HandoverState::public_key));
+    for proc in procs.iter_mut() {
+        proc.voters = voters.clone();
+    }
+
+    // a split vote, so late votes nest merges of everyone's proposals
+    let mut msgs = VecDeque::new();
+    for (i, proc) in procs.iter_mut().enumerate() {
+        msgs.extend(proc.propose(DummyProposal(i as u64 % 3))?);
+    }
+    let (mut full_bytes, mut compact_bytes) = (0, 0);
+    let mut largest = (0, 0);
+    while let Some(msg) = msgs.pop_front() {
+        let compact = msg.compact();
+        assert_eq!(compact.expand()?, msg);
+        let sizes = (
+            bincode::serialize(&msg)?.len(),
+            bincode::serialize(&compact)?.len(),
+        );
+        full_bytes += sizes.0;
+        compact_bytes += sizes.1;
+        largest = largest.max(sizes);
+
+        let dest = procs.iter_mut().find(|p| p.public_key() == msg.dest).unwrap();
+        msgs.extend(dest.handle_compact_vote_msg(compact)?.msgs);
+    }
+    assert!(procs.iter().all(|p| p.consensus.is_some()));
+    assert!(compact_bytes < full_bytes);
+    assert!(largest.1 * 3 < largest.0 * 2);
+
+    // messages go out compacted on the wire, no one has to ask for it
+    let msg = procs[0]
+        .anti_entropy(procs[1].public_key())
+        .into_iter()
+        .find(|msg| msg.vote.vote.is_super_majority_ballot())
+        .unwrap();
+    assert_eq!(
+        msg.to_bytes()?[1..],
+        bincode::serialize(&msg.compact())?[..]
+    );
+    assert_eq!(VoteMsg::from_bytes(&msg.to_bytes()?)?, msg);
+
+    // entries only refer to the ones before them
+    let mut compact = msg.vote.compact();
+    assert_eq!(compact.entries.len(), msg.vote.unpack_votes().len());
+    compact.entries.reverse();
+    assert!(matches!(
+        compact.expand(),
+        Err(Error::Protocol(ProtocolError::Malformed(_)))
+    ));
+
+    // and can't expand to more than we're willing to hold, shared entries counted every time
+    let entry = compact.entries.pop().unwrap();
+    let bomb = |ballots: Vec<CompactBallot<DummyProposal>>| CompactVote {
+        entries: Vec::from_iter(ballots.into_iter().map(|ballot| CompactEntry {
+            ballot,
+            ..entry.clone()
+        })),
+    };
+    let doubling = bomb(Vec::from_iter((0..40).map(|i| match i {
+        0 => CompactBallot::Propose(DummyProposal(0)),
+        i => CompactBallot::Merge(Vec::from_iter(0..i)),
+    })));
+    let deep = bomb(Vec::from_iter((0..100).map(|i| match i {
+        0 => CompactBallot::Propose(DummyProposal(0)),
+        i => CompactBallot::Merge(vec![i - 1]),
+    })));
+    for bomb in [doubling, deep] {
+        assert!(matches!(
+            bomb.expand(),
+            Err(Error::Protocol(ProtocolError::Malformed(_)))
+        ));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_simulate_peer_votes_predicts_the_round() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut procs = Vec::from_iter(
+        (0..4).map(|_| HandoverState::<DummyProposal>::random(&mut rng, Default::default())),
+    );
+    let voters = BTreeSet::from_iter(procs.iter().map(HandoverState::public_key));
+    for proc in procs.iter_mut() {
+        proc.voters = voters.clone();
+    }
+    let keys = Vec::from_iter(procs.iter().map(HandoverState::public_key));
+    let mut msgs = VecDeque::from_iter(procs[0].propose(DummyProposal(0))?);
+
+    // on our own we can't terminate the round
+    let alone = procs[0].simulate_peer_votes([])?;
+    assert_eq!(alone.participants, BTreeSet::from_iter([keys[0]]));
+    assert!(!alone.quorum);
+    assert_eq!(alone.likely_winner, None);
+
+    // with two peers agreeing we would, and we can't be talked out of our own proposal
+    let agreeing = procs[0].simulate_peer_votes([
+        (keys[0], DummyProposal(9)),
+        (keys[1], DummyProposal(0)),
+        (keys[2], DummyProposal(0)),
+    ])?;
+    assert!(agreeing.quorum && !agreeing.split);
+    assert_eq!(agreeing.likely_winner, Some(DummyProposal(0)));
+
+    // a split still terminates, on the proposal the merge resolves to
+    let assumed = [(keys[1], DummyProposal(1)), (keys[2], DummyProposal(2))];
+    let split = procs[0].simulate_peer_votes(assumed)?;
+    assert!(split.quorum && split.split);
+    assert_eq!(procs[0].votes.len(), 1);
+
+    for (i, proposal) in assumed {
+        let proc = procs.iter_mut().find(|p| p.public_key() == i).unwrap();
+        msgs.extend(proc.propose(proposal)?);
+    }
+    deliver_among(&mut procs, msgs)?;
+    assert!(procs.iter().all(|p| p.consensus == split.likely_winner));
+    Ok(())
+}
+
+#[test]
+fn test_consensus_holds_over_a_faulty_link() -> eyre::Result<()> {
+    let faults = LinkFaults {
+        drop: 0.1,
+        duplicate: 0.1,
+        reorder: 0.3,
+        delay: 0.2,
+        max_delay: 5,
+    };
+    for seed in 0..3 {
+        let mut rng = StdRng::from_seed([seed; 32]);
+        let mut net = Net::with_procs(5, &mut rng).with_all_voters();
+        for i in 0..net.procs.len() {
+            net.propose(i, DummyProposal(i as u64 % 3))?;
+        }
+        net.drain_with_faults(faults, &mut rng)?;
+        assert!(net.honest_procs_decided());
+        net.check_invariants()?;
+
+        // the checker does catch an honest elder deciding on its own
+        let theirs = net.procs[0].consensus.map(|DummyProposal(p)| DummyProposal(p + 1));
+        net.procs[4].consensus = theirs;
+        assert!(matches!(
+            net.check_invariants(),
+            Err(Violation::UnprovenDecision { gen: 0, .. })
+        ));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_history_stats_survive_a_restart() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut procs = Vec::from_iter(
+        (0..4).map(|_| HandoverState::<DummyProposal>::random(&mut rng, Default::default())),
+    );
+    let voters = BTreeSet::from_iter(procs.iter().map(HandoverState::public_key));
+    for proc in procs.iter_mut() {
+        proc.voters = voters.clone();
+        proc.config.stats_retention = Some(2);
+    }
+
+    let mut log = InMemoryVoteLog::default();
+    for gen in 0..3 {
+        let mut msgs = VecDeque::new();
+        for (i, proc) in procs.iter_mut().enumerate() {
+            msgs.extend(proc.propose(DummyProposal(gen * (i as u64 % 2)))?);
+        }
+        deliver_among(&mut procs, msgs)?;
+        for proc in procs.iter_mut() {
+            proc.advance(voters.clone())?;
+        }
+        procs[0].log_stats(&mut log)?;
+    }
+
+    // only the latest rounds are kept
+    let stats = procs[0].history_stats();
+    assert_eq!(Vec::from_iter(stats.rounds.iter().map(|r| r.gen)), vec![1, 2]);
+    let kept = Vec::from_iter(procs[0].history.rounds.keys().copied());
+    assert_eq!(kept, vec![1, 2]);
+    assert!(stats.rounds.iter().all(|r| r.participants == voters));
+    assert!(stats.rounds.iter().all(|r| r.rounds >= 2));
+    assert!(stats.mean_duration().is_some());
+    assert!(stats.mean_rounds().unwrap() >= 2.0);
+
+    // the log has them all, a restarted node picks up the latest of them
+    assert_eq!(log.stats.len(), 3);
+    let mut restarted = HandoverState::<DummyProposal>::random(&mut rng, voters.clone());
+    restarted.config.stats_retention = Some(2);
+    restarted.load_stats(&log)?;
+    assert_eq!(restarted.history_stats(), stats);
+    assert!(HistoryStats::default().slowdown().is_none());
+    Ok(())
+}
+
+#[test]
+fn test_vote_msgs_round_trip_through_every_wire_version() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut procs = Vec::from_iter(
+        (0..4).map(|_| HandoverState::<DummyProposal>::random(&mut rng, Default::default())),
+    );
+    let voters = BTreeSet::from_iter(procs.iter().map(HandoverState::public_key));
+    for proc in procs.iter_mut() {
+        proc.voters = voters.clone();
+    }
+    let msg = procs[0].propose(DummyProposal(5))?.remove(0).with_correlation_id(42);
+
+    let bytes = msg.to_bytes()?;
+    assert_eq!(bytes[0], wire::WIRE_VERSION);
+    assert_eq!(VoteMsg::from_bytes(&bytes)?, msg);
+
+    // a peer that wasn't upgraded yet gets the layout of the first release
+    let v1_bytes = msg.to_bytes_in(wire::WIRE_V1)?;
+    let vote = &msg.vote;
+    let first_release = (
+        vote.vote.gen,
+        0u32, // Propose
+        DummyProposal(5),
+        vote.voter,
+        &vote.sig,
+        msg.dest,
+    );
+    assert_eq!(v1_bytes[1..], bincode::serialize(&first_release)?[..]);
+
+    // and still verifies our signature
+    let old = VoteMsg::<DummyProposal>::from_bytes(&v1_bytes)?;
+    assert_eq!(old.vote, msg.vote);
+    assert_eq!(old.correlation_id, None);
+    assert_eq!(old.priority, Priority::Propose);
+    old.vote.validate_signature()?;
+    let dest = procs.iter_mut().find(|p| p.public_key() == old.dest).unwrap();
+    dest.handle_vote_msg(old)?;
+
+    // versions from the future are refused, not misread
+    let mut future = bytes.clone();
+    future[0] = wire::WIRE_VERSION + 1;
+    assert!(matches!(
+        VoteMsg::<DummyProposal>::from_bytes(&future),
+        Err(Error::Protocol(ProtocolError::UnsupportedWireVersion { version, .. }))
+            if version == wire::WIRE_VERSION + 1
+    ));
+    assert!(msg.to_bytes_in(0).is_err());
+    assert!(matches!(
+        VoteMsg::<DummyProposal>::from_bytes(&[]),
+        Err(Error::Protocol(ProtocolError::MissingWireVersion))
+    ));
+
+    // the first release had no room for extensions
+    let mut extended = msg.clone();
+    extended.vote.vote.extensions.insert(1, vec![1]);
+    assert!(matches!(
+        extended.to_bytes_in(wire::WIRE_V1),
+        Err(Error::Protocol(ProtocolError::NotInWireVersion {
+            version: 1,
+            ..
+        }))
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_hostile_bytes_are_rejected_without_panicking() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut procs = Vec::from_iter(
+        (0..4).map(|_| HandoverState::<DummyProposal>::random(&mut rng, Default::default())),
+    );
+    let voters = BTreeSet::from_iter(procs.iter().map(HandoverState::public_key));
+    for proc in procs.iter_mut() {
+        proc.voters = voters.clone();
+    }
+    let bytes = procs[0].propose(DummyProposal(5))?.remove(0).to_bytes()?;
+
+    // truncations and bit flips of a genuine message, then plain noise
+    let mut inputs = Vec::from_iter((0..bytes.len()).map(|len| bytes[..len].to_vec()));
+    for _ in 0..500 {
+        let mut mutated = bytes.clone();
+        let i = rng.gen_range(0, mutated.len());
+        mutated[i] ^= rng.gen::<u8>() | 1;
+        inputs.push(mutated);
+    }
+    inputs.extend((0..200).map(|len| Vec::from_iter((0..len).map(|_| rng.gen::<u8>()))));
+
+    for input in inputs {
+        let _ = verify_vote_bytes(&input);
+        let _ = procs[3].handle_vote_msg_bytes(input.as_slice());
+        if let Ok(msg) = VoteMsg::<DummyProposal>::from_bytes(&input) {
+            let _ = procs[1].handle_vote_msg(msg);
+        }
+        if let Ok(msg) = bincode::deserialize::<sn_handover::CompactVoteMsg<DummyProposal>>(&input) {
+            let _ = procs[2].handle_compact_vote_msg(msg);
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_catch_up_is_paginated_past_the_page_size() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut procs = Vec::from_iter(
+        (0..4).map(|_| HandoverState::<DummyProposal>::random(&mut rng, Default::default())),
+    );
+    let voters = BTreeSet::from_iter(procs.iter().map(HandoverState::public_key));
+    for proc in procs.iter_mut() {
+        proc.voters = voters.clone();
+    }
+    let voters_after = |_: &Decision<DummyProposal>| voters.clone();
+
+    // the last elder misses 4 generations
+    let (online, lagging) = procs.split_at_mut(3);
+    let lagging = &mut lagging[0];
+    for gen in 0..4 {
+        let mut msgs = VecDeque::new();
+        for proc in online.iter_mut() {
+            msgs.extend(proc.propose(DummyProposal(gen))?);
+        }
+        deliver_among(online, msgs)?;
+        for proc in online.iter_mut() {
+            proc.advance(voters.clone())?;
+        }
+    }
+    let _ = online[0].propose(DummyProposal(4))?;
+
+    // pages fit a single decision proof, nested votes included, the round's votes come last
+    let proof = &online[0].decision_for(0).unwrap().votes;
+    let page_size = proof.iter().map(SignedVote::vote_count).sum();
+    assert!(page_size > proof.len());
+    online[0].config.catch_up_page_size = Some(page_size);
+    let mut request = Some(lagging.sync_request(online[0].public_key()));
+    let mut pages = 0;
+    while let Some(next) = request {
+        let catch_up = online[0].handle_sync_request(next)?;
+        assert!(catch_up.vote_count() <= page_size);
+        request = catch_up.next_request();
+        lagging.handle_catch_up(catch_up, voters_after)?;
+        pages += 1;
+    }
+    assert_eq!(pages, 5);
+    assert_eq!(lagging.gen, 4);
+    assert_eq!(lagging.decision_for(3).map(|d| d.proposal), Some(DummyProposal(3)));
+    assert!(lagging.votes.contains_key(&online[0].public_key()));
+
+    // the round's votes are paged too, each page carries at least one of them
+    let mut msgs = VecDeque::new();
+    for proc in online.iter_mut().skip(1) {
+        msgs.extend(proc.propose(DummyProposal(4))?);
+    }
+    deliver_among(online, msgs)?;
+    online[0].config.catch_up_page_size = Some(1);
+    assert_eq!(online[0].anti_entropy(lagging.public_key()).len(), 1);
+
+    // anti-entropy goes on where its previous page stopped
+    let mut page = online[0].anti_entropy_page(lagging.public_key(), None);
+    let mut sent = page.msgs.len();
+    while let Some(continuation) = page.continuation {
+        page = online[0].anti_entropy_page(lagging.public_key(), Some(continuation));
+        assert_eq!(page.msgs.len(), 1);
+        sent += 1;
+    }
+    assert_eq!(sent, online[0].votes.len());
+    assert_eq!(online[0].full_anti_entropy(lagging.public_key()).len(), sent);
+    let mut request = Some(lagging.sync_request(online[0].public_key()));
+    let mut pages = 0;
+    while let Some(next) = request {
+        let catch_up = online[0].handle_sync_request(next)?;
+        assert_eq!(catch_up.summary.votes.len(), 1);
+        request = catch_up.next_request();
+        lagging.handle_catch_up(catch_up, voters_after)?;
+        pages += 1;
+    }
+    assert_eq!(pages, online[0].votes.len());
+    assert!(online[0]
+        .votes
+        .keys()
+        .all(|voter| lagging.votes.contains_key(voter)));
+
+    // uncapped, it all comes at once
+    online[0].config.catch_up_page_size = None;
+    let fresh = HandoverState::<DummyProposal>::random(&mut rng, voters.clone());
+    let catch_up = online[0].handle_sync_request(fresh.sync_request(online[0].public_key()))?;
+    assert_eq!(catch_up.decisions.len(), 4);
+    assert_eq!(catch_up.continuation, None);
+    Ok(())
+}
+
+#[test]
+fn test_relays_bridge_elders_that_cant_reach_each_other() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut procs = Vec::from_iter(
+        (0..4).map(|_| HandoverState::<DummyProposal>::random(&mut rng, Default::default())),
+    );
+    let voters = BTreeSet::from_iter(procs.iter().map(HandoverState::public_key));
+    for proc in procs.iter_mut() {
+        proc.voters = voters.clone();
+    }
+    let known = BTreeSet::from([1, 2]);
+    let mut relays = [
+        Relay::new(1, 0, voters.clone(), known.clone()),
+        Relay::new(2, 0, voters.clone(), known.clone()),
+    ];
+
+    // the first and last elders only hear from each other through both relays
+    let (first, last) = (procs[0].public_key(), procs[3].public_key());
+    let bridged = |msg: &VoteMsg<DummyProposal>| {
+        BTreeSet::from([msg.vote.voter, msg.dest]) == BTreeSet::from([first, last])
+    };
+    let mut msgs = VecDeque::new();
+    for proc in procs.iter_mut() {
+        msgs.extend(proc.propose(DummyProposal(1))?);
+    }
+    let mut relayed = 0;
+    while let Some(msg) = msgs.pop_front() {
+        let msg = match bridged(&msg) {
+            true => {
+                let hop = relays[0].relay(RelayedVote::new(msg))?.unwrap();
+                let hop = relays[1].relay(hop)?.unwrap();
+                assert_eq!(hop.path, vec![1, 2]);
+                relayed += 1;
+                hop.into_msg()
+            }
+            false => msg,
+        };
+        let dest = procs.iter().position(|p| p.public_key() == msg.dest).unwrap();
+        msgs.extend(procs[dest].handle_vote_msg(msg)?.msgs);
+    }
+    assert!(relayed > 0);
+    assert!(procs.iter().all(|p| p.consensus == Some(DummyProposal(1))));
+
+    // a vote is forwarded to a destination once, copies and resends are dropped
+    let mut relays = [
+        Relay::new(1, 0, voters.clone(), known.clone()),
+        Relay::new(2, 0, voters.clone(), known),
+    ];
+    let msg = procs[0].anti_entropy(last).remove(0);
+    let hop = relays[0].relay(RelayedVote::new(msg.clone()))?.unwrap();
+    assert!(relays[0].relay(RelayedVote::new(msg.clone()))?.is_none());
+    let mut other_dest = msg.clone();
+    other_dest.dest = procs[1].public_key();
+    assert!(relays[0].relay(RelayedVote::new(other_dest))?.is_some());
+
+    // votes can't go round in circles, nor on forever, nor through relays we don't know
+    let back = relays[1].relay(hop)?.unwrap();
+    assert!(matches!(
+        relays[0].relay(back),
+        Err(Error::Protocol(ProtocolError::RelayLoop(1)))
+    ));
+    let mut hop = RelayedVote::with_ttl(msg.clone(), 1);
+    hop.ttl -= 1;
+    hop.path.push(1);
+    assert!(matches!(
+        relays[1].relay(hop),
+        Err(Error::Protocol(ProtocolError::RelayTtlExpired))
+    ));
+    let mut hop = RelayedVote::with_ttl(msg.clone(), u8::MAX);
+    hop.path.extend(1..=MAX_RELAY_HOPS as u64);
+    assert!(matches!(
+        relays[1].relay(hop),
+        Err(Error::Protocol(ProtocolError::RelayTtlExpired))
+    ));
+    let mut hop = RelayedVote::new(msg.clone());
+    hop.path.push(3);
+    assert!(matches!(
+        relays[1].relay(hop),
+        Err(Error::Protocol(ProtocolError::UnknownRelay(3)))
+    ));
+
+    // and a relay doesn't pass on what the elders didn't sign
+    let mut forged = msg;
+    forged.vote.vote.gen += 1;
+    assert!(relays[0].relay(RelayedVote::new(forged.clone())).is_err());
+    relays[0].advance(1, voters);
+    assert!(relays[0].relay(RelayedVote::new(forged)).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_quorum_co_signs_a_consistent_backup_point() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut procs = Vec::from_iter(
+        (0..4).map(|_| HandoverState::<DummyProposal>::random(&mut rng, Default::default())),
+    );
+    let voters = BTreeSet::from_iter(procs.iter().map(HandoverState::public_key));
+    for proc in procs.iter_mut() {
+        proc.voters = voters.clone();
+    }
+    assert!(procs[0].attest_state(0).is_err());
+
+    let mut msgs = VecDeque::new();
+    for (i, proc) in procs.iter_mut().enumerate() {
+        msgs.extend(proc.propose(DummyProposal(i as u64 % 2))?);
+    }
+    deliver_among(&mut procs, msgs)?;
+    let snapshot = procs[0].snapshot();
+
+    // every elder saw its own votes, yet they all attest the same state
+    let mut attestations = Vec::new();
+    for proc in procs.iter() {
+        attestations.push(proc.attest_state(0)?);
+    }
+    assert!(attestations.iter().all(|a| a.state == attestations[0].state));
+
+    // our attestation and one other's don't make a quorum, two others do
+    assert_eq!(procs[0].co_sign_state(0, attestations[1..2].to_vec())?, None);
+    let proof = procs[0].co_sign_state(0, attestations[1..3].to_vec())?.unwrap();
+    let policy = QuorumPolicy::default();
+    proof.verify(&policy, &voters)?;
+    assert!(proof.covers(&snapshot)?);
+
+    // the state stays attestable once we moved on, from our history
+    procs[1].advance(voters.clone())?;
+    assert_eq!(procs[1].attest_state(0)?.state, proof.state);
+    assert!(!proof.covers(&procs[1].snapshot())?);
+
+    // diverging states, forged signatures and other voter sets are refused
+    let mut diverging = attestations[1].clone();
+    diverging.state.decided = Default::default();
+    assert!(matches!(
+        procs[0].co_sign_state(0, [diverging]),
+        Err(Error::Protocol(ProtocolError::AttestedStateMismatch { gen: 0, .. }))
+    ));
+    let mut forged = attestations[1].clone();
+    forged.sig = attestations[2].clone().sig;
+    assert!(procs[0].co_sign_state(0, [forged]).is_err());
+    let others = BTreeSet::from_iter(voters.iter().copied().skip(1));
+    assert!(proof.verify(&policy, &others).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_proposals_move_through_their_lifecycle() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut procs = Vec::from_iter(
+        (0..4).map(|_| HandoverState::<DummyProposal>::random(&mut rng, Default::default())),
+    );
+    let voters = BTreeSet::from_iter(procs.iter().map(HandoverState::public_key));
+    for proc in procs.iter_mut() {
+        proc.voters = voters.clone();
+    }
+    assert_eq!(procs[3].proposal_status(&DummyProposal(1))?, None);
+
+    let mut msgs = VecDeque::new();
+    for (i, proc) in procs.iter_mut().enumerate().take(3) {
+        msgs.extend(proc.propose(DummyProposal(i as u64))?);
+    }
+    assert_eq!(
+        procs[0].proposal_status(&DummyProposal(0))?,
+        Some(ProposalStatus::EndorsedByMe)
+    );
+
+    // the last elder only watches, its events tell how the round went
+    let mut events = Vec::new();
+    while let Some(msg) = msgs.pop_front() {
+        let dest = procs.iter().position(|p| p.public_key() == msg.dest).unwrap();
+        let outcome = procs[dest].handle_vote_msg(msg)?;
+        if dest == 3 {
+            events.extend(outcome.events);
+        }
+        msgs.extend(outcome.msgs);
+    }
+    let decided = procs[3].consensus.unwrap();
+    let statuses = Vec::from_iter(
+        events
+            .iter()
+            .filter(|e| e.proposal == decided)
+            .map(|e| e.status),
+    );
+    assert_eq!(statuses.first(), Some(&ProposalStatus::Seen));
+    assert!(statuses.contains(&ProposalStatus::SuperMajorityForming));
+    assert_eq!(statuses.last(), Some(&ProposalStatus::Decided));
+    assert!(statuses.windows(2).all(|w| w[0] < w[1]));
+
+    // the other candidates were abandoned
+    for proc in procs.iter() {
+        for (proposal, status) in proc.proposal_statuses() {
+            match *proposal == decided {
+                true => assert_eq!(status, ProposalStatus::Decided),
+                false => assert_eq!(status, ProposalStatus::Abandoned),
+            }
+        }
+        assert_eq!(proc.proposal_statuses().count(), 3);
+    }
+
+    // a new generation starts from scratch
+    procs[3].advance(voters)?;
+    assert_eq!(procs[3].proposal_status(&decided)?, None);
+    Ok(())
+}
+
+#[test]
+fn test_hooks_inject_failures_at_protocol_points() -> eyre::Result<()> {
+    // loses what the elder sends, votes for 1 instead of what it was asked to
+    #[derive(Debug)]
+    struct Partitioned;
+    impl Hooks<DummyProposal> for Partitioned {
+        fn before_sign(&self, vote: &mut Vote<DummyProposal>) -> HookAction {
+            if let Ballot::Propose(_) = vote.ballot {
+                vote.ballot = Ballot::Propose(DummyProposal(1));
+            }
+            HookAction::Continue
+        }
+
+        fn before_send(&self, _msg: &mut VoteMsg<DummyProposal>) -> HookAction {
+            HookAction::Drop
+        }
+    }
+
+    #[derive(Debug)]
+    struct Deaf;
+    impl Hooks<DummyProposal> for Deaf {
+        fn before_sign(&self, _vote: &mut Vote<DummyProposal>) -> HookAction {
+            HookAction::Drop
+        }
+
+        fn before_apply(&self, _signed_vote: &mut SignedVote<DummyProposal>) -> HookAction {
+            HookAction::Drop
+        }
+    }
+
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut procs = Vec::from_iter(
+        (0..7).map(|_| HandoverState::<DummyProposal>::random(&mut rng, Default::default())),
+    );
+    let voters = BTreeSet::from_iter(procs.iter().map(HandoverState::public_key));
+    for proc in procs.iter_mut() {
+        proc.voters = voters.clone();
+    }
+
+    // the partitioned elder signs its mutated proposal, but none of it leaves
+    procs[5].set_hooks(Partitioned);
+    assert!(procs[5].propose(DummyProposal(0))?.is_empty());
+    let partitioned = procs[5].public_key();
+    assert_eq!(
+        procs[5].votes[&partitioned].proposals(),
+        BTreeSet::from_iter([(partitioned, DummyProposal(1))])
+    );
+
+    // the deaf elder can't vote and ignores what it's sent
+    procs[6].set_hooks(Deaf);
+    assert!(matches!(
+        procs[6].propose(DummyProposal(0)),
+        Err(Error::State(StateError::DroppedByHook))
+    ));
+    let vote = procs[0].sign_vote(Vote {
+        gen: 0,
+        ballot: Ballot::Propose(DummyProposal(0)),
+        extensions: Default::default(),
+    })?;
+    assert_eq!(procs[6].handle_signed_vote(vote)?, Outcome::default());
+    assert!(procs[6].votes.is_empty());
+
+    // the other five are a super majority of seven and still decide
+    let mut msgs = VecDeque::new();
+    for proc in procs[..5].iter_mut() {
+        msgs.extend(proc.propose(DummyProposal(0))?);
+    }
+    deliver_among(&mut procs, msgs)?;
+    for proc in procs[..5].iter() {
+        assert_eq!(proc.consensus, Some(DummyProposal(0)));
+    }
+    assert_eq!(procs[6].consensus, None);
+    Ok(())
+}
+
+#[test]
+fn test_estimated_wire_sizes_match_the_encoding() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut procs = Vec::from_iter(
+        (0..4).map(|_| HandoverState::<DummyProposal>::random(&mut rng, Default::default())),
+    );
+    let voters = BTreeSet::from_iter(procs.iter().map(HandoverState::public_key));
+    for proc in procs.iter_mut() {
+        proc.voters = voters.clone();
+    }
+    let mut msgs = VecDeque::new();
+    for (i, proc) in procs.iter_mut().enumerate() {
+        msgs.extend(proc.propose(DummyProposal(i as u64 % 2))?);
+    }
+    deliver_among(&mut procs, msgs)?;
+
+    // every ballot of the round, from proposals up to the deciding super majorities
+    let dest = procs[1].public_key();
+    for mut msg in procs[0].anti_entropy(dest) {
+        let v2_bytes = msg.to_bytes_in(wire::WIRE_V2)?;
+        assert_eq!(msg.estimated_wire_size()?, v2_bytes.len());
+        msg = msg.with_correlation_id(7);
+        msg.vote.vote.extensions.insert(3, vec![0; 5]);
+        let v2_bytes = msg.to_bytes_in(wire::WIRE_V2)?;
+        assert_eq!(msg.estimated_wire_size()?, v2_bytes.len());
+        assert_eq!(
+            msg.vote.vote.ballot.estimated_wire_size()?,
+            bincode::serialize(&msg.vote.vote.ballot)?.len()
+        );
+    }
+
+    let decision = procs[0].decision()?.unwrap();
+    assert_eq!(
+        decision.estimated_wire_size()?,
+        bincode::serialize(&decision)?.len()
+    );
+    Ok(())
+}
+
+#[test]
+fn test_completed_handovers_emit_a_transition_receipt() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut procs = Vec::from_iter(
+        (0..4).map(|_| HandoverState::<DummyProposal>::random(&mut rng, Default::default())),
+    );
+    let voters = BTreeSet::from_iter(procs.iter().map(HandoverState::public_key));
+    for proc in procs.iter_mut() {
+        proc.voters = voters.clone();
+    }
+
+    let mut msgs = VecDeque::new();
+    for proc in procs.iter_mut() {
+        msgs.extend(proc.propose(DummyProposal(7))?);
+    }
+    deliver_among(&mut procs, msgs)?;
+    assert!(matches!(
+        procs[0].transition_receipt(0),
+        Err(Error::State(StateError::NoDecision(0)))
+    ));
+
+    // the last elder hands over to a newcomer
+    let newcomer = SecretKey::random(&mut rng).public_key();
+    let mut new_elders = BTreeSet::from_iter(voters.iter().copied().take(3));
+    new_elders.insert(newcomer);
+    let decision = procs[0].decision()?.unwrap();
+    procs[0].advance(new_elders.clone())?;
+
+    let receipt = procs[0].transition_receipt(0)?;
+    assert_eq!(receipt.old_elders, voters);
+    assert_eq!(receipt.new_elders, new_elders);
+    assert_eq!(receipt.gen, 1);
+    assert_eq!(receipt.value_hash, proposal_hash(&DummyProposal(7))?);
+    assert_eq!(
+        receipt.proof_hash,
+        sn_handover::Hash::of(&bincode::serialize(&decision)?)
+    );
+
+    // the receipt and its hash survive the trip to the node
+    let bytes = bincode::serialize(&receipt)?;
+    let received: TransitionReceipt = bincode::deserialize(&bytes)?;
+    assert_eq!(received, receipt);
+    assert_eq!(received.hash()?, receipt.hash()?);
+
+    // elders holding another proof of the same decision agree on the hash
+    procs[1].advance(new_elders)?;
+    let other = procs[1].transition_receipt(0)?;
+    assert_eq!(other.hash()?, receipt.hash()?);
+    let mut proof_elsewhere = receipt.clone();
+    proof_elsewhere.proof_hash = sn_handover::Hash::of(b"another proof");
+    assert_eq!(proof_elsewhere.hash()?, receipt.hash()?);
+    Ok(())
+}
+
+#[test]
+fn test_bad_nested_signatures_are_pinned_to_their_vote() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut procs = Vec::from_iter(
+        (0..4).map(|_| HandoverState::<DummyProposal>::random(&mut rng, Default::default())),
+    );
+    let voters = BTreeSet::from_iter(procs.iter().map(HandoverState::public_key));
+    for proc in procs.iter_mut() {
+        proc.voters = voters.clone();
+    }
+    let propose = Vote {
+        gen: 0,
+        ballot: Ballot::Propose(DummyProposal(1)),
+        extensions: Default::default(),
+    };
+
+    // the third elder passes off a proposal of its own as the second elder's
+    let honest = procs[2].sign_vote(propose.clone())?;
+    let forged = SignedVote {
+        voter: procs[1].public_key(),
+        ..procs[2].sign_vote(propose)?
+    };
+    let merge = procs[2].sign_vote(Vote {
+        gen: 0,
+        ballot: Ballot::Merge(BTreeSet::from_iter([honest, forged.clone()])),
+        extensions: Default::default(),
+    })?;
+    let outer = procs[3].sign_vote(Vote {
+        gen: 0,
+        ballot: Ballot::Merge(BTreeSet::from_iter([merge.clone()])),
+        extensions: Default::default(),
+    })?;
+
+    let index = match &merge.vote.ballot {
+        Ballot::Merge(votes) => votes.iter().position(|v| v == &forged),
+        _ => None,
+    };
+    assert_eq!(outer.path_to(&forged), Some(vec![0, index.unwrap()]));
+    match &procs[0].handle_signed_vote(outer) {
+        Err(Error::Protocol(
+            err @ ProtocolError::InvalidNestedSignature {
+                path,
+                voter,
+                reason,
+            },
+        )) => {
+            assert_eq!(path, &vec![0, index.unwrap()]);
+            assert_eq!(voter, &procs[1].public_key());
+            // why it failed is the source of the error
+            let source = std::error::Error::source(err).map(ToString::to_string);
+            assert_eq!(source, Some(reason.to_string()));
+        }
+        resp => panic!("expected the forged vote to be pinned down, got {:?}", resp),
+    }
+    Ok(())
+}
+
+#[test]
+fn test_shutdown_leaves_our_vote_with_the_peers() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut procs = Vec::from_iter(
+        (0..4).map(|_| HandoverState::<DummyProposal>::random(&mut rng, Default::default())),
+    );
+    let voters = BTreeSet::from_iter(procs.iter().map(HandoverState::public_key));
+    for proc in procs.iter_mut() {
+        proc.voters = voters.clone();
+    }
+
+    // the last elder votes, but restarts before any of its messages got out
+    let leaving = procs[3].public_key();
+    procs[3].propose(DummyProposal(1))?;
+    let burst = procs[3].shutdown();
+    assert_eq!(burst.len(), 3);
+    assert!(burst.iter().all(|msg| msg.dest != leaving));
+
+    // it's read-only until it restarts
+    assert!(matches!(
+        procs[3].propose(DummyProposal(1)),
+        Err(Error::State(StateError::ShutDown))
+    ));
+    let vote = procs[0].sign_vote(Vote {
+        gen: 0,
+        ballot: Ballot::Propose(DummyProposal(1)),
+        extensions: Default::default(),
+    })?;
+    assert!(matches!(
+        procs[3].handle_signed_vote(vote),
+        Err(Error::State(StateError::ShutDown))
+    ));
+    assert!(!procs[3].anti_entropy(procs[0].public_key()).is_empty());
+
+    // its vote counts towards the decision of the others all the same
+    let mut msgs = VecDeque::from_iter(burst);
+    for proc in procs[..3].iter_mut() {
+        msgs.extend(proc.propose(DummyProposal(1))?);
+    }
+    deliver_among(&mut procs[..3], msgs)?;
+    for proc in procs[..3].iter() {
+        assert_eq!(proc.consensus, Some(DummyProposal(1)));
+        assert!(proc.votes.contains_key(&leaving));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_shutdown_sends_every_page_of_votes() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut procs = Vec::from_iter(
+        (0..4).map(|_| HandoverState::<DummyProposal>::random(&mut rng, Default::default())),
+    );
+    let voters = BTreeSet::from_iter(procs.iter().map(HandoverState::public_key));
+    for proc in procs.iter_mut() {
+        proc.voters = voters.clone();
+        proc.config.catch_up_page_size = Some(1);
+    }
+
+    // the leaving elder holds more votes than fit in a page, its own among them
+    let leaving = procs[3].public_key();
+    let mut msgs = VecDeque::new();
+    for proc in procs[..2].iter_mut() {
+        msgs.extend(proc.propose(DummyProposal(1))?);
+    }
+    msgs.extend(procs[3].propose(DummyProposal(1))?);
+    msgs.retain(|msg| msg.dest == leaving);
+    deliver_among(&mut procs[3..], msgs)?;
+    assert_eq!(procs[3].votes.len(), 3);
+
+    let burst = procs[3].shutdown();
+    assert_eq!(burst.len(), 3 * 3);
+    for peer in procs[..3].iter().map(HandoverState::public_key) {
+        let voters = BTreeSet::from_iter(
+            burst
+                .iter()
+                .filter(|msg| msg.dest == peer)
+                .map(|msg| msg.vote.voter),
+        );
+        assert_eq!(voters, BTreeSet::from_iter(procs[3].votes.keys().copied()));
+        assert!(voters.contains(&leaving));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_backpressure_counts_the_messages_peers_did_not_answer() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut procs = Vec::from_iter(
+        (0..4).map(|_| HandoverState::<DummyProposal>::random(&mut rng, Default::default())),
+    );
+    let voters = BTreeSet::from_iter(procs.iter().map(HandoverState::public_key));
+    for proc in procs.iter_mut() {
+        proc.voters = voters.clone();
+    }
+    let (a, b) = (procs[0].public_key(), procs[1].public_key());
+
+    // only the messages we numbered are tracked
+    let msgs = procs[0].propose(DummyProposal(1))?;
+    assert!(procs[0].backpressure().values().all(|n| *n == 0));
+
+    let mut to_b = Vec::new();
+    for msg in msgs.into_iter().chain(procs[0].anti_entropy(b)) {
+        if msg.dest == b {
+            to_b.push(procs[0].tag(msg));
+        }
+    }
+    assert_eq!(procs[0].backpressure()[&b], 2);
+    assert_eq!(procs[0].backpressure()[&a], 0);
+
+    // another voter using the same id doesn't answer for b
+    let last = to_b.last().unwrap().clone();
+    let id = last.correlation_id.unwrap();
+    let mut from_c = procs[2].propose(DummyProposal(2))?;
+    from_c.retain(|msg| msg.dest == a);
+    procs[0].handle_vote_msg(from_c.remove(0).with_correlation_id(id))?;
+    assert_eq!(procs[0].backpressure()[&b], 2);
+
+    // an answer to the latest message acknowledges the earlier one too
+    let answers = procs[1].handle_vote_msg(last)?.msgs;
+    let answer = answers.into_iter().find(|msg| msg.dest == a).unwrap();
+    assert_eq!(answer.correlation_id, Some(id));
+    let mut forged = answer.clone();
+    forged.vote.vote.gen += 1;
+    assert!(procs[0].handle_vote_msg(forged).is_err());
+    assert_eq!(procs[0].backpressure()[&b], 2);
+    procs[0].handle_vote_msg(answer)?;
+    assert_eq!(procs[0].backpressure()[&b], 0);
+    Ok(())
+}
+
+#[test]
+fn test_sealed_proposals_are_revealed_once_decided() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut procs = Vec::from_iter(
+        (0..4).map(|_| HandoverState::<SealedProposal>::random(&mut rng, Default::default())),
+    );
+    let voters = BTreeSet::from_iter(procs.iter().map(HandoverState::public_key));
+    for proc in procs.iter_mut() {
+        proc.voters = voters.clone();
+    }
+
+    // the votes only carry commitments, even to the same proposal they differ
+    let mut openings = Vec::new();
+    let mut msgs = VecDeque::new();
+    for (i, proc) in procs.iter_mut().enumerate() {
+        let (sealed, opening) = SealedProposal::seal(DummyProposal(i as u64 % 2), &mut rng)?;
+        openings.push(opening);
+        msgs.extend(proc.propose(sealed)?);
+    }
+    assert_ne!(openings[0].sealed()?, openings[2].sealed()?);
+    while let Some(msg) = msgs.pop_front() {
+        if let Some(dest) = procs.iter_mut().find(|p| p.public_key() == msg.dest) {
+            msgs.extend(dest.handle_vote_msg(msg)?.msgs);
+        }
+    }
+
+    let decision = procs[0].decision()?.unwrap();
+    decision.verify(&voters)?;
+    let proposer = procs
+        .iter()
+        .zip(openings.iter())
+        .find(|(_, opening)| matches!(opening.sealed(), Ok(s) if s == decision.proposal))
+        .map(|(proc, _)| proc.public_key())
+        .unwrap();
+    let mut reveal = Reveal::new(decision.clone(), Duration::from_secs(60))?;
+    let mut late = Reveal::<DummyProposal>::new(decision.clone(), Duration::ZERO)?;
+    assert_eq!(reveal.proposers(), &BTreeSet::from([proposer]));
+    assert_eq!(reveal.outcome(), RevealOutcome::Pending);
+
+    let (decided, others): (Vec<_>, Vec<_>) = openings
+        .into_iter()
+        .partition(|opening| matches!(opening.sealed(), Ok(s) if s == decision.proposal));
+    assert_eq!(decided.len(), 1);
+    assert_eq!(decision.reveal(decided[0])?, decided[0].proposal);
+
+    // nothing else opens the decided commitment
+    for opening in others {
+        assert!(matches!(
+            decision.reveal(opening),
+            Err(Error::Protocol(ProtocolError::InvalidOpening(_)))
+        ));
+    }
+    let mut tampered = decided[0];
+    tampered.proposal = DummyProposal(9);
+    assert!(decision.reveal(tampered).is_err());
+
+    // the proposer reveals before the deadline, or is excluded once it passed
+    assert!(reveal.handle_opening(tampered).is_err());
+    assert_eq!(reveal.outcome(), RevealOutcome::Pending);
+    reveal.handle_opening(decided[0])?;
+    assert_eq!(
+        reveal.outcome(),
+        RevealOutcome::Revealed(&decided[0].proposal)
+    );
+    late.handle_opening(decided[0])?;
+    assert_eq!(
+        late.outcome(),
+        RevealOutcome::Excluded(&BTreeSet::from([proposer]))
+    );
+    Ok(())
+}
+
+#[test]
+fn test_v0_snapshots_migrate_to_the_current_format() -> eyre::Result<()> {
+    // the v0 layout, as a v0 release wrote it, around the votes of the first release
+    #[allow(dead_code, clippy::large_enum_variant)]
+    #[derive(Serialize)]
+    enum FaultV0 {
+        InvalidProposal {
+            vote: v1::SignedVote<DummyProposal>,
+        },
+        ConflictingVotes {
+            first: v1::SignedVote<DummyProposal>,
+            second: v1::SignedVote<DummyProposal>,
+        },
+    }
+    #[derive(Serialize)]
+    struct SnapshotV0 {
+        gen: Generation,
+        votes: BTreeMap<PublicKey, v1::SignedVote<DummyProposal>>,
+        voters: Vec<PublicKey>,
+        consensus: Option<DummyProposal>,
+        faults: Vec<FaultV0>,
+        config: (Duration, Option<Duration>, Duration, bool, bool),
+    }
+    #[derive(Serialize)]
+    struct VoteLogV0 {
+        votes: Vec<v1::SignedVote<DummyProposal>>,
+    }
+
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut keys: Vec<SecretKey> = (0..4).map(|_| SecretKey::random(&mut rng)).collect();
+    let voters: BTreeSet<PublicKey> = keys.iter().map(|k| k.public_key()).collect();
+    let v0_vote = |key: &SecretKey, ballot| -> eyre::Result<v1::SignedVote<DummyProposal>> {
+        let vote = v1::Vote { gen: 3, ballot };
+        let sig = key.sign(&vote.to_bytes()?);
+        Ok(v1::SignedVote {
+            vote,
+            voter: key.public_key(),
+            sig,
+        })
+    };
+    let propose = |proposal| v1::Ballot::Propose(DummyProposal(proposal));
+
+    // nested votes of several proposals, which the first release and we order differently
+    let mut proposals = BTreeSet::new();
+    for (i, key) in keys.iter().enumerate() {
+        proposals.insert(v0_vote(key, propose(7 + i as u64))?);
+    }
+    let mut merges = BTreeSet::new();
+    for key in keys.iter().take(3) {
+        merges.insert(v0_vote(key, v1::Ballot::Merge(proposals.clone()))?);
+    }
+    let ours = v0_vote(&keys[0], v1::Ballot::SuperMajority(merges.clone()))?;
+    let merge = merges
+        .iter()
+        .find(|v| v.voter == keys[2].public_key())
+        .unwrap();
+    let mut forged = v0_vote(&keys[1], propose(7))?;
+    forged.vote.ballot = propose(8);
+    let conflict = FaultV0::ConflictingVotes {
+        first: v0_vote(&keys[2], propose(7))?,
+        second: merge.clone(),
+    };
+    let forged_conflict = FaultV0::ConflictingVotes {
+        first: v0_vote(&keys[3], propose(7))?,
+        second: forged.clone(),
+    };
+    let v0 = SnapshotV0 {
+        gen: 3,
+        votes: BTreeMap::from_iter([
+            (ours.voter, ours.clone()),
+            (merge.voter, merge.clone()),
+            (forged.voter, forged.clone()),
+        ]),
+        voters: voters.iter().copied().collect(),
+        consensus: None,
+        faults: vec![conflict, forged_conflict],
+        config: (
+            Duration::from_secs(5),
+            None,
+            Duration::from_secs(1),
+            true,
+            false,
+        ),
+    };
+
+    // the votes that verify as v0 signed them carry over, and still verify
+    let migrated = sn_handover::migrate::from_v0::<DummyProposal>(&bincode::serialize(&v0)?)?;
+    assert_eq!(migrated.snapshot.gen, 3);
+    assert_eq!(migrated.snapshot.voters, voters);
+    assert_eq!(
+        BTreeSet::from_iter(migrated.snapshot.votes.values().cloned()),
+        BTreeSet::from([
+            SignedVote::from(ours.clone()),
+            SignedVote::from(merge.clone())
+        ])
+    );
+    assert_eq!(migrated.snapshot.faults.len(), 1);
+    assert_eq!(
+        migrated.snapshot.faults.iter().next().unwrap().vote().voter,
+        keys[2].public_key()
+    );
+    assert_eq!(
+        migrated.snapshot.config.startup_grace_period,
+        Duration::from_secs(5)
+    );
+    assert!(migrated.snapshot.config.unanimity_below_bft_minimum);
+    assert_eq!((migrated.dropped_votes, migrated.dropped_faults), (1, 1));
+    assert!(migrated.must_rebuild());
+
+    let log = VoteLogV0 {
+        votes: vec![ours.clone(), forged, v0_vote(&keys[3], propose(10))?],
+    };
+    let migrated_log =
+        sn_handover::migrate::from_v0_log::<DummyProposal>(&bincode::serialize(&log)?)?;
+    assert_eq!(migrated_log.dropped_votes, 1);
+    assert_eq!(migrated_log.log.votes.len(), 2);
+
+    // we may have lost a vote in v0, resynchronize before voting again
+    let logged_voter = keys[3].public_key();
+    let mut proc = HandoverState::restore(migrated.snapshot, keys.remove(0))?;
+    proc.check_integrity()?;
+    proc.replay(&migrated_log.log)?;
+    assert!(proc.votes.contains_key(&logged_voter));
+    assert_eq!(proc.rebuild_from_peers().len(), 3);
+    assert!(proc.propose(DummyProposal(8)).is_err());
+
+    assert!(sn_handover::migrate::from_v0::<DummyProposal>(b"not a snapshot").is_err());
+    assert!(sn_handover::migrate::from_v0_log::<DummyProposal>(b"not a log").is_err());
+    Ok(())
+}
+
+#[derive(Debug)]
+struct SplitOn42(Split);
+
+impl SplitPolicy<DummyProposal> for SplitOn42 {
+    fn split(&self, _gen: Generation, decision: &DummyProposal) -> Option<Split> {
+        (decision.0 == 42).then(|| self.0.clone())
+    }
+}
+
+#[test]
+fn test_split_spawns_children_bound_to_the_parent_decision() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut procs = Vec::from_iter(
+        (0..8).map(|_| HandoverState::<DummyProposal>::random(&mut rng, Default::default())),
+    );
+    let voters = BTreeSet::from_iter(procs.iter().map(HandoverState::public_key));
+    for proc in procs.iter_mut() {
+        proc.voters = voters.clone();
+    }
+    let keys = Vec::from_iter(procs.iter().map(HandoverState::public_key));
+    let split = Split {
+        prefix: Prefix { bits: vec![true] },
+        voters: [
+            BTreeSet::from_iter(keys[..4].iter().copied()),
+            BTreeSet::from_iter(keys[4..].iter().copied()),
+        ],
+    };
+    let policy = SplitOn42(split.clone());
+
+    // nothing to split before the decision
+    let undecided = HandoverState::<DummyProposal>::random(&mut rng, voters.clone());
+    assert!(matches!(
+        undecided.split(&policy),
+        Err(Error::State(StateError::NoDecision(0)))
+    ));
+
+    let mut msgs = VecDeque::new();
+    for proc in procs.iter_mut() {
+        msgs.extend(proc.propose(DummyProposal(42))?);
+    }
+    deliver_among(&mut procs, msgs)?;
+    let parent = procs[0].section_state(0)?;
+
+    // each elder runs the child it's an elder of
+    let mut children: [Vec<HandoverState<DummyProposal>>; 2] = Default::default();
+    for proc in procs {
+        let route = split.route(&proc.public_key()).unwrap();
+        let [zero, one] = proc.split(&policy)?;
+        assert_eq!((zero.gen, one.gen), (1, 1));
+        match route == split.children()[0] {
+            true => children[0].push(zero),
+            false => children[1].push(one),
+        }
+    }
+    for (child, prefix) in children.iter().zip(split.children()) {
+        assert_eq!(child[0].config.genesis, Some(split_genesis(&parent, &prefix)?));
+    }
+    assert_ne!(children[0][0].config.genesis, children[1][0].config.genesis);
+
+    for (i, child) in children.iter_mut().enumerate() {
+        let mut msgs = VecDeque::new();
+        for proc in child.iter_mut() {
+            msgs.extend(proc.propose(DummyProposal(i as u64))?);
+        }
+        deliver_among(child, msgs)?;
+        assert!(child.iter().all(|p| p.consensus == Some(DummyProposal(i as u64))));
+        assert!(child[0].decision()?.is_some());
+    }
+
+    // child votes don't verify outside of their instance
+    let vote = children[0][0].votes.values().next().unwrap().clone();
+    let genesis = children[0][0].config.genesis.unwrap();
+    assert!(vote
+        .validate_signature_with(&KeyVerifier, SigningDomain::Split(genesis))
+        .is_ok());
+    assert!(vote
+        .validate_signature_with(&KeyVerifier, SigningDomain::Live)
+        .is_err());
+    let sibling_genesis = children[1][0].config.genesis.unwrap();
+    assert!(vote
+        .validate_signature_with(&KeyVerifier, SigningDomain::Split(sibling_genesis))
+        .is_err());
+    Ok(())
+}
+
+#[test]
+fn prop_bft_safety_over_arbitrary_schedules() {
+    properties::check(|schedule| {
+        properties::safety(&schedule, |rng| DummyProposal(rng.gen::<u64>() % 3))
+    });
+}
+
+#[test]
+fn prop_honest_elders_terminate_over_arbitrary_schedules() {
+    properties::check(|schedule| {
+        properties::termination(&schedule, |rng| DummyProposal(rng.gen::<u64>() % 3))
+    });
+}
+
+#[test]
+fn test_elders_terminate_among_byzantine_elders_that_do_not_equivocate() {
+    for seed in 0..4 {
+        let schedule = properties::Schedule {
+            elders: 4,
+            byzantine: 1,
+            forged: 4,
+            equivocate: false,
+            faults: Default::default(),
+            seed,
+        };
+        let proposal = |rng: &mut StdRng| DummyProposal(rng.gen::<u64>() % 3);
+        let terminated = properties::termination(&schedule, proposal);
+        assert!(!terminated.is_failure(), "{:?}: {:?}", schedule, terminated);
+        let matched = properties::matches_oracle(&schedule, proposal);
+        assert!(!matched.is_failure(), "{:?}: {:?}", schedule, matched);
+    }
+}
+
+#[test]
+fn test_config_handshake_reports_every_parameter_peers_differ_on() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let a = HandoverState::<DummyProposal>::random(&mut rng, Default::default());
+    let mut b = HandoverState::<DummyProposal>::random(&mut rng, Default::default());
+
+    // local tunables are ours to pick
+    b.config.startup_grace_period = Duration::from_secs(30);
+    b.config.stats_retention = Some(10);
+    assert_eq!(a.config.fingerprint()?, b.config.fingerprint()?);
+    let theirs: ConfigHandshake =
+        bincode::deserialize(&bincode::serialize(&b.config.handshake()?)?)?;
+    a.config.handshake()?.check(&theirs)?;
+
+    // the rules we vote by aren't
+    b.set_quorum_policy(QuorumPolicy::SuperMajority {
+        numerator: 3,
+        denominator: 4,
+    })?;
+    b.config.rehearsal = true;
+    let differences = a.config.handshake()?.diff(&b.config.handshake()?);
+    assert_eq!(
+        Vec::from_iter(differences.iter().map(|d| d.field)),
+        vec!["quorum_rule", "rehearsal"]
+    );
+    assert!(matches!(
+        a.config.handshake()?.check(&b.config.handshake()?),
+        Err(Error::Config(ConfigError::IncompatibleConfig(differences))) if differences.len() == 2
+    ));
+
+    // nor are the pages we send and the size of the messages we take in
+    let mut c = HandoverState::<DummyProposal>::random(&mut rng, Default::default());
+    c.config.catch_up_page_size = Some(10);
+    c.config.max_message_size = Some(1 << 20);
+    assert_ne!(a.config.fingerprint()?, c.config.fingerprint()?);
+    let differences = a.config.handshake()?.diff(&c.config.handshake()?);
+    assert_eq!(
+        Vec::from_iter(differences.iter().map(|d| d.field)),
+        vec!["limits.max_message_size", "limits.catch_up_page_size"]
+    );
+    assert_eq!(differences[1].theirs, "Some(10)");
+
+    // stakes don't show in the quorum rule
+    let stakes = BTreeMap::from_iter([(a.public_key(), 1), (b.public_key(), 2)]);
+    let mut a = a;
+    a.set_quorum_policy(QuorumPolicy::Weighted(stakes))?;
+    b.set_quorum_policy(QuorumPolicy::Weighted(BTreeMap::from_iter([(a.public_key(), 1)])))?;
+    b.config.rehearsal = false;
+    let differences = a.config.handshake()?.diff(&b.config.handshake()?);
+    assert_eq!(
+        Vec::from_iter(differences.iter().map(|d| d.field)),
+        vec!["quorum_policy"]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_voter_history_summarizes_participation_across_generations() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut procs = Vec::from_iter(
+        (0..4).map(|_| HandoverState::<DummyProposal>::random(&mut rng, Default::default())),
+    );
+    let voters = BTreeSet::from_iter(procs.iter().map(HandoverState::public_key));
+    for proc in procs.iter_mut() {
+        proc.voters = voters.clone();
+    }
+    let (first, last) = (procs[0].public_key(), procs[3].public_key());
+
+    // gen 0: the last elder signs a conflicting vote for the first one
+    let mut msgs = VecDeque::from_iter(procs[3].propose(DummyProposal(0))?);
+    let to_first = msgs.iter().position(|m| m.dest == first).unwrap();
+    let to_first = msgs.remove(to_first).unwrap();
+    procs[0].handle_vote_msg(to_first)?;
+    let conflicting = procs[3].sign_vote(Vote {
+        gen: 0,
+        ballot: Ballot::Propose(DummyProposal(9)),
+        extensions: Default::default(),
+    })?;
+    let _ = procs[0].handle_vote_msg(VoteMsg {
+        vote: conflicting,
+        dest: first,
+        correlation_id: None,
+        priority: Default::default(),
+    });
+    assert_eq!(procs[0].faults().len(), 1);
+    for proc in procs.iter_mut().take(3) {
+        msgs.extend(proc.propose(DummyProposal(0))?);
+    }
+    deliver_among(&mut procs, msgs)?;
+    for proc in procs.iter_mut() {
+        proc.advance(voters.clone())?;
+    }
+
+    // gen 1: it's offline
+    let (online, _) = procs.split_at_mut(3);
+    let mut msgs = VecDeque::new();
+    for proc in online.iter_mut() {
+        msgs.extend(proc.propose(DummyProposal(1))?);
+    }
+    deliver_among(online, msgs)?;
+    for proc in online.iter_mut() {
+        proc.advance(voters.clone())?;
+    }
+
+    let history = procs[0].voter_history(last);
+    assert_eq!(
+        Vec::from_iter(history.generations.clone()),
+        vec![(0, Participation::Faulted), (1, Participation::Abstained)]
+    );
+    assert_eq!(history.count(Participation::Voted), 0);
+
+    let history = procs[0].voter_history(procs[1].public_key());
+    assert_eq!(history.count(Participation::Voted), 2);
+    assert_eq!(history.mean_response_round, Some(0.0));
+
+    let stranger = SecretKey::random(&mut rng).public_key();
+    assert!(procs[0].voter_history(stranger).generations.is_empty());
+    Ok(())
+}
+
+#[test]
+fn prop_decisions_match_the_reference_oracle_over_arbitrary_schedules() {
+    properties::check(|schedule| {
+        properties::matches_oracle(&schedule, |rng| DummyProposal(rng.gen::<u64>() % 3))
+    });
+}
+
+// A proposal that fails to encode, whoever proposed it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+struct Unencodable;
+
+impl Serialize for Unencodable {
+    fn serialize<S: serde::Serializer>(&self, _: S) -> Result<S::Ok, S::Error> {
+        Err(serde::ser::Error::custom("no encoding"))
+    }
+}
+
+impl Proposal for Unencodable {
+    fn validate(&self) -> sn_handover::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_undecodable_peer_input_is_a_protocol_error() {
+    let garbage = [wire::WIRE_VERSION, 0xff, 0xff];
+    let err = VoteMsg::<DummyProposal>::from_bytes(&garbage).unwrap_err();
+    // the layers of the taxonomy hand on the error they wrap as its source, without repeating it
+    match &err {
+        Error::Protocol(inner @ ProtocolError::Malformed(_)) => {
+            let source = std::error::Error::source(&err).map(ToString::to_string);
+            assert_eq!(source, Some(inner.to_string()));
+            assert!(!err.to_string().contains(&inner.to_string()));
+            assert_eq!(err.report(), format!("{}: {}", err, inner));
+        }
+        _ => panic!("{:?} is not a malformed message", err),
+    }
+
+    // proposals and votes of peers that don't encode are theirs to fix, not our storage
+    assert!(matches!(
+        proposal_hash(&Unencodable),
+        Err(Error::Protocol(ProtocolError::Malformed(_)))
+    ));
+    let vote = Vote {
+        gen: 0,
+        ballot: Ballot::Propose(Unencodable),
+        extensions: Default::default(),
+    };
+    assert!(matches!(
+        vote.to_bytes(),
+        Err(Error::Protocol(ProtocolError::Malformed(_)))
+    ));
+}
+
+#[test]
+fn test_snapshots_keep_history_and_stats() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut net = Net::with_procs(1, &mut rng);
+    let (elder, keystore) = elder_with_keystore(&mut rng)?;
+    net.procs[0] = elder;
+    let a_0 = net.procs[0].public_key();
+    net.force_join(a_0, a_0);
+    let voters = net.procs[0].voters.clone();
+    for proposal in [DummyProposal(1), DummyProposal(2)] {
+        decide_alone(&mut net, proposal)?;
+        net.procs[0].advance(voters.clone())?;
+    }
+
+    // a restored elder still serves the rounds it terminated and reports how they went
+    let mut persisted = net.procs[0].snapshot();
+    let secret_key: SecretKey = bincode::deserialize(&keystore)?;
+    let restored = HandoverState::restore(persisted.clone(), secret_key)?;
+    assert_eq!(restored.history, net.procs[0].history);
+    assert_eq!(restored.history.rounds.len(), 2);
+    assert_eq!(restored.history_stats(), net.procs[0].history_stats());
+    assert_eq!(restored.history_stats().rounds.len(), 2);
+
+    // deltas carry them too
+    decide_alone(&mut net, DummyProposal(3))?;
+    net.procs[0].advance(voters)?;
+    let delta = net.procs[0].snapshot_since(&persisted);
+    assert_eq!(delta.rounds.len(), 1);
+    assert_eq!(delta.stats.len(), 1);
+    persisted.apply(delta)?;
+    assert_eq!(persisted, net.procs[0].snapshot());
+    assert_eq!(persisted.history.rounds.len(), 3);
+    Ok(())
+}
+
 // #[quickcheck]
 // fn prop_validate_proposal(
 //     join_or_leave: bool,
@@ -0,0 +1,181 @@
+// test-env-log has been renamed to test-log, keep using it until we upgrade
+#![allow(deprecated)]
+
+use rand::{prelude::StdRng, SeedableRng};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+mod net;
+use net::DummyProposal;
+
+use test_env_log::test;
+
+use sn_handover::v1::{
+    Ballot, Error, HandoverState, PublicKey, SecretKey, SignedVote, Vote, VoteMsg,
+};
+
+#[test]
+fn test_v1_api_reaches_consensus() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let mut procs = Vec::from_iter(
+        (0..4).map(|_| HandoverState::<DummyProposal>::random(&mut rng, Default::default())),
+    );
+    let actors = Vec::from_iter(procs.iter().map(HandoverState::public_key));
+    for proc in procs.iter_mut() {
+        actors.iter().for_each(|actor| proc.force_join(*actor));
+    }
+
+    let mut msgs: VecDeque<VoteMsg<DummyProposal>> =
+        VecDeque::from(procs[0].propose(DummyProposal(1))?);
+    while let Some(msg) = msgs.pop_front() {
+        let dest = procs.iter_mut().find(|p| p.public_key() == msg.dest);
+        msgs.extend(dest.unwrap().handle_signed_vote(msg.vote)?);
+    }
+
+    for proc in procs {
+        assert_eq!(proc.consensus(), Some(DummyProposal(1)));
+        assert_eq!(proc.voters().len(), 4);
+        assert!(proc.secret_key().is_some());
+
+        // the core's votes still verify as the first release signed them
+        let votes = proc.votes();
+        assert_eq!(votes.len(), 4);
+        votes
+            .values()
+            .try_for_each(SignedVote::validate_signature)?;
+
+        // migrating means unwrapping the core state
+        let core = proc.into_inner();
+        assert!(core.decision()?.is_some());
+    }
+    Ok(())
+}
+
+// Signs as the first release did
+fn v1_vote(
+    key: &SecretKey,
+    ballot: Ballot<DummyProposal>,
+) -> eyre::Result<SignedVote<DummyProposal>> {
+    let vote = Vote { gen: 0, ballot };
+    let sig = key.sign(&vote.to_bytes()?);
+    Ok(SignedVote {
+        vote,
+        voter: key.public_key(),
+        sig,
+    })
+}
+
+#[test]
+fn test_nested_v1_votes_still_verify_once_converted() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let keys = Vec::from_iter((0..6).map(|_| SecretKey::random(&mut rng)));
+
+    // proposals ordered by their own `Ord` in v1, by the hash of their encoding in the core
+    let mut proposals = BTreeSet::new();
+    for (i, key) in keys.iter().enumerate() {
+        proposals.insert(v1_vote(key, Ballot::Propose(DummyProposal(i as u64)))?);
+    }
+    let mut merges = BTreeSet::new();
+    for key in keys.iter() {
+        merges.insert(v1_vote(key, Ballot::Merge(proposals.clone()))?);
+    }
+    let super_majority = v1_vote(&keys[0], Ballot::SuperMajority(merges))?;
+
+    let core = sn_handover::SignedVote::from(super_majority.clone());
+    let votes = core.unpack_votes();
+    assert_eq!(votes.len(), 13);
+    for vote in votes {
+        vote.validate_signature()?;
+    }
+    assert_eq!(SignedVote::try_from(core)?, super_majority);
+    Ok(())
+}
+
+// Signs `vote` and the votes nested in it again, as a first release node signed them
+fn resign(
+    vote: &SignedVote<DummyProposal>,
+    keys: &BTreeMap<PublicKey, SecretKey>,
+) -> eyre::Result<SignedVote<DummyProposal>> {
+    let resign_all = |votes: &BTreeSet<SignedVote<DummyProposal>>| {
+        votes
+            .iter()
+            .map(|vote| resign(vote, keys))
+            .collect::<eyre::Result<BTreeSet<_>>>()
+    };
+    let ballot = match &vote.vote.ballot {
+        Ballot::Propose(proposal) => Ballot::Propose(*proposal),
+        Ballot::Merge(votes) => Ballot::Merge(resign_all(votes)?),
+        Ballot::SuperMajority(votes) => Ballot::SuperMajority(resign_all(votes)?),
+    };
+    v1_vote(&keys[&vote.voter], ballot)
+}
+
+#[test]
+fn test_nested_votes_of_v1_peers_are_accepted_by_the_core() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let keys = BTreeMap::from_iter((0..4).map(|_| {
+        let key = SecretKey::random(&mut rng);
+        (key.public_key(), key)
+    }));
+    let voters = BTreeSet::from_iter(keys.keys().copied());
+    let copy = |key: &SecretKey| -> eyre::Result<SecretKey> {
+        Ok(bincode::deserialize(&bincode::serialize(key)?)?)
+    };
+    let mut peers = Vec::new();
+    for key in keys.values().take(3) {
+        peers.push(HandoverState::<DummyProposal>::from(
+            copy(key)?,
+            0,
+            voters.clone(),
+        ));
+    }
+    let core_key = keys.values().last().unwrap();
+    let mut core = sn_handover::HandoverState::<DummyProposal>::from(copy(core_key)?, 0, voters);
+
+    // every elder proposes its own, the round goes through merges and super majorities
+    let mut msgs = VecDeque::new();
+    for (i, peer) in peers.iter_mut().enumerate() {
+        msgs.extend(peer.propose(DummyProposal(i as u64))?);
+    }
+    for msg in core.propose(DummyProposal(3))? {
+        msgs.push_back(VoteMsg::try_from(msg)?);
+    }
+    let mut nested_from_peers = 0;
+    while let Some(msg) = msgs.pop_front() {
+        if msg.dest == core.public_key() {
+            // the core gets the votes as a first release node would have signed them
+            let vote = resign(&msg.vote, &keys)?;
+            if !matches!(vote.vote.ballot, Ballot::Propose(_)) {
+                nested_from_peers += 1;
+            }
+            for reply in core.handle_signed_vote(vote.into())?.msgs {
+                msgs.push_back(VoteMsg::try_from(reply)?);
+            }
+        } else if let Some(peer) = peers.iter_mut().find(|p| p.public_key() == msg.dest) {
+            msgs.extend(peer.handle_signed_vote(msg.vote)?);
+        }
+    }
+
+    assert!(nested_from_peers > 0);
+    assert!(core.consensus.is_some());
+    assert!(peers.iter().all(|peer| peer.consensus() == core.consensus));
+    Ok(())
+}
+
+#[test]
+fn test_conflicting_votes_are_refused_as_in_the_first_release() -> eyre::Result<()> {
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let keys = Vec::from_iter((0..4).map(|_| SecretKey::random(&mut rng)));
+    let mut proc = HandoverState::<DummyProposal>::random(&mut rng, Default::default());
+    keys.iter()
+        .for_each(|key| proc.force_join(key.public_key()));
+    proc.force_join(proc.public_key());
+
+    proc.handle_signed_vote(v1_vote(&keys[0], Ballot::Propose(DummyProposal(1)))?)?;
+    let conflicting = v1_vote(&keys[0], Ballot::Propose(DummyProposal(2)))?;
+    assert!(matches!(
+        proc.handle_signed_vote(conflicting),
+        Err(Error::ExistingVoteIncompatibleWithNewVote { .. })
+    ));
+    assert_eq!(proc.inner().faults().len(), 1);
+    Ok(())
+}